@@ -0,0 +1,44 @@
+// テキスト解析と長さ・音高の予測だけをchibivoxで行い、波形の生成は自前の実装に任せる
+// ここでは代わりの「decoder」として、f0に沿った正弦波を鳴らす
+// 別のボコーダやランタイムを組み込む場合も、Intermediatesから同じ値を受け取ればよい
+// cargo run --example custom_backend -- <MODEL_DIR> <TEXT>
+
+use anyhow::Result;
+use chibivox::{prelude::*, wav::SampleFormat, Intermediates};
+use std::{env, f32::consts::TAU, fs};
+
+// decoderの1フレームあたりのサンプル数
+const HOP_SIZE: usize = 256;
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let model_dir = args.next().unwrap_or_else(|| "model".into());
+    let text = args
+        .next()
+        .unwrap_or_else(|| "音の高さだけを鳴らします".into());
+    let speaker_id = 0;
+
+    let core = VoicevoxCore::new(&model_dir)?;
+    let options = core.default_options(speaker_id);
+    let intermediates = core.intermediates(&text, speaker_id, &options)?;
+    println!("phonemes: {}", intermediates.phonemes.join(" "));
+
+    let wav = sine_backend(&intermediates);
+    fs::write("custom_backend.wav", wav.to_wav(SampleFormat::I16))?;
+    println!("wrote custom_backend.wav ({:.2}s)", wav.duration());
+    Ok(())
+}
+
+// f0はフレームごとの対数周波数で、0は無声
+fn sine_backend(intermediates: &Intermediates) -> AudioBuffer {
+    let mut phase = 0f32;
+    let mut samples = Vec::with_capacity(intermediates.f0.len() * HOP_SIZE);
+    for &f0 in &intermediates.f0 {
+        let (frequency, amplitude) = if f0 > 0. { (f0.exp(), 0.3) } else { (0., 0.) };
+        for _ in 0..HOP_SIZE {
+            samples.push(amplitude * phase.sin());
+            phase = (phase + TAU * frequency / SAMPLING_RATE as f32) % TAU;
+        }
+    }
+    AudioBuffer::mono(samples, SAMPLING_RATE)
+}
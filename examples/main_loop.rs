@@ -0,0 +1,40 @@
+// スレッドを立てずに、GUIのメインループの合間に少しずつ合成する
+// 一回のstepはONNXの呼び出し一つ分までなので、その間も画面の更新や入力の処理を続けられる
+// cargo run --example main_loop -- <MODEL_DIR> <TEXT> | aplay
+
+use anyhow::Result;
+use chibivox::{
+    prelude::*,
+    wav::{SampleFormat, WavWriter},
+    Progress, Synthesis,
+};
+use std::{env, io, thread, time::Duration};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let model_dir = args.next().unwrap_or_else(|| "model".into());
+    let text = args.next().unwrap_or_else(|| {
+        "読点で息継ぎをするたびに、そこまでの音声が届きます、その間も画面は止まりません。".into()
+    });
+    let speaker_id = 0;
+
+    let core = VoicevoxCore::new(&model_dir)?;
+    let mut synthesis = Synthesis::new(&core, &text, speaker_id, &core.default_options(speaker_id));
+    let mut writer = WavWriter::new(io::stdout().lock(), SAMPLING_RATE, 1, SampleFormat::I16)?;
+    // 60fpsのつもりの、一コマごとの処理
+    for frame in 0.. {
+        match synthesis.step()? {
+            Progress::Working => {}
+            Progress::Audio(audio) => writer.write_samples(&audio.samples)?,
+            Progress::Done => break,
+        }
+        eprint!("\r{}", ['|', '/', '-', '\\'][frame % 4]);
+        thread::sleep(Duration::from_millis(16));
+    }
+    eprintln!();
+    for warning in synthesis.warnings().iter() {
+        eprintln!("warning: {warning}");
+    }
+    writer.into_inner()?;
+    Ok(())
+}
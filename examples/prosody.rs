@@ -0,0 +1,47 @@
+// AudioQueryを編集して抑揚や話速を変えてから合成する
+// cargo run --example prosody -- <MODEL_DIR>
+
+use anyhow::{Context, Result};
+use chibivox::{prelude::*, wav::SampleFormat};
+use std::{env, fs};
+
+fn main() -> Result<()> {
+    let model_dir = env::args().nth(1).unwrap_or_else(|| "model".into());
+    let speaker_id = 0;
+
+    let core = VoicevoxCore::new(&model_dir)?;
+    let mut audio_query = core.audio_query("今日はいい天気ですね", speaker_id)?;
+    println!("kana: {}", audio_query.kana.as_deref().unwrap_or_default());
+
+    // 最初のアクセント句のアクセント核を句末に移し、その句の音高だけを予測し直す
+    let first = audio_query
+        .accent_phrases
+        .first_mut()
+        .context("no accent phrases")?;
+    first.accent = first.moras.len();
+    audio_query.accent_phrases =
+        core.update_mora_pitch_for_phrase(audio_query.accent_phrases, 0, speaker_id)?;
+
+    // 最後のモーラを伸ばして少し上げる
+    let last = audio_query
+        .accent_phrases
+        .last_mut()
+        .and_then(|accent_phrase| accent_phrase.moras.last_mut())
+        .context("no moras")?;
+    last.vowel_length *= 1.5;
+    if last.pitch > 0. {
+        last.pitch += 0.2;
+    }
+
+    // 全体の話速と抑揚
+    audio_query.speed_scale = 0.9;
+    audio_query.intonation_scale = 1.3;
+
+    // 手で変えた値が合成できる範囲にあるかを先に確かめる
+    audio_query.validate()?;
+    let wav = core.synthesis(&audio_query, speaker_id)?;
+    fs::write("prosody.wav", wav.to_wav(SampleFormat::I16))?;
+    fs::write("prosody.json", serde_json::to_string_pretty(&audio_query)?)?;
+    println!("wrote prosody.wav and prosody.json");
+    Ok(())
+}
@@ -0,0 +1,58 @@
+// `chibivox serve` にテキストを送り、返ってきたWAVを保存する
+// HTTPクライアントのクレートを使わず、標準ライブラリだけでリクエストを組み立てる
+// cargo run -- serve & cargo run --example server_client -- 127.0.0.1:50021 <TEXT>
+
+use anyhow::{bail, Context, Result};
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:50021".into());
+    let text = args
+        .next()
+        .unwrap_or_else(|| "サーバーから合成しました。".into());
+    let speaker_id = 0;
+
+    let mut stream = TcpStream::connect(&address)?;
+    write!(
+        stream,
+        "POST /tts?speaker={speaker_id} HTTP/1.1\r\n\
+         Host: {address}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        text.len()
+    )?;
+    stream.write_all(text.as_bytes())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .context("malformed response")?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+
+    let mut lines = head.lines();
+    let status = lines.next().unwrap_or_default();
+    if !status.split(' ').nth(1).is_some_and(|code| code == "200") {
+        bail!("{status}: {}", String::from_utf8_lossy(body));
+    }
+    // 合成は成功したが気になる点があれば、X-Warningsに入っている
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("x-warnings") {
+                println!("warnings: {}", value.trim());
+            }
+        }
+    }
+
+    fs::write("server_client.wav", body)?;
+    println!("wrote server_client.wav ({} bytes)", body.len());
+    Ok(())
+}
@@ -0,0 +1,32 @@
+// 文ごとに合成したそばから、WAVとして標準出力に流す
+// 全体を合成し終わる前に再生を始められる
+// cargo run --example streaming -- <MODEL_DIR> <TEXT> | aplay
+
+use anyhow::Result;
+use chibivox::{
+    prelude::*,
+    wav::{SampleFormat, WavWriter},
+};
+use std::{env, io};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let model_dir = args.next().unwrap_or_else(|| "model".into());
+    let text = args.next().unwrap_or_else(|| {
+        "長い文章を読み上げるときは、文ごとに区切って合成します。最初の文ができた時点で再生が始まります。".into()
+    });
+    let speaker_id = 0;
+
+    let core = VoicevoxCore::new(&model_dir)?;
+    // 標準出力はSeekできないので、長さ不明のヘッダのまま流す
+    let mut writer = WavWriter::new(io::stdout().lock(), SAMPLING_RATE, 1, SampleFormat::I16)?;
+    for sentence in text.split_inclusive('。') {
+        if sentence.trim().is_empty() {
+            continue;
+        }
+        let wav = core.tts(sentence, speaker_id)?;
+        writer.write_samples(&wav.samples)?;
+    }
+    writer.into_inner()?;
+    Ok(())
+}
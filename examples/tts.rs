@@ -0,0 +1,29 @@
+// テキストを合成してWAVに書き出す、いちばん簡単な使い方
+// cargo run --example tts -- <MODEL_DIR> <TEXT>
+
+use anyhow::{Context, Result};
+use chibivox::{prelude::*, wav::SampleFormat};
+use std::{env, fs};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let model_dir = args.next().unwrap_or_else(|| "model".into());
+    let text = args
+        .next()
+        .unwrap_or_else(|| "こんにちは、音声合成の世界へようこそ。".into());
+
+    let core = VoicevoxCore::new(&model_dir)?;
+    // metas.jsonの最初のスタイルで読む
+    let speaker_id = core
+        .metas()
+        .iter()
+        .flat_map(|speaker| &speaker.styles)
+        .next()
+        .context("no styles in metas.json")?
+        .id;
+
+    let wav = core.tts(&text, speaker_id)?;
+    fs::write("tts.wav", wav.to_wav(SampleFormat::I16))?;
+    println!("wrote tts.wav ({:.2}s)", wav.duration());
+    Ok(())
+}
@@ -72,6 +72,10 @@ impl OjtPhoneme {
         "pau".into()
     }
 
+    pub fn contains(phoneme: &str) -> bool {
+        PHONEME_MAP.contains_key(phoneme)
+    }
+
     pub fn phoneme_id(&self) -> i64 {
         if self.phoneme.is_empty() {
             -1
@@ -0,0 +1,145 @@
+use crate::{
+    flac, pitch_shift,
+    wav::{self, SampleFormat},
+};
+
+// 合成結果の波形。サンプリングレートとチャンネル数を一緒に持ち運ぶ
+// 複数チャンネルの場合、samplesはフレームごとにインターリーブされている
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioBuffer {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioBuffer {
+    pub fn mono(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            channels: 1,
+        }
+    }
+
+    pub fn from_wav(bytes: &[u8]) -> anyhow::Result<Self> {
+        wav::decode_wav(bytes)
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    pub fn to_i16(&self) -> Vec<i16> {
+        self.samples
+            .iter()
+            .map(|sample| (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i16)
+            .collect()
+    }
+
+    pub fn to_wav(&self, format: SampleFormat) -> Vec<u8> {
+        wav::encode_wav(&self.samples, self.sample_rate, self.channels, format)
+    }
+
+    pub fn to_flac(&self) -> Vec<u8> {
+        flac::encode_flac(&self.samples, self.sample_rate, self.channels)
+    }
+
+    #[cfg(feature = "ogg")]
+    pub fn to_ogg(&self) -> anyhow::Result<Vec<u8>> {
+        crate::ogg::encode_ogg(&self.samples, self.sample_rate, self.channels)
+    }
+
+    // 各チャンネルの平均を取る
+    pub fn to_mono(&self) -> Self {
+        if self.channels == 1 {
+            return self.clone();
+        }
+        let channels = self.channels.max(1) as usize;
+        let samples = self
+            .samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Self::mono(samples, self.sample_rate)
+    }
+
+    pub fn to_stereo(&self) -> Self {
+        if self.channels == 2 {
+            return self.clone();
+        }
+        let samples = self
+            .to_mono()
+            .samples
+            .into_iter()
+            .flat_map(|sample| [sample, sample])
+            .collect();
+        Self {
+            samples,
+            sample_rate: self.sample_rate,
+            channels: 2,
+        }
+    }
+
+    // 線形補間による簡易的なリサンプリング
+    // ローパスフィルタを掛けていないので、大きく下げると折り返しが出る
+    pub fn resample(&self, sample_rate: u32) -> Self {
+        let frames = self.frames();
+        if sample_rate == self.sample_rate || frames == 0 {
+            return Self {
+                sample_rate,
+                ..self.clone()
+            };
+        }
+
+        let channels = self.channels.max(1) as usize;
+        let new_frames = (frames as u64 * sample_rate as u64 / self.sample_rate as u64) as usize;
+        let ratio = self.sample_rate as f64 / sample_rate as f64;
+        let mut samples = Vec::with_capacity(new_frames * channels);
+        for i in 0..new_frames {
+            let position = i as f64 * ratio;
+            let index = (position as usize).min(frames - 1);
+            let next = (index + 1).min(frames - 1);
+            let fraction = (position - index as f64) as f32;
+            for channel in 0..channels {
+                let current = self.samples[index * channels + channel];
+                let next = self.samples[next * channels + channel];
+                samples.push(current + (next - current) * fraction);
+            }
+        }
+
+        Self {
+            samples,
+            sample_rate,
+            channels: self.channels,
+        }
+    }
+
+    // モデルのpitch_scaleとは別に、波形の段階で半音単位で音高をずらす
+    // フォルマントは保たれるので、声の高さだけが変わる
+    pub fn pitch_shift(&self, semitones: f32) -> Self {
+        let channels = self.channels.max(1) as usize;
+        let shifted: Vec<Vec<f32>> = (0..channels)
+            .map(|channel| {
+                let samples: Vec<f32> = self
+                    .samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .copied()
+                    .collect();
+                pitch_shift::shift_pitch(&samples, self.sample_rate, semitones)
+            })
+            .collect();
+        let samples = (0..self.frames())
+            .flat_map(|i| shifted.iter().map(move |channel| channel[i]))
+            .collect();
+        Self {
+            samples,
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+
+const F0_FRAME_SIZE: usize = 1024;
+const F0_HOP_SIZE: usize = 256;
+const F0_MIN: f32 = 60.;
+const F0_MAX: f32 = 500.;
+const VOICED_THRESHOLD: f32 = 0.5;
+const SILENCE_THRESHOLD: f32 = 1e-4;
+const SPECTRUM_FRAME_SIZE: usize = 512;
+const SPECTRUM_HOP_SIZE: usize = 2048;
+
+// 外部ツールなしで外れ値を見つけるための簡易的な特徴量
+#[derive(Clone, Debug)]
+pub struct AudioFeatures {
+    pub duration: f32,
+    pub f0_mean: Option<f32>,
+    pub f0_p5: Option<f32>,
+    pub f0_p50: Option<f32>,
+    pub f0_p95: Option<f32>,
+    pub spectral_centroid: Option<f32>,
+}
+
+impl AudioFeatures {
+    pub fn extract(wave: &[f32], sampling_rate: u32) -> Self {
+        let mut f0_list = f0_by_autocorrelation(wave, sampling_rate);
+        f0_list.sort_by(f32::total_cmp);
+        let f0_mean =
+            (!f0_list.is_empty()).then(|| f0_list.iter().sum::<f32>() / f0_list.len() as f32);
+
+        Self {
+            duration: wave.len() as f32 / sampling_rate as f32,
+            f0_mean,
+            f0_p5: percentile(&f0_list, 0.05),
+            f0_p50: percentile(&f0_list, 0.5),
+            f0_p95: percentile(&f0_list, 0.95),
+            spectral_centroid: spectral_centroid(wave, sampling_rate),
+        }
+    }
+}
+
+fn frames(wave: &[f32], frame_size: usize, hop_size: usize) -> impl Iterator<Item = &[f32]> {
+    (0..wave.len().saturating_sub(frame_size))
+        .step_by(hop_size)
+        .map(move |start| &wave[start..start + frame_size])
+        .filter(|frame| energy(frame) > SILENCE_THRESHOLD)
+}
+
+fn energy(frame: &[f32]) -> f32 {
+    frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len() as f32
+}
+
+fn f0_by_autocorrelation(wave: &[f32], sampling_rate: u32) -> Vec<f32> {
+    let min_lag = (sampling_rate as f32 / F0_MAX) as usize;
+    let max_lag = (sampling_rate as f32 / F0_MIN) as usize;
+
+    frames(wave, F0_FRAME_SIZE, F0_HOP_SIZE)
+        .filter_map(|frame| {
+            let power: f32 = frame.iter().map(|sample| sample * sample).sum();
+            let (lag, correlation) = (min_lag..max_lag)
+                .map(|lag| {
+                    let correlation: f32 = frame[..frame.len() - lag]
+                        .iter()
+                        .zip(&frame[lag..])
+                        .map(|(a, b)| a * b)
+                        .sum();
+                    (lag, correlation / power)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+            (correlation > VOICED_THRESHOLD).then(|| sampling_rate as f32 / lag as f32)
+        })
+        .collect()
+}
+
+fn spectral_centroid(wave: &[f32], sampling_rate: u32) -> Option<f32> {
+    let centroids: Vec<f32> = frames(wave, SPECTRUM_FRAME_SIZE, SPECTRUM_HOP_SIZE)
+        .filter_map(|frame| {
+            let (weighted_sum, sum) = (1..SPECTRUM_FRAME_SIZE / 2)
+                .map(|k| {
+                    let (re, im) =
+                        frame
+                            .iter()
+                            .enumerate()
+                            .fold((0., 0.), |(re, im), (n, sample)| {
+                                let theta = 2. * PI * (k * n) as f32 / SPECTRUM_FRAME_SIZE as f32;
+                                (re + sample * theta.cos(), im - sample * theta.sin())
+                            });
+                    let frequency = k as f32 * sampling_rate as f32 / SPECTRUM_FRAME_SIZE as f32;
+                    let magnitude = (re * re + im * im).sqrt();
+                    (frequency * magnitude, magnitude)
+                })
+                .fold((0., 0.), |(a, b), (c, d)| (a + c, b + d));
+            (sum > 0.).then(|| weighted_sum / sum)
+        })
+        .collect();
+
+    (!centroids.is_empty()).then(|| centroids.iter().sum::<f32>() / centroids.len() as f32)
+}
+
+fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    Some(sorted[index])
+}
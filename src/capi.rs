@@ -0,0 +1,380 @@
+// VOICEVOX CORE 0.14と同じ形のC API。voicevox_core.hに合わせて組み込んだアプリが、差し替えるだけで使えるように
+// モデルは環境変数CHIBIVOX_MODEL_DIR(無ければ./model)から読む。初期化の設定で使うのはcpu_num_threadsとGPU指定の有無だけ
+// 辞書は同梱しているので、open_jtalk_dict_dirは使わない。予測を個別に呼ぶ関数は無い
+// 引数の約束(NULLでない、NUL終端など)はVOICEVOX CORE 0.14のヘッダーと同じ
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{
+    wav::SampleFormat, AudioQueryModel, InitializeOptions, InvalidAccentPhraseError,
+    TermsNotAcknowledgedError, UnknownSpeakerError, UnsupportedStyleError, VoicevoxCore,
+};
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr, CString},
+    path::PathBuf,
+    ptr,
+    sync::{Mutex, RwLock},
+};
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum VoicevoxResultCode {
+    VOICEVOX_RESULT_OK = 0,
+    VOICEVOX_RESULT_NOT_LOADED_OPENJTALK_DICT_ERROR = 1,
+    VOICEVOX_RESULT_LOAD_MODEL_ERROR = 2,
+    VOICEVOX_RESULT_GET_SUPPORTED_DEVICES_ERROR = 3,
+    VOICEVOX_RESULT_GPU_SUPPORT_ERROR = 4,
+    VOICEVOX_RESULT_LOAD_METAS_ERROR = 5,
+    VOICEVOX_RESULT_UNINITIALIZED_STATUS_ERROR = 6,
+    VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR = 7,
+    VOICEVOX_RESULT_INVALID_MODEL_INDEX_ERROR = 8,
+    VOICEVOX_RESULT_INFERENCE_ERROR = 9,
+    VOICEVOX_RESULT_EXTRACT_FULL_CONTEXT_LABEL_ERROR = 10,
+    VOICEVOX_RESULT_INVALID_UTF8_INPUT_ERROR = 11,
+    VOICEVOX_RESULT_PARSE_KANA_ERROR = 12,
+    VOICEVOX_RESULT_INVALID_AUDIO_QUERY_ERROR = 13,
+}
+
+use VoicevoxResultCode::*;
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum VoicevoxAccelerationMode {
+    VOICEVOX_ACCELERATION_MODE_AUTO = 0,
+    VOICEVOX_ACCELERATION_MODE_CPU = 1,
+    VOICEVOX_ACCELERATION_MODE_GPU = 2,
+}
+
+#[repr(C)]
+pub struct VoicevoxInitializeOptions {
+    pub acceleration_mode: VoicevoxAccelerationMode,
+    pub cpu_num_threads: u16,
+    pub load_all_models: bool,
+    pub open_jtalk_dict_dir: *const c_char,
+}
+
+#[repr(C)]
+pub struct VoicevoxAudioQueryOptions {
+    pub kana: bool,
+}
+
+#[repr(C)]
+pub struct VoicevoxSynthesisOptions {
+    pub enable_interrogative_upspeak: bool,
+}
+
+#[repr(C)]
+pub struct VoicevoxTtsOptions {
+    pub kana: bool,
+    pub enable_interrogative_upspeak: bool,
+}
+
+// C APIは状態を一つだけ持つ。合成は並行に呼べるように、読み込みと解放の間だけ書き込みで取る
+static CORE: RwLock<Option<VoicevoxCore>> = RwLock::new(None);
+// voicevox_get_metas_jsonが返す文字列。voicevox_finalizeまで有効
+static METAS_JSON: Mutex<Option<CString>> = Mutex::new(None);
+// voicevox_wav_freeには長さが渡されないので、渡したWAVを先頭のアドレスで覚えておく
+static WAVS: Mutex<Option<HashMap<usize, Box<[u8]>>>> = Mutex::new(None);
+
+type Result<T> = std::result::Result<T, VoicevoxResultCode>;
+
+fn result_code(result: Result<()>) -> VoicevoxResultCode {
+    result.err().unwrap_or(VOICEVOX_RESULT_OK)
+}
+
+// 合成の段階で起きたエラーを、VOICEVOX COREの結果コードに読み替える
+fn inference_error(err: anyhow::Error) -> VoicevoxResultCode {
+    if err.downcast_ref::<UnknownSpeakerError>().is_some()
+        || err.downcast_ref::<UnsupportedStyleError>().is_some()
+        || err.downcast_ref::<TermsNotAcknowledgedError>().is_some()
+    {
+        VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR
+    } else if err.downcast_ref::<InvalidAccentPhraseError>().is_some() {
+        VOICEVOX_RESULT_INVALID_AUDIO_QUERY_ERROR
+    } else {
+        VOICEVOX_RESULT_INFERENCE_ERROR
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str> {
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| VOICEVOX_RESULT_INVALID_UTF8_INPUT_ERROR)
+}
+
+fn with_core<T>(f: impl FnOnce(&VoicevoxCore) -> Result<T>) -> Result<T> {
+    let core = CORE.read().unwrap_or_else(|err| err.into_inner());
+    f(core
+        .as_ref()
+        .ok_or(VOICEVOX_RESULT_UNINITIALIZED_STATUS_ERROR)?)
+}
+
+// kanaならAquesTalk風記法として読み、話者の既定値でAudioQueryを作る
+fn audio_query(
+    core: &VoicevoxCore,
+    text: &str,
+    speaker_id: u32,
+    kana: bool,
+) -> Result<AudioQueryModel> {
+    let accent_phrases = if kana {
+        core.create_accent_phrases_from_kana(text)
+            .map_err(|_| VOICEVOX_RESULT_PARSE_KANA_ERROR)?
+    } else {
+        core.create_accent_phrases(text)
+            .map_err(|_| VOICEVOX_RESULT_EXTRACT_FULL_CONTEXT_LABEL_ERROR)?
+    };
+    let accent_phrases = core
+        .replace_mora_data(accent_phrases, speaker_id)
+        .map_err(inference_error)?;
+    Ok(core.audio_query_from_accent_phrases(accent_phrases, &core.default_options(speaker_id)))
+}
+
+// 語尾上げを切る場合は、synthコマンドと同じくdecode_onlyで合わせる
+fn synthesize_wav(
+    core: &VoicevoxCore,
+    audio_query: &AudioQueryModel,
+    speaker_id: u32,
+    enable_interrogative_upspeak: bool,
+) -> Result<Vec<u8>> {
+    let wav = if enable_interrogative_upspeak {
+        core.synthesis(audio_query, speaker_id)
+    } else {
+        core.synthesis_decode_only(audio_query, speaker_id)
+    }
+    .map_err(inference_error)?;
+    Ok(wav.to_wav(SampleFormat::I16))
+}
+
+unsafe fn write_wav(wav: Vec<u8>, output_wav_length: *mut usize, output_wav: *mut *mut u8) {
+    let mut wav = wav.into_boxed_slice();
+    let pointer = wav.as_mut_ptr();
+    *output_wav_length = wav.len();
+    *output_wav = pointer;
+    WAVS.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(pointer as usize, wav);
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_make_default_initialize_options() -> VoicevoxInitializeOptions {
+    VoicevoxInitializeOptions {
+        acceleration_mode: VoicevoxAccelerationMode::VOICEVOX_ACCELERATION_MODE_AUTO,
+        cpu_num_threads: 0,
+        load_all_models: false,
+        open_jtalk_dict_dir: ptr::null(),
+    }
+}
+
+// 既に読み込んでいれば、読み込み直す
+#[no_mangle]
+pub extern "C" fn voicevox_initialize(options: VoicevoxInitializeOptions) -> VoicevoxResultCode {
+    if options.acceleration_mode == VoicevoxAccelerationMode::VOICEVOX_ACCELERATION_MODE_GPU {
+        return VOICEVOX_RESULT_GPU_SUPPORT_ERROR;
+    }
+    let model_dir = std::env::var_os("CHIBIVOX_MODEL_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| "model".into());
+    let initialize_options = InitializeOptions {
+        cpu_num_threads: options.cpu_num_threads.into(),
+        acknowledge_terms: std::env::var("CHIBIVOX_ACKNOWLEDGE_TERMS")
+            .is_ok_and(|value| value == "true" || value == "1"),
+        ..Default::default()
+    };
+    let core = match VoicevoxCore::new_with_options(&model_dir, &initialize_options) {
+        Ok(core) => core,
+        Err(_) => return VOICEVOX_RESULT_LOAD_MODEL_ERROR,
+    };
+    let Ok(metas) = serde_json::to_string(core.metas()) else {
+        return VOICEVOX_RESULT_LOAD_METAS_ERROR;
+    };
+    *METAS_JSON.lock().unwrap_or_else(|err| err.into_inner()) = CString::new(metas).ok();
+    *CORE.write().unwrap_or_else(|err| err.into_inner()) = Some(core);
+    VOICEVOX_RESULT_OK
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_get_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+// モデルは話者によらず一つなので、voicevox_initializeで全て読み込み済み。話者が正しいかだけを確かめる
+#[no_mangle]
+pub extern "C" fn voicevox_load_model(speaker_id: u32) -> VoicevoxResultCode {
+    result_code(with_core(|core| {
+        if voicevox_is_model_loaded_impl(core, speaker_id) {
+            Ok(())
+        } else {
+            Err(VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR)
+        }
+    }))
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_is_gpu_mode() -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_is_model_loaded(speaker_id: u32) -> bool {
+    with_core(|core| Ok(voicevox_is_model_loaded_impl(core, speaker_id))).unwrap_or(false)
+}
+
+fn voicevox_is_model_loaded_impl(core: &VoicevoxCore, speaker_id: u32) -> bool {
+    core.metas()
+        .iter()
+        .flat_map(|speaker| &speaker.styles)
+        .any(|style| style.id == speaker_id)
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_finalize() {
+    *CORE.write().unwrap_or_else(|err| err.into_inner()) = None;
+    *METAS_JSON.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+// 読み込む前はNULL
+#[no_mangle]
+pub extern "C" fn voicevox_get_metas_json() -> *const c_char {
+    METAS_JSON
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+        .map_or(ptr::null(), |metas| metas.as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_get_supported_devices_json() -> *const c_char {
+    c"{\"cpu\":true,\"cuda\":false,\"dml\":false}".as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_make_default_audio_query_options() -> VoicevoxAudioQueryOptions {
+    VoicevoxAudioQueryOptions { kana: false }
+}
+
+// 結果はvoicevox_audio_query_json_freeで解放する
+#[no_mangle]
+pub unsafe extern "C" fn voicevox_audio_query(
+    text: *const c_char,
+    speaker_id: u32,
+    options: VoicevoxAudioQueryOptions,
+    output_audio_query_json: *mut *mut c_char,
+) -> VoicevoxResultCode {
+    result_code((|| {
+        let text = to_str(text)?;
+        let audio_query = with_core(|core| audio_query(core, text, speaker_id, options.kana))?;
+        let json =
+            serde_json::to_string(&audio_query).map_err(|_| VOICEVOX_RESULT_INFERENCE_ERROR)?;
+        *output_audio_query_json = CString::new(json).unwrap().into_raw();
+        Ok(())
+    })())
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_make_default_synthesis_options() -> VoicevoxSynthesisOptions {
+    VoicevoxSynthesisOptions {
+        enable_interrogative_upspeak: true,
+    }
+}
+
+// 結果はvoicevox_wav_freeで解放する
+#[no_mangle]
+pub unsafe extern "C" fn voicevox_synthesis(
+    audio_query_json: *const c_char,
+    speaker_id: u32,
+    options: VoicevoxSynthesisOptions,
+    output_wav_length: *mut usize,
+    output_wav: *mut *mut u8,
+) -> VoicevoxResultCode {
+    result_code((|| {
+        let audio_query: AudioQueryModel = serde_json::from_str(to_str(audio_query_json)?)
+            .map_err(|_| VOICEVOX_RESULT_INVALID_AUDIO_QUERY_ERROR)?;
+        audio_query
+            .validate()
+            .map_err(|_| VOICEVOX_RESULT_INVALID_AUDIO_QUERY_ERROR)?;
+        let wav = with_core(|core| {
+            synthesize_wav(
+                core,
+                &audio_query,
+                speaker_id,
+                options.enable_interrogative_upspeak,
+            )
+        })?;
+        write_wav(wav, output_wav_length, output_wav);
+        Ok(())
+    })())
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_make_default_tts_options() -> VoicevoxTtsOptions {
+    VoicevoxTtsOptions {
+        kana: false,
+        enable_interrogative_upspeak: true,
+    }
+}
+
+// 結果はvoicevox_wav_freeで解放する
+#[no_mangle]
+pub unsafe extern "C" fn voicevox_tts(
+    text: *const c_char,
+    speaker_id: u32,
+    options: VoicevoxTtsOptions,
+    output_wav_length: *mut usize,
+    output_wav: *mut *mut u8,
+) -> VoicevoxResultCode {
+    result_code((|| {
+        let text = to_str(text)?;
+        let wav = with_core(|core| {
+            let audio_query = audio_query(core, text, speaker_id, options.kana)?;
+            synthesize_wav(
+                core,
+                &audio_query,
+                speaker_id,
+                options.enable_interrogative_upspeak,
+            )
+        })?;
+        write_wav(wav, output_wav_length, output_wav);
+        Ok(())
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn voicevox_audio_query_json_free(audio_query_json: *mut c_char) {
+    if !audio_query_json.is_null() {
+        drop(CString::from_raw(audio_query_json));
+    }
+}
+
+// このライブラリが渡したもの以外は無視する
+#[no_mangle]
+pub extern "C" fn voicevox_wav_free(wav: *mut u8) {
+    if let Some(wavs) = WAVS.lock().unwrap_or_else(|err| err.into_inner()).as_mut() {
+        wavs.remove(&(wav as usize));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn voicevox_error_result_to_message(
+    result_code: VoicevoxResultCode,
+) -> *const c_char {
+    let message = match result_code {
+        VOICEVOX_RESULT_OK => c"no error",
+        VOICEVOX_RESULT_NOT_LOADED_OPENJTALK_DICT_ERROR => c"dictionary is not loaded",
+        VOICEVOX_RESULT_LOAD_MODEL_ERROR => c"failed to load the models",
+        VOICEVOX_RESULT_GET_SUPPORTED_DEVICES_ERROR => c"failed to get the supported devices",
+        VOICEVOX_RESULT_GPU_SUPPORT_ERROR => c"GPU is not supported",
+        VOICEVOX_RESULT_LOAD_METAS_ERROR => c"failed to load metas.json",
+        VOICEVOX_RESULT_UNINITIALIZED_STATUS_ERROR => c"not initialized",
+        VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR => c"invalid speaker id",
+        VOICEVOX_RESULT_INVALID_MODEL_INDEX_ERROR => c"invalid model index",
+        VOICEVOX_RESULT_INFERENCE_ERROR => c"inference failed",
+        VOICEVOX_RESULT_EXTRACT_FULL_CONTEXT_LABEL_ERROR => c"text analysis failed",
+        VOICEVOX_RESULT_INVALID_UTF8_INPUT_ERROR => c"input is not valid UTF-8",
+        VOICEVOX_RESULT_PARSE_KANA_ERROR => c"invalid kana",
+        VOICEVOX_RESULT_INVALID_AUDIO_QUERY_ERROR => c"invalid AudioQuery",
+    };
+    message.as_ptr()
+}
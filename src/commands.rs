@@ -0,0 +1,518 @@
+pub mod ab;
+pub mod bench;
+pub mod config;
+#[cfg(all(unix, feature = "server"))]
+pub mod ctl;
+#[cfg(unix)]
+pub mod daemon;
+pub mod errors;
+pub mod eval;
+pub mod i18n;
+pub mod journal;
+pub mod list_speakers;
+pub mod output;
+pub mod presets;
+pub mod progress;
+pub mod query;
+pub mod read;
+pub mod rerender;
+pub mod rpc;
+pub mod script;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod stats;
+pub mod summary;
+pub mod synth;
+pub mod terms;
+#[cfg(feature = "unstable")]
+pub mod voice_change;
+pub mod watch;
+pub mod watermark;
+
+use anyhow::{anyhow, bail, Result};
+use chibivox::{
+    available_cpus, default_worker_count,
+    wav::{self, SampleFormat, WavWriter},
+    AudioBuffer, InitializeOptions, ProsodySmoother, SynthesisOptions, VoicevoxCore, Warnings,
+};
+use errors::{AtStage, Stage};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// 評価やベンチマークに使う例文
+pub const SAMPLE_SENTENCES: &str = include_str!("resources/eval_sentences.txt");
+
+// speaker=0,speed=1.0 の形式で与える合成パラメータ
+#[derive(Clone, Default)]
+pub struct Params {
+    pub speaker_id: u32,
+    pub options: SynthesisOptions,
+    // 合成後に波形の段階でずらす半音の数
+    pub pitch_shift: f32,
+}
+
+impl Params {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut params = Self::default();
+        for pair in s.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or(anyhow!("invalid params: {pair}"))?;
+            match key {
+                "speaker" => params.speaker_id = value.parse()?,
+                "speed" => params.options.speed_scale = value.parse()?,
+                "pitch" => params.options.pitch_scale = value.parse()?,
+                "intonation" => params.options.intonation_scale = value.parse()?,
+                "volume" => params.options.volume_scale = value.parse()?,
+                "pre" => params.options.pre_phoneme_length = value.parse()?,
+                "post" => params.options.post_phoneme_length = value.parse()?,
+                "upspeak" => params.options.enable_interrogative_upspeak = value.parse()?,
+                "shift" => params.pitch_shift = value.parse()?,
+                _ => bail!("unknown param: {key}"),
+            }
+        }
+        Ok(params)
+    }
+}
+
+// 指定されなかったものは、スタイルごとの既定値(無ければ全体の既定値)になる
+#[derive(clap::Args)]
+pub struct ProsodyArgs {
+    /// Preset saved in presets.toml [env: CHIBIVOX_PRESETS] that picks the speaker in place
+    /// of --speaker and the defaults of the flags below
+    #[arg(long, value_parser = presets::parse)]
+    preset: Option<presets::Preset>,
+    /// Speaking speed scale [default: style default, otherwise 1]
+    #[arg(long, value_parser = ranged(0.5, 2.))]
+    speed: Option<f32>,
+    /// Pitch shift (in log2 of f0) [default: style default, otherwise 0]
+    #[arg(
+        long,
+        value_parser = ranged(-0.15, 0.15),
+        allow_negative_numbers = true
+    )]
+    pitch: Option<f32>,
+    /// Intonation scale [default: style default, otherwise 1]
+    #[arg(long, value_parser = ranged(0., 2.))]
+    intonation: Option<f32>,
+    /// Volume scale [default: style default, otherwise 1]
+    #[arg(long, value_parser = ranged(0., 2.))]
+    volume: Option<f32>,
+}
+
+impl ProsodyArgs {
+    // --presetがあれば、その話者
+    pub fn speaker(&self, speaker_id: u32) -> u32 {
+        self.preset
+            .as_ref()
+            .map_or(speaker_id, |preset| preset.style_id)
+    }
+
+    pub fn to_options(&self, core: &VoicevoxCore, speaker_id: u32) -> SynthesisOptions {
+        let mut defaults = core.default_options(speaker_id);
+        if let Some(preset) = &self.preset {
+            preset.apply(&mut defaults);
+        }
+        SynthesisOptions {
+            speed_scale: self.speed.unwrap_or(defaults.speed_scale),
+            pitch_scale: self.pitch.unwrap_or(defaults.pitch_scale),
+            intonation_scale: self.intonation.unwrap_or(defaults.intonation_scale),
+            volume_scale: self.volume.unwrap_or(defaults.volume_scale),
+            ..defaults
+        }
+    }
+}
+
+fn ranged(min: f32, max: f32) -> impl Fn(&str) -> Result<f32, String> + Clone {
+    move |s| {
+        let value: f32 = s.parse().map_err(|err| format!("{err}"))?;
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(format!("must be between {min} and {max}"))
+        }
+    }
+}
+
+// 10s、10m、1hのような時間。単位が無ければ秒
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60.,
+        "h" => value * 3600.,
+        _ => return Err(format!("unknown unit: {unit} (expected s, m or h)")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("{err}"))
+}
+
+// --jobsと--cpu-num-threadsから、並行に合成する数を決める
+// 両者の積がCPU数を超えないように、ORTのスレッド数が自動ならジョブの数に合わせて減らす
+pub fn resolve_jobs(jobs: usize, options: &mut InitializeOptions) -> usize {
+    if jobs == 1 {
+        return 1;
+    }
+    if options.cpu_num_threads == 0 && !options.deterministic {
+        let jobs = if jobs == 0 { available_cpus() } else { jobs };
+        options.cpu_num_threads = (available_cpus() / jobs).max(1);
+        return jobs;
+    }
+    if jobs == 0 {
+        default_worker_count(options.intra_threads())
+    } else {
+        jobs
+    }
+}
+
+// 0..countをjobs個のスレッドで分け合って処理し、結果を添字の順に返す
+// VoicevoxCoreは共有したまま、各スレッドから同時にセッションを実行する
+pub fn parallel_map<R: Send>(count: usize, jobs: usize, f: impl Fn(usize) -> R + Sync) -> Vec<R> {
+    if jobs <= 1 || count <= 1 {
+        return (0..count).map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(count))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= count {
+                            break results;
+                        }
+                        results.push((i, f(i)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// --cache-optimized-models。起動時に一度だけ設定する
+static CACHE_OPTIMIZED_MODELS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_cache_optimized_models(enabled: bool) {
+    CACHE_OPTIMIZED_MODELS.store(enabled, Ordering::Relaxed);
+}
+
+// コマンドによらないオプションを、読み込むモデルに伝える
+pub fn initialize_options(options: &InitializeOptions) -> InitializeOptions {
+    InitializeOptions {
+        cache_optimized_models: CACHE_OPTIMIZED_MODELS.load(Ordering::Relaxed),
+        ..terms::initialize_options(options)
+    }
+}
+
+pub fn load_core(model_dir: &Path, options: &InitializeOptions) -> Result<VoicevoxCore> {
+    let core = VoicevoxCore::new_with_options(model_dir, &initialize_options(options))
+        .at_stage(Stage::Load, &model_dir.display().to_string())?;
+    warn_rule_based(&core, model_dir);
+    Ok(core)
+}
+
+// 予測モデルが足りず規則で代用している場合は、品質が落ちることを知らせる
+pub fn warn_rule_based(core: &VoicevoxCore, model_dir: &Path) {
+    let model_dir = model_dir.display();
+    if core.is_rule_based_duration() {
+        let message = i18n::pick(
+            format!("no duration model in {model_dir}, falling back to rule-based lengths (lower quality)"),
+            format!("{model_dir} に音素長の予測モデルが無いため、規則で長さを決めます(品質が下がります)"),
+        );
+        eprintln!("warning: {message}");
+    }
+    if core.is_rule_based_intonation() {
+        let message = i18n::pick(
+            format!("no intonation model in {model_dir}, falling back to rule-based pitch (lower quality)"),
+            format!("{model_dir} に抑揚の予測モデルが無いため、規則で音高を決めます(品質が下がります)"),
+        );
+        eprintln!("warning: {message}");
+    }
+}
+
+// 出力の形式。指定が無ければ出力先の拡張子から決め、それでも分からなければWAVにする
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Wav,
+    Flac,
+    /// Ogg Vorbis (requires the `ogg` feature)
+    Ogg,
+    /// Headerless 32-bit float little-endian PCM
+    Raw,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "wav" => Some(Self::Wav),
+            "flac" => Some(Self::Flac),
+            "ogg" | "oga" => Some(Self::Ogg),
+            "raw" | "pcm" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+            Self::Ogg => "ogg",
+            Self::Raw => "raw",
+        }
+    }
+}
+
+// 一つの入力の合成の結果。警告と経過時間は表示せずに返す
+// 並行に合成しても、呼び出し側が入力の順に表示できるように
+pub struct Synthesis {
+    pub wav: AudioBuffer,
+    pub warnings: Warnings,
+    pub timings: summary::Timings,
+}
+
+// 合成してpathに書き出す。save_queryなら実際に使ったAudioQueryを同名の.jsonに、
+// dump_intermediatesなら合成の途中の値を同名の.intermediates.jsonに書き出す
+pub fn render(
+    core: &VoicevoxCore,
+    text: &str,
+    params: &Params,
+    path: &Path,
+    format: Option<OutputFormat>,
+    save_query: bool,
+    dump_intermediates: bool,
+) -> Result<Synthesis> {
+    let query_path = save_query.then(|| path.with_extension("json"));
+    let intermediates_path = dump_intermediates.then(|| path.with_extension("intermediates.json"));
+    let synthesis = synthesize(
+        core,
+        text,
+        params,
+        query_path.as_deref(),
+        intermediates_path.as_deref(),
+    )?;
+
+    let credit = terms::credit(core, params.speaker_id);
+    write_audio(path, &synthesis.wav, format, credit)?;
+    Ok(synthesis)
+}
+
+// query_pathがあれば合成に使ったAudioQueryを、intermediates_pathがあれば途中の値をそこに書き出す
+pub fn synthesize(
+    core: &VoicevoxCore,
+    text: &str,
+    params: &Params,
+    query_path: Option<&Path>,
+    intermediates_path: Option<&Path>,
+) -> Result<Synthesis> {
+    synthesize_smoothed(core, text, params, query_path, intermediates_path, None)
+}
+
+// smootherがあれば、前に合成した文に声の高さと速さを寄せてから合成する。入力の順に呼ぶこと
+// 並行に呼んでも推論は塞がないように、smootherは寄せる間だけ取る
+pub fn synthesize_smoothed(
+    core: &VoicevoxCore,
+    text: &str,
+    params: &Params,
+    query_path: Option<&Path>,
+    intermediates_path: Option<&Path>,
+    smoother: Option<&Mutex<ProsodySmoother>>,
+) -> Result<Synthesis> {
+    let Params {
+        speaker_id,
+        options,
+        pitch_shift,
+    } = params;
+    if let Some(intermediates_path) = intermediates_path {
+        let intermediates = core
+            .intermediates(text, *speaker_id, options)
+            .at_stage(Stage::Analysis, text)?;
+        fs::write(
+            intermediates_path,
+            serde_json::to_string_pretty(&intermediates)?,
+        )?;
+    }
+
+    // --statsで段階ごとの時間を出せるように、一段ずつ進める
+    let mut lap = Lap::new();
+    let mut warnings = Warnings::new();
+    let accent_phrases = core
+        .create_accent_phrases_with_warnings(text, &mut warnings)
+        .at_stage(Stage::Analysis, text)?;
+    let analysis = lap.split();
+    let accent_phrases = core
+        .replace_mora_data_with_warnings(accent_phrases, *speaker_id, &mut warnings)
+        .at_stage(Stage::Prediction, text)?;
+    let prediction = lap.split();
+    let mut audio_query = core.audio_query_from_accent_phrases(accent_phrases, options);
+    if let Some(smoother) = smoother {
+        smoother
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .apply(&mut audio_query);
+    }
+    if let Some(query_path) = query_path {
+        fs::write(query_path, serde_json::to_string_pretty(&audio_query)?)?;
+    }
+    // AudioQueryには語尾上げの指定が無いので、切る場合はdecode_onlyで合わせる
+    let wav = if options.enable_interrogative_upspeak {
+        core.synthesis(&audio_query, *speaker_id)
+    } else {
+        core.synthesis_decode_only(&audio_query, *speaker_id)
+    }
+    .at_stage(Stage::Decode, text)?;
+    let decode = lap.split();
+
+    let wav = wav.pitch_shift(*pitch_shift);
+    warnings.check_clipping(&wav);
+    stats::record(*speaker_id, &wav);
+    let timings = summary::Timings {
+        analysis,
+        prediction,
+        decode,
+    };
+    summary::record(text, &audio_query, &wav, &timings);
+    Ok(Synthesis {
+        wav,
+        warnings,
+        timings,
+    })
+}
+
+pub struct Lap(Instant);
+
+impl Lap {
+    pub fn new() -> Self {
+        Self(Instant::now())
+    }
+
+    // 前回からの経過時間を返して計測し直す
+    pub fn split(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.0;
+        self.0 = now;
+        elapsed
+    }
+}
+
+// 出力先がディレクトリならその中に連番で、複数の入力をファイル名に出力するなら末尾に番号を付ける
+// extensionはファイル名をこちらで決めるときの拡張子
+pub fn output_paths(output: &Path, count: usize, extension: &str) -> Result<Vec<PathBuf>> {
+    if count == 1 && !is_dir_path(output) {
+        return Ok(vec![output.to_path_buf()]);
+    }
+    (1..=count)
+        .map(|i| numbered_path(output, i, extension))
+        .collect()
+}
+
+pub fn numbered_path(output: &Path, i: usize, extension: &str) -> Result<PathBuf> {
+    if is_dir_path(output) {
+        fs::create_dir_all(output)?;
+        return Ok(output.join(format!("{i:03}.{extension}")));
+    }
+
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audio".into());
+    let extension = output
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| extension.into());
+    Ok(output.with_file_name(format!("{stem}_{i:03}.{extension}")))
+}
+
+fn is_dir_path(path: &Path) -> bool {
+    path.is_dir() || path.to_string_lossy().ends_with(std::path::is_separator)
+}
+
+// creditはWAVの場合のみ、LIST/INFOチャンクに書く
+pub fn write_audio(
+    path: &Path,
+    wav: &AudioBuffer,
+    format: Option<OutputFormat>,
+    credit: Option<&str>,
+) -> Result<()> {
+    let format = format
+        .or_else(|| OutputFormat::from_path(path))
+        .unwrap_or(OutputFormat::Wav);
+    let wav = &*watermark::apply(wav);
+    match format {
+        OutputFormat::Wav => save_wav(path, wav, credit)?,
+        OutputFormat::Flac => fs::write(path, wav.to_flac())?,
+        #[cfg(feature = "ogg")]
+        OutputFormat::Ogg => fs::write(path, wav.to_ogg()?)?,
+        #[cfg(not(feature = "ogg"))]
+        OutputFormat::Ogg => bail!("ogg output is not available: rebuild with --features ogg"),
+        OutputFormat::Raw => fs::write(path, wav::encode_samples(&wav.samples, SampleFormat::F32))?,
+    }
+    Ok(())
+}
+
+pub fn write_wav(path: impl AsRef<Path>, wav: &AudioBuffer, credit: Option<&str>) -> Result<()> {
+    save_wav(path, &watermark::apply(wav), credit)
+}
+
+fn save_wav(path: impl AsRef<Path>, wav: &AudioBuffer, credit: Option<&str>) -> Result<()> {
+    // 保存
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = WavWriter::new(file, wav.sample_rate, wav.channels, SampleFormat::F32)?;
+    writer.write_samples(&wav.samples)?;
+    writer.finalize_with_chunk(&credit.map(wav::info_chunk).unwrap_or_default())?;
+
+    Ok(())
+}
+
+// 既定の出力デバイスで再生し、鳴り終わるまで待つ
+#[cfg(feature = "play")]
+pub fn play(wav: &AudioBuffer) -> Result<()> {
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    sink.append(rodio::buffer::SamplesBuffer::new(
+        wav.channels,
+        wav.sample_rate,
+        wav.samples.clone(),
+    ));
+    sink.sleep_until_end();
+    Ok(())
+}
+
+#[cfg(not(feature = "play"))]
+pub fn play(_wav: &AudioBuffer) -> Result<()> {
+    bail!("playback is not available: rebuild with --features play")
+}
+
+pub fn find_files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_files(&path, extension)?);
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
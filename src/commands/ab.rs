@@ -0,0 +1,84 @@
+use super::{load_core, output::Record, render, terms, write_wav, Params};
+use anyhow::Result;
+use chibivox::{AudioBuffer, InitializeOptions, SAMPLING_RATE};
+use std::{
+    f32::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Text to synthesize
+    text: String,
+    /// Parameters for A, e.g. speaker=0,speed=1.0
+    #[arg(long, value_parser = Params::parse, default_value = "")]
+    params_a: Params,
+    /// Parameters for B, e.g. speaker=1,pitch=0.05 (shift=N shifts the waveform by N semitones)
+    #[arg(long, value_parser = Params::parse, default_value = "")]
+    params_b: Params,
+    /// Also write the AudioQuery actually used as a .json next to each WAV
+    #[arg(long)]
+    save_query: bool,
+    /// Output directory for audio_a.wav, audio_b.wav and audio_ab.wav
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    fs::create_dir_all(&args.output)?;
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let path_a = args.output.join("audio_a.wav");
+    let a = render(
+        &core,
+        &args.text,
+        &args.params_a,
+        &path_a,
+        None,
+        args.save_query,
+        false,
+    )?;
+    let path_b = args.output.join("audio_b.wav");
+    let b = render(
+        &core,
+        &args.text,
+        &args.params_b,
+        &path_b,
+        None,
+        args.save_query,
+        false,
+    )?;
+
+    Record::new("A", &path_a, &a).print()?;
+    Record::new("B", &path_b, &b).print()?;
+
+    // A、区切りの音、Bの順に並べる
+    let wav_ab = a
+        .wav
+        .samples
+        .into_iter()
+        .chain(separator_tone())
+        .chain(b.wav.samples)
+        .collect();
+    let wav_ab = AudioBuffer::mono(wav_ab, SAMPLING_RATE);
+
+    // A、Bで話者が違えば両方のクレジットを並べる
+    let mut credits: Vec<&str> = [&args.params_a, &args.params_b]
+        .into_iter()
+        .filter_map(|params| terms::credit(&core, params.speaker_id))
+        .collect();
+    credits.dedup();
+    let credit = (!credits.is_empty()).then(|| credits.join(", "));
+    write_wav(args.output.join("audio_ab.wav"), &wav_ab, credit.as_deref())
+}
+
+fn separator_tone() -> Vec<f32> {
+    const FREQUENCY: f32 = 1000.;
+    const AMPLITUDE: f32 = 0.2;
+
+    let size = SAMPLING_RATE as usize / 4;
+    let silence = std::iter::repeat(0.).take(size);
+    let beep = (0..size)
+        .map(|i| (2. * PI * FREQUENCY * i as f32 / SAMPLING_RATE as f32).sin() * AMPLITUDE);
+    silence.clone().chain(beep).chain(silence).collect()
+}
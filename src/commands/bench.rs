@@ -0,0 +1,164 @@
+use super::{load_core, output, Lap, SAMPLE_SENTENCES};
+use anyhow::{bail, Result};
+use chibivox::{AudioQueryModel, InitializeOptions, SynthesisOptions, SAMPLING_RATE};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const STAGES: [&str; 4] = ["analysis", "duration", "intonation", "decode"];
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Speaker id
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    /// Number of measured passes over the corpus
+    #[arg(short = 'n', long, default_value_t = 5)]
+    iterations: usize,
+    /// Unmeasured passes to run first
+    #[arg(long, default_value_t = 1)]
+    warmup: usize,
+    /// Corpus with one sentence per line (defaults to the built-in sample sentences)
+    #[arg(long)]
+    text_file: Option<PathBuf>,
+    /// Pin ONNX Runtime threading as in `synth --deterministic`
+    #[arg(long)]
+    deterministic: bool,
+    /// ONNX Runtime intra-op threads (0 = available CPUs)
+    #[arg(long, default_value_t = 0)]
+    cpu_num_threads: usize,
+}
+
+// 段階ごとに時間を測り、平均とパーセンタイルを表にする
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let initialize_options = InitializeOptions {
+        deterministic: args.deterministic,
+        cpu_num_threads: args.cpu_num_threads,
+        ..Default::default()
+    };
+    let core = load_core(model_dir, &initialize_options)?;
+
+    let corpus = match &args.text_file {
+        Some(text_file) => fs::read_to_string(text_file)?,
+        None => SAMPLE_SENTENCES.to_string(),
+    };
+    let sentences: Vec<&str> = corpus
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if sentences.is_empty() {
+        bail!("corpus is empty");
+    }
+
+    let options = SynthesisOptions::default();
+    let mut timings: [Vec<Duration>; 4] = Default::default();
+    let mut audio_duration = 0.;
+    for iteration in 0..args.warmup + args.iterations {
+        for text in &sentences {
+            let mut lap = Lap::new();
+            let accent_phrases = core.create_accent_phrases(text)?;
+            let analysis = lap.split();
+            let accent_phrases = core.replace_phoneme_length(accent_phrases, args.speaker)?;
+            let duration = lap.split();
+            let accent_phrases = core.replace_mora_pitch(accent_phrases, args.speaker)?;
+            let intonation = lap.split();
+            let audio_query = AudioQueryModel {
+                accent_phrases,
+                speed_scale: options.speed_scale,
+                pitch_scale: options.pitch_scale,
+                intonation_scale: options.intonation_scale,
+                volume_scale: options.volume_scale,
+                pre_phoneme_length: options.pre_phoneme_length,
+                post_phoneme_length: options.post_phoneme_length,
+                output_sampling_rate: SAMPLING_RATE,
+                output_stereo: false,
+                kana: None,
+            };
+            let wav = core.synthesis(&audio_query, args.speaker)?;
+            let decode = lap.split();
+
+            if iteration >= args.warmup {
+                for (timing, elapsed) in timings
+                    .iter_mut()
+                    .zip([analysis, duration, intonation, decode])
+                {
+                    timing.push(elapsed);
+                }
+                audio_duration += wav.duration();
+            }
+        }
+    }
+
+    let total: Duration = timings.iter().flatten().sum();
+    let realtime_factor = (audio_duration > 0.).then(|| total.as_secs_f32() / audio_duration);
+    // 平均、p50、p90、p99
+    let summaries: Vec<[Duration; 4]> = timings
+        .iter_mut()
+        .map(|timing| {
+            timing.sort();
+            let mean = timing.iter().sum::<Duration>() / timing.len().max(1) as u32;
+            [
+                mean,
+                percentile(timing, 0.5),
+                percentile(timing, 0.9),
+                percentile(timing, 0.99),
+            ]
+        })
+        .collect();
+
+    if output::json() {
+        // 時間は秒で書く
+        let stages: serde_json::Map<_, _> = STAGES
+            .iter()
+            .zip(&summaries)
+            .map(|(stage, [mean, p50, p90, p99])| {
+                let value = serde_json::json!({
+                    "mean": mean.as_secs_f64(),
+                    "p50": p50.as_secs_f64(),
+                    "p90": p90.as_secs_f64(),
+                    "p99": p99.as_secs_f64(),
+                });
+                (stage.to_string(), value)
+            })
+            .collect();
+        return output::emit(&serde_json::json!({
+            "sentences": sentences.len(),
+            "iterations": args.iterations,
+            "stages": stages,
+            "realtime_factor": realtime_factor,
+        }));
+    }
+
+    println!(
+        "{} sentences x {} iterations",
+        sentences.len(),
+        args.iterations
+    );
+    println!(
+        "{:<12}{:>10}{:>10}{:>10}{:>10}",
+        "stage", "mean", "p50", "p90", "p99"
+    );
+    for (stage, summary) in STAGES.iter().zip(&summaries) {
+        let [mean, p50, p90, p99] = summary.map(millis);
+        println!("{stage:<12}{mean:>10}{p50:>10}{p90:>10}{p99:>10}");
+    }
+    if let Some(realtime_factor) = realtime_factor {
+        println!("real-time factor: {realtime_factor:.3}");
+    }
+
+    Ok(())
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.)
+}
@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use clap::Command;
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// 毎回同じフラグを並べなくて済むように、設定ファイルからフラグの既定値を読む
+// 最上位のキーは同じ名前の引数を持つ全てのサブコマンドに、[serve]などの表はそのサブコマンドだけに効く
+// コマンドラインで指定すれば、そちらが優先される
+pub struct Config {
+    // 表の名前(最上位は空文字列)ごとの、引数のidと値
+    tables: BTreeMap<String, Vec<(String, String)>>,
+}
+
+// CHIBIVOX_CONFIG、なければ$XDG_CONFIG_HOME/chibivox/config.toml、~/.config/chibivox/config.toml
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CHIBIVOX_CONFIG") {
+        return Some(path.into());
+    }
+    Some(dir()?.join("config.toml"))
+}
+
+// $XDG_CONFIG_HOME/chibivox、なければ~/.config/chibivox
+pub fn dir() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) => PathBuf::from(config_home),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("chibivox"))
+}
+
+// 設定ファイルを読めなかったこと。終了コードを分けられるように型を持たせる
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "config: {}", self.path.display())
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl Config {
+    // ファイルが無ければNone
+    pub fn load(path: &Path) -> Result<Option<Self>, ConfigError> {
+        let config_error = |source| ConfigError {
+            path: path.into(),
+            source,
+        };
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(config_error(err.into())),
+        };
+        Self::parse(&source).map_err(config_error).map(Some)
+    }
+
+    // TOMLのうち、表の見出しと、値が文字列・数値・真偽値の「キー = 値」だけを読む
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut tables: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+        let mut table = String::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                // ["ゆっくり"]のように、引用符で囲んだ名前も使える
+                table = match name
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|name| name.strip_suffix('"'))
+                {
+                    Some(name) => unescape(name).with_context(|| format!("line {}", i + 1))?,
+                    None => name.trim().to_string(),
+                };
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("line {}: expected `key = value`", i + 1);
+            };
+            let value = parse_value(value.trim()).with_context(|| format!("line {}", i + 1))?;
+            // フラグと同じmodel-dirでも、model_dirでもよい
+            let key = key.trim().trim_matches('"').replace('-', "_");
+            tables.entry(table.clone()).or_default().push((key, value));
+        }
+        Ok(Self { tables })
+    }
+
+    // 表の名前と、その中の「キー = 値」。値は文字列にしてある
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &[(String, String)])> {
+        self.tables
+            .iter()
+            .map(|(name, entries)| (name.as_str(), entries.as_slice()))
+    }
+
+    pub fn apply(&self, mut command: Command) -> Command {
+        for (table, entries) in &self.tables {
+            for (key, value) in entries {
+                let mut applied = false;
+                if table.is_empty() && has_arg(&command, key) {
+                    command = command.mut_arg(key, |arg| arg.default_value(leak(value)));
+                    applied = true;
+                }
+                let subcommands: Vec<String> = command
+                    .get_subcommands()
+                    .filter(|subcommand| table.is_empty() || subcommand.get_name() == table)
+                    .filter(|subcommand| has_arg(subcommand, key))
+                    .map(|subcommand| subcommand.get_name().to_string())
+                    .collect();
+                for subcommand in subcommands {
+                    command = command.mut_subcommand(subcommand, |subcommand| {
+                        subcommand.mut_arg(key, |arg| arg.default_value(leak(value)))
+                    });
+                    applied = true;
+                }
+                if !applied {
+                    let key = key.replace('_', "-");
+                    match table.as_str() {
+                        "" => eprintln!("warning: config: no command takes --{key}"),
+                        table => eprintln!("warning: config: [{table}] does not take --{key}"),
+                    }
+                }
+            }
+        }
+        command
+    }
+}
+
+fn has_arg(command: &Command, id: &str) -> bool {
+    command.get_arguments().any(|arg| arg.get_id() == id)
+}
+
+// clapの既定値は'staticな文字列を求める。起動時に一度だけなので、解放しなくてよい
+fn leak(value: &str) -> &'static str {
+    Box::leak(value.into())
+}
+
+// 文字列の中の#はコメントにしない
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<String> {
+    let value = if let Some(literal) = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+    {
+        literal.to_string()
+    } else if let Some(basic) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        unescape(basic)?
+    } else if value.is_empty() || value.starts_with(['[', '{']) {
+        bail!("only strings, numbers and booleans are supported: {value}");
+    } else {
+        // 数値と真偽値は、そのままclapに解釈させる
+        value.replace('_', "")
+    };
+    Ok(expand_home(value))
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => unescaped.push('"'),
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            other => bail!("unsupported escape: \\{}", other.unwrap_or(' ')),
+        }
+    }
+    Ok(unescaped)
+}
+
+// model_dir = "~/voicevox/model" のように書けるようにする
+fn expand_home(value: String) -> String {
+    match (value.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest).display().to_string(),
+        _ => value,
+    }
+}
@@ -0,0 +1,69 @@
+use super::{
+    output,
+    serve::control::{default_socket, Defaults, Setting, SOCKET_ENV},
+};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+// serve --controlで動かしているサーバーの既定値を、止めずに変える
+#[derive(clap::Args)]
+pub struct Args {
+    /// Control socket of the server [default: $XDG_RUNTIME_DIR/chibivox.sock, otherwise in
+    /// the temp directory]
+    #[arg(long, env = SOCKET_ENV)]
+    socket: Option<PathBuf>,
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Change the default speaker, speed or volume of /tts; "default" restores the style's
+    /// own value
+    Set {
+        #[arg(value_enum)]
+        setting: Setting,
+        value: String,
+    },
+    /// Print the current defaults
+    Get,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let line = match &args.action {
+        Action::Set { setting, value } => {
+            let name = setting.to_possible_value().unwrap();
+            format!("set {} {value}", name.get_name())
+        }
+        Action::Get => "get".to_string(),
+    };
+    let socket = args.socket.unwrap_or_else(default_socket);
+    let mut stream = UnixStream::connect(&socket).with_context(|| {
+        format!(
+            "cannot connect to {}; is `serve --control` running?",
+            socket.display()
+        )
+    })?;
+    writeln!(stream, "{line}")?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    let reply = reply.trim();
+    if let Some(message) = reply.strip_prefix("error: ") {
+        bail!("{message}");
+    }
+
+    let defaults: Defaults = serde_json::from_str(reply)?;
+    if output::json() {
+        return output::emit(&defaults);
+    }
+    let show = |value: Option<String>| value.unwrap_or_else(|| "default".to_string());
+    println!("speaker: {}", show(defaults.speaker.map(|v| v.to_string())));
+    println!("speed: {}", show(defaults.speed.map(|v| v.to_string())));
+    println!("volume: {}", show(defaults.volume.map(|v| v.to_string())));
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// HTTPを介さずに、同じマシンのツールから手早く合成するためのUNIXソケット
+// 一つの接続で何度でも、長さを前に付けたテキストを送ればPCMが返る。整数は全てリトルエンディアン
+//   要求: u32 話者(スタイル)ID、u32 バイト数、UTF-8のテキスト
+//   応答: u8 状態、u32 バイト数、本文
+// 状態が0なら本文は24000Hzモノラルの16bit整数のPCM、それ以外は終了コードと同じ番号で、本文はエラーの文
+use super::{errors, i18n, load_core, synthesize, watermark, Params, ProsodyArgs};
+use anyhow::{bail, Result};
+use chibivox::{
+    wav::{self, SampleFormat},
+    InitializeOptions, VoicevoxCore,
+};
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    thread,
+};
+
+// 誤って大きなファイルを送られても、メモリを使い果たさないように
+const MAX_TEXT_BYTES: usize = 1 << 20;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Socket to listen on [default: $XDG_RUNTIME_DIR/chibivox-daemon.sock, otherwise in the
+    /// temp directory]
+    #[arg(long, env = "CHIBIVOX_DAEMON_SOCKET")]
+    socket: Option<PathBuf>,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let path = args.socket.clone().unwrap_or_else(default_socket);
+    let listener = bind(&path)?;
+    eprintln!("listening on {}", path.display());
+
+    // 接続ごとにスレッドを立てる。同じマシンのツールだけが相手なので、数は限らない
+    let (core, args) = (&core, &args);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            scope.spawn(move || {
+                if let Err(err) = serve(core, args, stream) {
+                    eprintln!("warning: daemon: {err}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+// 相手が接続を閉じるまで、要求に一つずつ答える
+fn serve(core: &VoicevoxCore, args: &Args, stream: UnixStream) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+    loop {
+        let mut header = [0; 8];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+        let speaker_id = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if len > MAX_TEXT_BYTES {
+            // 続きを読み飛ばせないので、答えてから切る
+            let message = format!("text too long: {len} bytes (at most {MAX_TEXT_BYTES})");
+            reply(
+                &mut writer,
+                errors::ErrorKind::Argument.exit_code(),
+                message.as_bytes(),
+            )?;
+            bail!("{message}");
+        }
+        let mut text = vec![0; len];
+        reader.read_exact(&mut text)?;
+        let Ok(text) = String::from_utf8(text) else {
+            let message = "text is not valid UTF-8";
+            reply(
+                &mut writer,
+                errors::ErrorKind::Argument.exit_code(),
+                message.as_bytes(),
+            )?;
+            continue;
+        };
+
+        match synthesize_pcm(core, args, speaker_id, &text) {
+            Ok(pcm) => reply(&mut writer, 0, &pcm)?,
+            Err(err) => {
+                let message = i18n::message(err.as_ref());
+                eprintln!("error: {message}");
+                reply(
+                    &mut writer,
+                    errors::kind(&err).exit_code(),
+                    message.as_bytes(),
+                )?;
+            }
+        }
+    }
+}
+
+fn synthesize_pcm(
+    core: &VoicevoxCore,
+    args: &Args,
+    speaker_id: u32,
+    text: &str,
+) -> Result<Vec<u8>> {
+    let params = Params {
+        speaker_id,
+        options: args.prosody.to_options(core, speaker_id),
+        ..Default::default()
+    };
+    let synthesis = synthesize(core, text, &params, None, None)?;
+    for warning in &synthesis.warnings {
+        eprintln!("warning: {}", i18n::warning(warning));
+    }
+    let wav = watermark::apply(&synthesis.wav);
+    Ok(wav::encode_samples(&wav.samples, SampleFormat::I16))
+}
+
+fn reply(writer: &mut impl Write, status: u8, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&[status])?;
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+// $XDG_RUNTIME_DIRがあればそこに、無ければ一時ディレクトリに置く
+fn default_socket() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("chibivox-daemon.sock")
+}
+
+// 前回落ちたときに残ったソケットは消す。他のdaemonが使っていれば止める
+fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("{} is already in use by another daemon", path.display());
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    // 合成できるのは、daemonを動かしているユーザーだけ
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
@@ -0,0 +1,250 @@
+use super::{config::ConfigError, i18n};
+use chibivox::{TermsNotAcknowledgedError, UnknownSpeakerError, UnsupportedStyleError};
+use clap::error::ContextKind;
+use serde::Serialize;
+use std::{env, error::Error, fmt, io, process::ExitCode};
+
+// ラッパーのスクリプトが原因を見分けられるように、エラーの種類ごとに終了コードを分ける
+// 番号を変えるとスクリプトが壊れるので、足すときは末尾に足し、EXIT_CODESも合わせる
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    Argument,
+    Dictionary,
+    Model,
+    Inference,
+    Io,
+    Config,
+    Partial,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            // clapが引数の誤りで返すのと同じ
+            Self::Argument => 2,
+            Self::Dictionary => 3,
+            Self::Model => 4,
+            Self::Inference => 5,
+            Self::Io => 6,
+            Self::Config => 7,
+            Self::Partial => 8,
+        }
+    }
+}
+
+// --helpの末尾に載せる
+pub const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  other error
+  2  invalid arguments, speaker or style, or terms not acknowledged
+  3  text analysis failed
+  4  models could not be loaded
+  5  inference failed
+  6  file input/output failed
+  7  invalid config file
+  8  some inputs of a batch failed while others were written
+When every input of a batch fails, the code is that of the first failure.";
+
+// 失敗した段階
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Load,
+    Analysis,
+    Prediction,
+    Decode,
+}
+
+impl Stage {
+    fn kind(self) -> ErrorKind {
+        match self {
+            Self::Load => ErrorKind::Model,
+            Self::Analysis => ErrorKind::Dictionary,
+            Self::Prediction | Self::Decode => ErrorKind::Inference,
+        }
+    }
+}
+
+// 段階と入力を添えたエラー。表示は元のエラーのまま変えない
+#[derive(Debug)]
+pub struct StageError {
+    stage: Stage,
+    input: String,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl StageError {
+    pub fn inner(&self) -> &(dyn Error + 'static) {
+        self.source.as_ref()
+    }
+}
+
+impl Error for StageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.source()
+    }
+}
+
+pub trait AtStage<T> {
+    fn at_stage(self, stage: Stage, input: &str) -> anyhow::Result<T>;
+}
+
+impl<T> AtStage<T> for anyhow::Result<T> {
+    fn at_stage(self, stage: Stage, input: &str) -> anyhow::Result<T> {
+        self.map_err(|source| {
+            // 内側で既に段階が分かっている場合や、指定の誤りの場合はそのまま返す
+            if source.downcast_ref::<StageError>().is_some()
+                || source.downcast_ref::<UnknownSpeakerError>().is_some()
+                || source.downcast_ref::<UnsupportedStyleError>().is_some()
+                || source.downcast_ref::<TermsNotAcknowledgedError>().is_some()
+            {
+                return source;
+            }
+            StageError {
+                stage,
+                input: input.into(),
+                source,
+            }
+            .into()
+        })
+    }
+}
+
+// 複数の入力を処理して、失敗したものがあったこと。表示は呼び出し側が決めた文言
+#[derive(Debug)]
+pub struct BatchError {
+    message: String,
+    kind: ErrorKind,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for BatchError {}
+
+// 失敗した入力を数え、最初の失敗の種類を覚えておく
+#[derive(Default)]
+pub struct Failures {
+    count: usize,
+    first: Option<ErrorKind>,
+}
+
+impl Failures {
+    pub fn add(&mut self, err: &anyhow::Error) {
+        self.count += 1;
+        self.first.get_or_insert_with(|| kind(err));
+    }
+
+    // 一部だけ失敗したならPartial、全て失敗したなら最初の失敗の種類のBatchErrorにする
+    // messageには失敗した数を渡す
+    pub fn finish(self, total: usize, message: impl FnOnce(usize) -> String) -> anyhow::Result<()> {
+        let Some(first) = self.first else {
+            return Ok(());
+        };
+        let kind = if self.count < total {
+            ErrorKind::Partial
+        } else {
+            first
+        };
+        Err(BatchError {
+            message: message(self.count),
+            kind,
+        }
+        .into())
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    /// One JSON object per error on stderr
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    kind: ErrorKind,
+    stage: Option<Stage>,
+    input: Option<&'a str>,
+    message: String,
+}
+
+// 引数の解析に失敗した場合は--error-formatや--jsonも読めていないので、生の引数と環境変数から判断する
+pub fn json_requested() -> bool {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--error-format=json" | "--json" => return true,
+            "--error-format" if args.next().as_deref() == Some("json") => return true,
+            _ => {}
+        }
+    }
+    env::var("CHIBIVOX_ERROR_FORMAT").as_deref() == Ok("json")
+}
+
+pub fn report_argument_error(err: &clap::Error) -> ExitCode {
+    let kind = ErrorKind::Argument;
+    let input = err
+        .get(ContextKind::InvalidArg)
+        .map(|invalid_arg| invalid_arg.to_string());
+    print_json(&JsonError {
+        kind,
+        stage: None,
+        input: input.as_deref(),
+        message: err.kind().to_string(),
+    });
+    ExitCode::from(kind.exit_code())
+}
+
+pub fn kind(err: &anyhow::Error) -> ErrorKind {
+    if let Some(batch_error) = err.downcast_ref::<BatchError>() {
+        batch_error.kind
+    } else if let Some(stage_error) = err.downcast_ref::<StageError>() {
+        stage_error.stage.kind()
+    } else if err.downcast_ref::<ConfigError>().is_some() {
+        ErrorKind::Config
+    } else if err.downcast_ref::<UnknownSpeakerError>().is_some()
+        || err.downcast_ref::<UnsupportedStyleError>().is_some()
+        || err.downcast_ref::<TermsNotAcknowledgedError>().is_some()
+    {
+        ErrorKind::Argument
+    } else if err.downcast_ref::<io::Error>().is_some() {
+        ErrorKind::Io
+    } else {
+        ErrorKind::Other
+    }
+}
+
+pub fn report(err: &anyhow::Error, format: ErrorFormat) -> ExitCode {
+    let stage_error = err.downcast_ref::<StageError>();
+    let kind = kind(err);
+
+    match format {
+        ErrorFormat::Text => eprintln!("{}", i18n::report(err)),
+        ErrorFormat::Json => print_json(&JsonError {
+            kind,
+            stage: stage_error.map(|stage_error| stage_error.stage),
+            input: stage_error.map(|stage_error| stage_error.input.as_str()),
+            message: i18n::chain(err),
+        }),
+    }
+    ExitCode::from(kind.exit_code())
+}
+
+fn print_json(error: &JsonError) {
+    eprintln!("{}", serde_json::json!({ "error": error }));
+}
@@ -0,0 +1,110 @@
+use super::{load_core, output::Record, render, Params, SAMPLE_SENTENCES};
+use anyhow::Result;
+use chibivox::{AudioFeatures, InitializeOptions};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// <out-dir>/<system>/<sentence_id>.wav と manifest.csv を出力する
+#[derive(clap::Args)]
+pub struct Args {
+    /// Model directories to evaluate (defaults to --model-dir)
+    #[arg(long, value_delimiter = ',')]
+    model_dirs: Vec<PathBuf>,
+    /// Speaker ids to evaluate
+    #[arg(long, value_delimiter = ',', default_value = "0")]
+    speakers: Vec<u32>,
+    /// Output directory
+    #[arg(long, default_value = "eval")]
+    out_dir: PathBuf,
+    /// Append QA features (duration, f0, spectral centroid) to the manifest
+    #[arg(long)]
+    features: bool,
+    /// Also write the AudioQuery actually used as a .json next to each WAV
+    #[arg(long)]
+    save_query: bool,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let out_dir = args.out_dir;
+    fs::create_dir_all(&out_dir)?;
+    let mut manifest = File::create(out_dir.join("manifest.csv"))?;
+    write!(manifest, "system,model,speaker,sentence_id,text,path")?;
+    if args.features {
+        write!(
+            manifest,
+            ",duration,f0_mean,f0_p5,f0_p50,f0_p95,spectral_centroid"
+        )?;
+    }
+    writeln!(manifest)?;
+
+    let model_dirs = if args.model_dirs.is_empty() {
+        vec![model_dir.to_path_buf()]
+    } else {
+        args.model_dirs
+    };
+    for (i, model_dir) in model_dirs.iter().enumerate() {
+        let core = load_core(model_dir, &InitializeOptions::default())?;
+        let model_name = model_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| format!("model{i}"));
+
+        for speaker_id in &args.speakers {
+            let system = format!("{model_name}_{speaker_id}");
+            fs::create_dir_all(out_dir.join(&system))?;
+
+            for (j, text) in SAMPLE_SENTENCES.lines().enumerate() {
+                let sentence_id = format!("{:03}", j + 1);
+                let path = Path::new(&system).join(format!("{sentence_id}.wav"));
+                let synthesis = render(
+                    &core,
+                    text,
+                    &Params {
+                        speaker_id: *speaker_id,
+                        options: core.default_options(*speaker_id),
+                        ..Default::default()
+                    },
+                    &out_dir.join(&path),
+                    None,
+                    args.save_query,
+                    false,
+                )?;
+                Record::new(
+                    &path.display().to_string(),
+                    &out_dir.join(&path),
+                    &synthesis,
+                )
+                .print()?;
+                let wav = &synthesis.wav;
+                write!(
+                    manifest,
+                    "{system},{},{speaker_id},{sentence_id},\"{}\",{}",
+                    model_dir.display(),
+                    text.replace('"', "\"\""),
+                    path.display(),
+                )?;
+                if args.features {
+                    let features = AudioFeatures::extract(&wav.samples, wav.sample_rate);
+                    let field =
+                        |value: Option<f32>| value.map(|v| v.to_string()).unwrap_or_default();
+                    write!(
+                        manifest,
+                        ",{},{},{},{},{},{}",
+                        features.duration,
+                        field(features.f0_mean),
+                        field(features.f0_p5),
+                        field(features.f0_p50),
+                        field(features.f0_p95),
+                        field(features.spectral_centroid),
+                    )?;
+                }
+                writeln!(manifest)?;
+            }
+        }
+    }
+
+    Ok(())
+}
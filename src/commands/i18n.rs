@@ -0,0 +1,125 @@
+use super::{config::ConfigError, errors::StageError};
+use chibivox::{
+    InvalidAccentPhraseError, InvalidAccentPhraseReason, StyleType, TermsNotAcknowledgedError,
+    UnknownSpeakerError, UnsupportedStyleError, Warning,
+};
+use std::{error::Error, sync::OnceLock};
+
+// エラーと警告の文言。ログに残す英語を既定にし、--lang jaで日本語にする
+// 訳すのは種類が分かるものだけで、それ以外は元の英語のまま出す
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+// 引数を埋め込んだ決まり文句を選ぶ
+pub fn pick(en: String, ja: String) -> String {
+    match lang() {
+        Lang::En => en,
+        Lang::Ja => ja,
+    }
+}
+
+pub fn warning(warning: &Warning) -> String {
+    if lang() == Lang::En {
+        return warning.to_string();
+    }
+    match warning {
+        Warning::UnknownWord { surface } => format!("辞書に無い語です: {surface:?}"),
+        Warning::TruncatedPhrase { text } => {
+            format!("アクセント句が長すぎるため、{text:?} の後で切りました")
+        }
+        Warning::ClampedDuration { phoneme, predicted } => {
+            format!("{phoneme:?} の長さの予測値({predicted}秒)が短すぎるため、下限に引き上げました")
+        }
+        Warning::ClippedSamples { count, peak } => {
+            format!("{count} サンプルが音割れしています(最大 {peak:.2})")
+        }
+    }
+}
+
+// anyhowの{:#}と同じく、原因までを": "でつなげる
+pub fn chain(err: &anyhow::Error) -> String {
+    err.chain().map(message).collect::<Vec<_>>().join(": ")
+}
+
+// anyhowの{:?}と同じ形で、原因を一行ずつ並べる
+pub fn report(err: &anyhow::Error) -> String {
+    if lang() == Lang::En {
+        return format!("Error: {err:?}");
+    }
+    let mut messages = err.chain().map(message);
+    let mut report = format!("エラー: {}", messages.next().unwrap_or_default());
+    let causes: Vec<String> = messages.collect();
+    match causes.as_slice() {
+        [] => {}
+        [cause] => report += &format!("\n\n原因:\n    {cause}"),
+        causes => {
+            report += "\n\n原因:";
+            for (i, cause) in causes.iter().enumerate() {
+                report += &format!("\n    {i}: {cause}");
+            }
+        }
+    }
+    report
+}
+
+// 一つのエラーの文言。原因は含めない
+pub fn message(err: &(dyn Error + 'static)) -> String {
+    if lang() == Lang::En {
+        return err.to_string();
+    }
+    // 段階を添えただけのものは、元のエラーとして訳す
+    if let Some(err) = err.downcast_ref::<StageError>() {
+        return message(err.inner());
+    }
+    if let Some(err) = err.downcast_ref::<ConfigError>() {
+        return format!("設定ファイル {}", err.path.display());
+    }
+    if let Some(err) = err.downcast_ref::<UnknownSpeakerError>() {
+        return format!(
+            "スタイル {} はありません(使えるスタイル: {:?})",
+            err.speaker_id, err.available_speaker_ids
+        );
+    }
+    if let Some(err) = err.downcast_ref::<UnsupportedStyleError>() {
+        let style_type = match err.style_type {
+            StyleType::Talk => "会話",
+            StyleType::SingingTeacher => "ハミング",
+            StyleType::FrameDecode | StyleType::Sing => "歌唱",
+        };
+        return format!(
+            "スタイル {} は{style_type}用のため、会話の合成には使えません(会話用のスタイル: {:?})",
+            err.speaker_id, err.supported_speaker_ids
+        );
+    }
+    if let Some(err) = err.downcast_ref::<TermsNotAcknowledgedError>() {
+        return format!(
+            "話者 {:?}(スタイル {})は商用利用が認められていません。規約を確認し、同意したうえで使ってください",
+            err.speaker_name, err.speaker_id
+        );
+    }
+    if let Some(err) = err.downcast_ref::<InvalidAccentPhraseError>() {
+        let reason = match &err.reason {
+            InvalidAccentPhraseReason::NoMoras => "モーラがありません".to_string(),
+            InvalidAccentPhraseReason::UnknownVowel(vowel) => format!("不明な母音 {vowel:?}"),
+            InvalidAccentPhraseReason::UnknownConsonant(consonant) => {
+                format!("不明な子音 {consonant:?}")
+            }
+        };
+        return format!("{}: {reason}", err.location);
+    }
+    err.to_string()
+}
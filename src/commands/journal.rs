@@ -0,0 +1,78 @@
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// 中断した大量の書き出しを、終わった分を飛ばして再開するための設定
+#[derive(clap::Args)]
+pub struct ResumeArgs {
+    /// Skip inputs whose output file already exists (with --journal, only those recorded
+    /// as completed, so files left half-written by an interruption are redone)
+    #[arg(long)]
+    skip_existing: bool,
+    /// Append each completed output path to this file
+    #[arg(long)]
+    journal: Option<PathBuf>,
+}
+
+pub struct Journal {
+    file: Option<Mutex<File>>,
+    completed: Option<HashSet<PathBuf>>,
+    skip_existing: bool,
+}
+
+impl ResumeArgs {
+    pub fn is_enabled(&self) -> bool {
+        self.skip_existing || self.journal.is_some()
+    }
+
+    pub fn open(&self) -> Result<Journal> {
+        let (file, completed) = match &self.journal {
+            Some(path) => {
+                let completed = if path.exists() {
+                    fs::read_to_string(path)?
+                        .lines()
+                        .filter(|line| !line.is_empty())
+                        .map(PathBuf::from)
+                        .collect()
+                } else {
+                    HashSet::new()
+                };
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                (Some(Mutex::new(file)), Some(completed))
+            }
+            None => (None, None),
+        };
+        Ok(Journal {
+            file,
+            completed,
+            skip_existing: self.skip_existing,
+        })
+    }
+}
+
+impl Journal {
+    pub fn should_skip(&self, path: &Path) -> bool {
+        if !self.skip_existing || !path.exists() {
+            return false;
+        }
+        match &self.completed {
+            Some(completed) => completed.contains(path),
+            None => true,
+        }
+    }
+
+    // 書き出しが終わってから呼ぶ。一行ずつ書き切るので、途中で止まっても記録は壊れない
+    pub fn complete(&self, path: &Path) -> Result<()> {
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+            writeln!(file, "{}", path.display())?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
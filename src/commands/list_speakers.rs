@@ -0,0 +1,41 @@
+use super::output;
+use anyhow::{bail, Result};
+use chibivox::{load_metas, StyleType};
+use std::path::Path;
+
+// --jsonでは、表の代わりにmetas.jsonの中身をそのまま書く
+#[derive(clap::Args)]
+pub struct Args {}
+
+// モデルを読み込まずに、metas.jsonから--speakerに渡せるidを一覧する
+pub fn run(_args: Args, model_dir: &Path) -> Result<()> {
+    let path = model_dir.join("metas.json");
+    let metas = load_metas(&path)?;
+    if metas.is_empty() {
+        bail!("no speakers found: {} is missing or empty", path.display());
+    }
+
+    if output::json() {
+        return output::emit(&metas);
+    }
+
+    for speaker in &metas {
+        println!("{} ({})", speaker.name, speaker.speaker_uuid);
+        if let Some(terms) = &speaker.terms {
+            if let Some(credit) = &terms.credit {
+                println!("  credit: {credit}");
+            }
+            if !terms.commercial {
+                println!("  non-commercial only (requires --acknowledge-terms)");
+            }
+        }
+        for style in &speaker.styles {
+            match style.style_type {
+                StyleType::Talk => println!("  {:>4}  {}", style.id, style.name),
+                style_type => println!("  {:>4}  {} [{style_type:?}]", style.id, style.name),
+            }
+        }
+    }
+
+    Ok(())
+}
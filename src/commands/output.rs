@@ -0,0 +1,71 @@
+use super::{i18n, summary::Timings, Synthesis};
+use anyhow::Result;
+use chibivox::Warnings;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// --jsonを付けると、人向けの表示の代わりに、結果を一件ごとに一行のJSONで標準出力に書く
+// 合成の警告も結果に含め、標準エラー出力には書かない
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn emit(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+// 一つの入力を合成した結果
+#[derive(Serialize)]
+pub struct Record {
+    // 入力の名前(行番号や書き出したパス)
+    input: String,
+    path: PathBuf,
+    // 複数の入力を一つのファイルにつなげた場合の、その中での開始位置(秒)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<f64>,
+    // 音声の長さ(秒)
+    duration: f64,
+    warnings: Warnings,
+    timings: Timings,
+}
+
+impl Record {
+    pub fn new(input: &str, path: &Path, synthesis: &Synthesis) -> Self {
+        Self {
+            input: input.into(),
+            path: path.into(),
+            offset: None,
+            duration: synthesis.wav.duration() as f64,
+            warnings: synthesis.warnings.clone(),
+            timings: synthesis.timings,
+        }
+    }
+
+    pub fn at_offset(self, offset: f64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    // --jsonでなければ、警告だけを標準エラー出力に書く
+    pub fn print(&self) -> Result<()> {
+        if json() {
+            return emit(self);
+        }
+        for warning in &self.warnings {
+            eprintln!("warning: {}: {}", self.input, i18n::warning(warning));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+// VOICEVOX ENGINEと同じ、話者とパラメータに名前を付けてまとめたプリセット
+// CLIでは--presetで名前を指定して使い、serveでは/presetsなどで一覧・追加・更新・削除する
+// CHIBIVOX_PRESETS、なければ設定ファイルと同じディレクトリのpresets.tomlに、名前ごとの表として保存する
+//   ["ゆっくり"]
+//   id = 1
+//   speaker_uuid = "…"
+//   style_id = 3
+//   speed_scale = 0.8
+//   …
+use super::config::{self, Config};
+use anyhow::{bail, Context, Result};
+use chibivox::SynthesisOptions;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    ffi::OsString,
+    fmt::{self, Write as _},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const PATH_ENV: &str = "CHIBIVOX_PRESETS";
+
+// JSONではVOICEVOX ENGINEのPresetと同じ名前にする
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Preset {
+    pub id: u32,
+    pub name: String,
+    pub speaker_uuid: String,
+    pub style_id: u32,
+    #[serde(rename = "speedScale")]
+    pub speed_scale: f32,
+    #[serde(rename = "pitchScale")]
+    pub pitch_scale: f32,
+    #[serde(rename = "intonationScale")]
+    pub intonation_scale: f32,
+    #[serde(rename = "volumeScale")]
+    pub volume_scale: f32,
+    #[serde(rename = "prePhonemeLength")]
+    pub pre_phoneme_length: f32,
+    #[serde(rename = "postPhonemeLength")]
+    pub post_phoneme_length: f32,
+}
+
+impl Preset {
+    pub fn apply(&self, options: &mut SynthesisOptions) {
+        options.speed_scale = self.speed_scale;
+        options.pitch_scale = self.pitch_scale;
+        options.intonation_scale = self.intonation_scale;
+        options.volume_scale = self.volume_scale;
+        options.pre_phoneme_length = self.pre_phoneme_length;
+        options.post_phoneme_length = self.post_phoneme_length;
+    }
+
+    fn from_table(name: &str, entries: &[(String, String)]) -> Result<Self> {
+        let get = |key: &str| {
+            entries
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+                .with_context(|| format!("[{name:?}]: missing {key}"))
+        };
+        let number = |key: &str| -> Result<f32> {
+            get(key)?
+                .parse()
+                .with_context(|| format!("[{name:?}]: {key} must be a number"))
+        };
+        let integer = |key: &str| -> Result<u32> {
+            get(key)?
+                .parse()
+                .with_context(|| format!("[{name:?}]: {key} must be a non-negative integer"))
+        };
+        Ok(Self {
+            id: integer("id")?,
+            name: name.to_string(),
+            speaker_uuid: get("speaker_uuid").unwrap_or_default().to_string(),
+            style_id: integer("style_id")?,
+            speed_scale: number("speed_scale")?,
+            pitch_scale: number("pitch_scale")?,
+            intonation_scale: number("intonation_scale")?,
+            volume_scale: number("volume_scale")?,
+            pre_phoneme_length: number("pre_phoneme_length")?,
+            post_phoneme_length: number("post_phoneme_length")?,
+        })
+    }
+
+    fn write_table(&self, toml: &mut String) -> fmt::Result {
+        writeln!(toml, "[{}]", quote(&self.name))?;
+        writeln!(toml, "id = {}", self.id)?;
+        writeln!(toml, "speaker_uuid = {}", quote(&self.speaker_uuid))?;
+        writeln!(toml, "style_id = {}", self.style_id)?;
+        writeln!(toml, "speed_scale = {}", self.speed_scale)?;
+        writeln!(toml, "pitch_scale = {}", self.pitch_scale)?;
+        writeln!(toml, "intonation_scale = {}", self.intonation_scale)?;
+        writeln!(toml, "volume_scale = {}", self.volume_scale)?;
+        writeln!(toml, "pre_phoneme_length = {}", self.pre_phoneme_length)?;
+        writeln!(toml, "post_phoneme_length = {}", self.post_phoneme_length)
+    }
+
+    // 書き出したものを読み戻せないものと、値として意味を成さないものを断る
+    fn validate(&self) -> Result<(), InvalidPresetError> {
+        let invalid = |reason: String| Err(InvalidPresetError(reason));
+        if self.name.trim().is_empty() {
+            return invalid("name must not be empty".into());
+        }
+        for text in [&self.name, &self.speaker_uuid] {
+            if text.chars().any(|c| c.is_control() && c != '\t') {
+                return invalid(format!("{text:?} contains a control character"));
+            }
+        }
+        let numbers = [
+            ("speedScale", self.speed_scale),
+            ("pitchScale", self.pitch_scale),
+            ("intonationScale", self.intonation_scale),
+            ("volumeScale", self.volume_scale),
+            ("prePhonemeLength", self.pre_phoneme_length),
+            ("postPhonemeLength", self.post_phoneme_length),
+        ];
+        for (key, value) in numbers {
+            if !value.is_finite() {
+                return invalid(format!("{key} must be a finite number"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 追加・更新・削除できないプリセット。serveは422で断る
+#[derive(Debug)]
+pub struct InvalidPresetError(String);
+
+impl fmt::Display for InvalidPresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid preset: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPresetError {}
+
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(PATH_ENV) {
+        return Some(path.into());
+    }
+    Some(config::dir()?.join("presets.toml"))
+}
+
+// --presetの値。起動時に読み、無い名前ならclapの誤りとして止める
+pub fn parse(name: &str) -> Result<Preset, String> {
+    let path = path().ok_or("cannot locate presets.toml; set CHIBIVOX_PRESETS")?;
+    let presets = Presets::load(&path).map_err(|err| format!("{err:#}"))?;
+    let preset = presets
+        .list()
+        .iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| format!("no preset named {name:?} in {}", path.display()))?;
+    Ok(preset.clone())
+}
+
+pub struct Presets {
+    path: PathBuf,
+    // idの順
+    presets: Vec<Preset>,
+}
+
+impl Presets {
+    // ファイルが無ければ空
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).context(format!("presets: {}", path.display())),
+        };
+        let parse = || -> Result<Vec<Preset>> {
+            let mut presets = Vec::new();
+            for (name, entries) in Config::parse(&source)?.tables() {
+                if name.is_empty() {
+                    bail!("expected a [\"name\"] table before `{} = …`", entries[0].0);
+                }
+                presets.push(Preset::from_table(name, entries)?);
+            }
+            presets.sort_by_key(|preset| preset.id);
+            if let Some(pair) = presets.windows(2).find(|pair| pair[0].id == pair[1].id) {
+                bail!(
+                    "{:?} and {:?} share id {}",
+                    pair[0].name,
+                    pair[1].name,
+                    pair[0].id
+                );
+            }
+            Ok(presets)
+        };
+        let presets = parse().with_context(|| format!("presets: {}", path.display()))?;
+        Ok(Self {
+            path: path.into(),
+            presets,
+        })
+    }
+
+    pub fn list(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Preset> {
+        self.presets.iter().find(|preset| preset.id == id)
+    }
+
+    // VOICEVOX ENGINEと同じく、idが使われていれば最大のものの次を振る。振ったidを返す
+    pub fn add(&mut self, mut preset: Preset) -> Result<u32> {
+        preset.validate()?;
+        if self.get(preset.id).is_some() {
+            preset.id = self.presets.last().map_or(0, |last| last.id + 1);
+        }
+        self.check_name(&preset)?;
+        let id = preset.id;
+        self.presets.push(preset);
+        self.presets.sort_by_key(|preset| preset.id);
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn update(&mut self, preset: Preset) -> Result<u32> {
+        preset.validate()?;
+        self.check_name(&preset)?;
+        let Some(slot) = self.presets.iter_mut().find(|slot| slot.id == preset.id) else {
+            return Err(InvalidPresetError(format!("no preset with id {}", preset.id)).into());
+        };
+        *slot = preset;
+        let id = slot.id;
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn delete(&mut self, id: u32) -> Result<()> {
+        let Some(i) = self.presets.iter().position(|preset| preset.id == id) else {
+            return Err(InvalidPresetError(format!("no preset with id {id}")).into());
+        };
+        self.presets.remove(i);
+        self.save()
+    }
+
+    // --presetは名前で選ぶので、別のidで同じ名前は許さない
+    fn check_name(&self, preset: &Preset) -> Result<(), InvalidPresetError> {
+        match self
+            .presets
+            .iter()
+            .find(|other| other.name == preset.name && other.id != preset.id)
+        {
+            Some(other) => Err(InvalidPresetError(format!(
+                "{:?} is already used by id {}",
+                other.name, other.id
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // 書いている途中で落ちても前のものが残るように、別名で書いてから置き換える
+    fn save(&self) -> Result<()> {
+        let mut toml =
+            String::from("# chibivoxのプリセット。serveで書き換えると、コメントは残らない\n");
+        for preset in &self.presets {
+            toml.push('\n');
+            preset.write_table(&mut toml)?;
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = OsString::from(&self.path);
+        temporary.push(".tmp");
+        fs::write(&temporary, toml)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+// config.rsのunescapeが戻せる形で書く。他の制御文字はvalidateで断っている
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,42 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{fmt::Display, time::Duration};
+
+// 標準エラー出力に出す進捗。端末でなければ何も表示しない
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    pub fn new(len: usize) -> Self {
+        let bar = ProgressBar::new(len as u64);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner} [{elapsed_precise}] {wide_bar} {pos}/{len} (ETA {eta}) {msg}",
+            )
+            .unwrap(),
+        );
+        // 一つの入力が長くても止まって見えないように、定期的に描き直す
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self(bar)
+    }
+
+    // いま処理している入力とその段階
+    pub fn stage(&self, name: &str, stage: &str) {
+        self.0.set_message(format!("{name}: {stage}"));
+    }
+
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+
+    // 進捗の表示を崩さずにエラーなどを出す
+    pub fn eprintln(&self, message: impl Display) {
+        self.suspend(|| eprintln!("{message}"));
+    }
+
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.0.suspend(f)
+    }
+
+    pub fn finish(&self) {
+        self.0.finish_and_clear();
+    }
+}
@@ -0,0 +1,46 @@
+use super::{load_core, output, ProsodyArgs};
+use anyhow::Result;
+use chibivox::InitializeOptions;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Speaker (style) id
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    /// Text to analyze
+    text: String,
+    /// Write the JSON to a file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+}
+
+// 重いdecodeの前に韻律を確認・編集できるよう、AudioQueryだけを出力する
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let speaker_id = args.prosody.speaker(args.speaker);
+    let audio_query = core.audio_query_with_options(
+        &args.text,
+        speaker_id,
+        &args.prosody.to_options(&core, speaker_id),
+    )?;
+
+    // --jsonでは、標準出力には一行で書き、ファイルに書いた場合はそのパスを知らせる
+    match args.output {
+        Some(path) => {
+            fs::write(&path, serde_json::to_string_pretty(&audio_query)?)?;
+            if output::json() {
+                output::emit(&serde_json::json!({ "path": path }))?;
+            }
+        }
+        None if output::json() => output::emit(&audio_query)?,
+        None => println!("{}", serde_json::to_string_pretty(&audio_query)?),
+    }
+
+    Ok(())
+}
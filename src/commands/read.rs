@@ -0,0 +1,152 @@
+use super::{i18n, load_core, output, play, ranged, synthesize_smoothed, Params, ProsodyArgs};
+use anyhow::{Context, Result};
+use chibivox::{InitializeOptions, ProsodySmoother};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+// 長い文書を一文ずつ合成して読み上げる。落ちたり止めたりしても、--resumeで続きから読める
+#[derive(clap::Args)]
+pub struct Args {
+    /// UTF-8 text file to read aloud (requires the `play` feature)
+    document: PathBuf,
+    /// Speaker (style) id
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+    /// Pull each sentence's mean pitch and speaking rate toward the preceding ones so the
+    /// narration does not reset at every sentence (0 = off, 1 = match fully)
+    #[arg(long, value_parser = ranged(0., 1.), num_args = 0..=1, default_missing_value = "0.5")]
+    smooth_prosody: Option<f32>,
+    /// Continue after the last sentence played, as recorded in the position file
+    #[arg(long)]
+    resume: bool,
+    /// File recording the last sentence played [default: <DOCUMENT>.position]
+    #[arg(long)]
+    position: Option<PathBuf>,
+}
+
+// 最後に鳴らし終えた文。文書が書き換えられても、同じ文を探して続けられるように本文も残す
+#[derive(Serialize, Deserialize)]
+struct Position {
+    played: usize,
+    text: String,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let document = fs::read_to_string(&args.document)
+        .with_context(|| format!("cannot read {}", args.document.display()))?;
+    let sentences = split_sentences(&document);
+    let position_path = args.position.clone().unwrap_or_else(|| {
+        let mut path = OsString::from(&args.document);
+        path.push(".position");
+        path.into()
+    });
+    let start = if args.resume {
+        resume_at(&position_path, &sentences)?
+    } else {
+        0
+    };
+    if start >= sentences.len() {
+        eprintln!("already read to the end of {}", args.document.display());
+        return Ok(());
+    }
+
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let speaker_id = args.prosody.speaker(args.speaker);
+    let params = Params {
+        speaker_id,
+        options: args.prosody.to_options(&core, speaker_id),
+        ..Default::default()
+    };
+    // 鳴らしている間に次の文を合成しておき、文の間を空けない
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let (core, params, sentences) = (&core, &params, &sentences);
+        let smoother = args
+            .smooth_prosody
+            .map(|strength| Mutex::new(ProsodySmoother::new(strength)));
+        scope.spawn(move || {
+            for (i, sentence) in sentences.iter().enumerate().skip(start) {
+                let result =
+                    synthesize_smoothed(core, sentence, params, None, None, smoother.as_ref());
+                // 再生が失敗して受け手がいなくなれば止める
+                if sender.send((i, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        for (i, result) in receiver {
+            let synthesis = result?;
+            if output::json() {
+                output::emit(&serde_json::json!({
+                    "sentence": i + 1,
+                    "text": sentences[i],
+                    "duration": synthesis.wav.duration(),
+                    "warnings": synthesis.warnings,
+                }))?;
+            } else {
+                println!("[{}/{}] {}", i + 1, sentences.len(), sentences[i]);
+                for warning in &synthesis.warnings {
+                    eprintln!("warning: sentence {}: {}", i + 1, i18n::warning(warning));
+                }
+            }
+            play(&synthesis.wav)?;
+            save_position(
+                &position_path,
+                &Position {
+                    played: i + 1,
+                    text: sentences[i].to_string(),
+                },
+            )?;
+        }
+        Ok(())
+    })
+}
+
+// 句点・感嘆符・疑問符と改行で区切る。区切りの記号は前の文に含め、閉じ括弧だけのような読むもののない断片は捨てる
+fn split_sentences(document: &str) -> Vec<&str> {
+    document
+        .split_inclusive(['。', '！', '？', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|sentence| sentence.chars().any(char::is_alphanumeric))
+        .collect()
+}
+
+// 記録した位置の文が変わっていれば、同じ文を探してその次から読む。見つからなければ初めから
+fn resume_at(path: &Path, sentences: &[&str]) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let json = fs::read_to_string(path)?;
+    let position: Position = serde_json::from_str(&json)
+        .with_context(|| format!("invalid position file: {}", path.display()))?;
+    if position.played > 0 && sentences.get(position.played - 1) == Some(&position.text.as_str()) {
+        return Ok(position.played);
+    }
+    match sentences
+        .iter()
+        .position(|sentence| *sentence == position.text)
+    {
+        Some(i) => Ok(i + 1),
+        None => {
+            eprintln!("warning: the document has changed; reading from the beginning");
+            Ok(0)
+        }
+    }
+}
+
+// 書いている途中で落ちても前の位置が残るように、別名で書いてから置き換える
+fn save_position(path: &Path, position: &Position) -> Result<()> {
+    let mut temporary = OsString::from(path);
+    temporary.push(".tmp");
+    fs::write(&temporary, serde_json::to_string(position)?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use super::{errors::Failures, find_files, i18n, load_core, output, stats, terms, write_wav};
+use anyhow::Result;
+use chibivox::{AudioQueryModel, InitializeOptions, ValidationMode};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// dir以下のAudioQuery(.json)を現在のモデルで合成し直し、同名の.wavを上書きする
+#[derive(clap::Args)]
+pub struct Args {
+    /// Directory to search for AudioQuery .json files
+    dir: PathBuf,
+    /// Speaker id
+    #[arg(long, default_value_t = 0)]
+    speaker: u32,
+    /// Decode only, keeping the lengths and pitches stored in each query
+    #[arg(long)]
+    keep_prosody: bool,
+    /// How to treat malformed queries: reject them, or repair them with warnings
+    #[arg(long, value_enum, default_value_t = Validation::Strict)]
+    validation: Validation,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Validation {
+    Strict,
+    Lenient,
+}
+
+impl From<Validation> for ValidationMode {
+    fn from(validation: Validation) -> Self {
+        match validation {
+            Validation::Strict => ValidationMode::Strict,
+            Validation::Lenient => ValidationMode::Lenient,
+        }
+    }
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let paths = find_files(&args.dir, "json")?;
+    let mut failures = Failures::default();
+    for path in &paths {
+        let result = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(serde_json::from_str::<AudioQueryModel>(&json)?))
+            .and_then(|mut audio_query| {
+                let repairs = audio_query.validate_with_mode(args.validation.into())?;
+                if !output::json() {
+                    for repair in &repairs {
+                        eprintln!("{}: {repair}", path.display());
+                    }
+                }
+                if !args.keep_prosody {
+                    audio_query.accent_phrases =
+                        core.replace_mora_data(audio_query.accent_phrases, args.speaker)?;
+                }
+                let wav = core.synthesis(&audio_query, args.speaker)?;
+                stats::record(args.speaker, &wav);
+                let credit = terms::credit(&core, args.speaker);
+                let wav_path = path.with_extension("wav");
+                write_wav(&wav_path, &wav, credit)?;
+                if output::json() {
+                    output::emit(&serde_json::json!({
+                        "input": path,
+                        "path": wav_path,
+                        "duration": wav.duration(),
+                        "repairs": repairs,
+                    }))?;
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            eprintln!("{}: {err}", path.display());
+            failures.add(&err);
+        }
+    }
+
+    failures.finish(paths.len(), |failures| {
+        i18n::pick(
+            format!("{failures} of {} queries failed", paths.len()),
+            format!("{} 件中 {failures} 件の合成に失敗しました", paths.len()),
+        )
+    })
+}
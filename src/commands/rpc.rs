@@ -0,0 +1,329 @@
+// エディタやノートアプリが、言語サーバーと同じように子プロセスとして組み込むためのJSON-RPC 2.0
+// メッセージはLSPと同じく、Content-Lengthのヘッダーを前に付けて標準入出力でやりとりする
+//   audio_query {text, speaker?, kana?} -> AudioQuery
+//   synthesize {text または audio_query, speaker?} -> {wav: base64のWAV, duration, warnings}
+//   cancel {id} (LSPの$/cancelRequestも同じ) -> 指定した要求を、次の段階に進む前に止める
+// 要求はそれぞれ別のスレッドで処理するので、応答は届いた順とは限らない
+use super::{
+    errors::{self, AtStage, Stage},
+    i18n, load_core, watermark, ProsodyArgs,
+};
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chibivox::{
+    wav::SampleFormat, AudioBuffer, AudioQueryModel, InitializeOptions, Progress, Synthesis,
+    VoicevoxCore, Warnings, SAMPLING_RATE,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// 誤って大きなものを送られても、メモリを使い果たさないように
+const MAX_MESSAGE_BYTES: usize = 16 << 20;
+
+// JSON-RPCのエラーコード。REQUEST_CANCELLEDはLSPのもの
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const SERVER_ERROR: i32 = -32000;
+const REQUEST_CANCELLED: i32 = -32800;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Speak JSON-RPC over standard input and output, framed with Content-Length headers as
+    /// in language servers (currently the only transport, but required as with them)
+    #[arg(long)]
+    stdio: bool,
+    /// Speaker (style) id for requests that do not give one
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    // 無ければ通知で、応答を返さない
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    text: String,
+    speaker: Option<u32>,
+    #[serde(default)]
+    kana: bool,
+}
+
+#[derive(Deserialize)]
+struct SynthesizeParams {
+    text: Option<String>,
+    audio_query: Option<AudioQueryModel>,
+    speaker: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    id: Value,
+}
+
+// 合成の失敗ではなく、要求そのものの誤り
+#[derive(Debug)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+fn rpc_error(code: i32, message: impl Into<String>) -> anyhow::Error {
+    RpcError {
+        code,
+        message: message.into(),
+    }
+    .into()
+}
+
+// 処理中の要求の取り消しの印。idはJSONの表記で区別するので、1と"1"は別の要求
+type Running = Mutex<HashMap<String, Arc<AtomicBool>>>;
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    if !args.stdio {
+        bail!("--stdio is required");
+    }
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    eprintln!("ready");
+
+    let running = Running::default();
+    let stdout = Mutex::new(io::stdout());
+    let (core, args, running, stdout) = (&core, &args, &running, &stdout);
+    thread::scope(|scope| {
+        let mut stdin = io::stdin().lock();
+        while let Some(body) = read_message(&mut stdin)? {
+            let request = match serde_json::from_slice::<Value>(&body) {
+                Ok(request) => serde_json::from_value::<Request>(request)
+                    .map_err(|err| rpc_error(INVALID_REQUEST, err.to_string())),
+                Err(err) => Err(rpc_error(PARSE_ERROR, err.to_string())),
+            };
+            let request = match request {
+                Ok(request) => request,
+                Err(err) => {
+                    send(stdout, &error_response(&Value::Null, &err))?;
+                    continue;
+                }
+            };
+            if matches!(request.method.as_str(), "cancel" | "$/cancelRequest") {
+                let result = params::<CancelParams>(request.params).map(|params| {
+                    cancel(running, &params.id);
+                    Value::Null
+                });
+                if let Some(id) = &request.id {
+                    send(stdout, &response(id, result))?;
+                }
+                continue;
+            }
+
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let key = request.id.as_ref().map(Value::to_string);
+            if let Some(key) = &key {
+                running
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .insert(key.clone(), cancelled.clone());
+            }
+            scope.spawn(move || {
+                let result = handle(core, args, &request.method, request.params, &cancelled);
+                if let Some(key) = &key {
+                    running
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .remove(key);
+                }
+                if let Some(id) = &request.id {
+                    if let Err(err) = send(stdout, &response(id, result)) {
+                        eprintln!("warning: rpc: {err}");
+                    }
+                }
+            });
+        }
+        // 標準入力が閉じられても、処理中の要求には応答してから終わる
+        Ok(())
+    })
+}
+
+fn handle(
+    core: &VoicevoxCore,
+    args: &Args,
+    method: &str,
+    params: Value,
+    cancelled: &AtomicBool,
+) -> Result<Value> {
+    match method {
+        "audio_query" => {
+            let params: QueryParams = self::params(params)?;
+            let speaker_id = params.speaker.unwrap_or(args.prosody.speaker(args.speaker));
+            let options = args.prosody.to_options(core, speaker_id);
+            let accent_phrases = if params.kana {
+                core.create_accent_phrases_from_kana(&params.text)
+            } else {
+                core.create_accent_phrases(&params.text)
+            }
+            .at_stage(Stage::Analysis, &params.text)?;
+            check(cancelled)?;
+            let accent_phrases = core
+                .replace_mora_data(accent_phrases, speaker_id)
+                .at_stage(Stage::Prediction, &params.text)?;
+            let audio_query = core.audio_query_from_accent_phrases(accent_phrases, &options);
+            Ok(serde_json::to_value(audio_query)?)
+        }
+        "synthesize" => {
+            let params: SynthesizeParams = self::params(params)?;
+            let speaker_id = params.speaker.unwrap_or(args.prosody.speaker(args.speaker));
+            let (wav, warnings) = match (params.text, params.audio_query) {
+                (Some(text), None) => synthesize(core, args, &text, speaker_id, cancelled)?,
+                (None, Some(audio_query)) => {
+                    check(cancelled)?;
+                    audio_query
+                        .validate()
+                        .map_err(|err| rpc_error(INVALID_PARAMS, i18n::message(err.as_ref())))?;
+                    let wav = core
+                        .synthesis(&audio_query, speaker_id)
+                        .at_stage(Stage::Decode, "audio_query")?;
+                    (wav, Warnings::new())
+                }
+                _ => return Err(rpc_error(INVALID_PARAMS, "give either text or audio_query")),
+            };
+            let wav = watermark::apply(&wav);
+            Ok(json!({
+                "wav": STANDARD.encode(wav.to_wav(SampleFormat::I16)),
+                "duration": wav.duration(),
+                "warnings": warnings,
+            }))
+        }
+        _ => Err(rpc_error(
+            METHOD_NOT_FOUND,
+            format!("unknown method: {method}"),
+        )),
+    }
+}
+
+// 一段ずつ進め、その合間に取り消されていないかを確かめる
+fn synthesize(
+    core: &VoicevoxCore,
+    args: &Args,
+    text: &str,
+    speaker_id: u32,
+    cancelled: &AtomicBool,
+) -> Result<(AudioBuffer, Warnings)> {
+    let options = args.prosody.to_options(core, speaker_id);
+    let mut synthesis = Synthesis::new(core, text, speaker_id, &options);
+    let mut wav = AudioBuffer::mono(Vec::new(), SAMPLING_RATE);
+    loop {
+        check(cancelled)?;
+        match synthesis.step().at_stage(Stage::Decode, text)? {
+            Progress::Working => {}
+            Progress::Audio(audio) => wav.samples.extend(audio.samples),
+            Progress::Done => break,
+        }
+    }
+    Ok((wav, synthesis.warnings().clone()))
+}
+
+fn check(cancelled: &AtomicBool) -> Result<()> {
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(rpc_error(REQUEST_CANCELLED, "request cancelled"));
+    }
+    Ok(())
+}
+
+// 終わった要求や知らないidは無視する
+fn cancel(running: &Running, id: &Value) {
+    if let Some(cancelled) = running
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(&id.to_string())
+    {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T> {
+    serde_json::from_value(params).map_err(|err| rpc_error(INVALID_PARAMS, err.to_string()))
+}
+
+fn response(id: &Value, result: Result<Value>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => error_response(id, &err),
+    }
+}
+
+// 合成の失敗には、--error-format jsonと同じ種類を添える
+fn error_response(id: &Value, err: &anyhow::Error) -> Value {
+    let error = match err.downcast_ref::<RpcError>() {
+        Some(err) => json!({ "code": err.code, "message": err.message }),
+        None => json!({
+            "code": SERVER_ERROR,
+            "message": i18n::chain(err),
+            "data": { "kind": errors::kind(err) },
+        }),
+    };
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+// ヘッダーを空行まで読み、Content-Lengthの分だけ本文を読む。標準入力が閉じられていればNone
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    // 本文の区切りが分からなくなるので、続けられない
+    let length = length.ok_or(anyhow!("missing Content-Length header"))?;
+    if length > MAX_MESSAGE_BYTES {
+        bail!("message too long: {length} bytes (at most {MAX_MESSAGE_BYTES})");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn send(stdout: &Mutex<io::Stdout>, message: &Value) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    let mut stdout = stdout.lock().unwrap_or_else(|err| err.into_inner());
+    write!(stdout, "Content-Length: {}\r\n\r\n", body.len())?;
+    stdout.write_all(&body)?;
+    stdout.flush()?;
+    Ok(())
+}
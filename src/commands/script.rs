@@ -0,0 +1,236 @@
+use super::{
+    errors::Failures, i18n, journal::ResumeArgs, load_core, output::Record, parallel_map,
+    progress::Progress, ranged, render, resolve_jobs, OutputFormat, Params,
+};
+use anyhow::{anyhow, bail, Result};
+use chibivox::{InitializeOptions, VoicevoxCore};
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+// 台本の各行を、行ごとの話者とパラメータで合成する
+// 一行目は列名で、text, speaker, speed, pitch, output を任意の順で並べる(textのみ必須)
+#[derive(clap::Args)]
+pub struct Args {
+    /// Script file (.csv, otherwise tab-separated) with a header row naming the columns
+    /// text, speaker, speed, pitch and output; only text is required
+    script: PathBuf,
+    /// Directory to write the files into; rows without an output column are numbered
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+    /// Speaker id for rows that leave the speaker column empty
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    /// Output format [default: inferred from each output extension, otherwise wav]
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+    /// Rows to synthesize concurrently (0 = as many as the CPUs allow); warnings come out in
+    /// script order, and with --deterministic the files are identical for any value
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+    /// Pin ONNX Runtime threading so the same row always yields identical audio
+    #[arg(long)]
+    deterministic: bool,
+    #[command(flatten)]
+    resume: ResumeArgs,
+}
+
+struct Row {
+    name: String,
+    text: String,
+    speaker_id: u32,
+    // 空欄ならスタイルごとの既定値を使う
+    speed: Option<f32>,
+    pitch: Option<f32>,
+    path: PathBuf,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    // 長い台本の途中で止まらないよう、合成を始める前に全行を読んでおく
+    let rows = parse_script(&args)?;
+    fs::create_dir_all(&args.output)?;
+
+    let mut initialize_options = InitializeOptions {
+        deterministic: args.deterministic,
+        ..Default::default()
+    };
+    let jobs = resolve_jobs(args.jobs, &mut initialize_options);
+    let core = load_core(model_dir, &initialize_options)?;
+
+    let journal = args.resume.open()?;
+    let pending: Vec<&Row> = rows
+        .iter()
+        .filter(|row| !journal.should_skip(&row.path))
+        .collect();
+    if pending.len() < rows.len() {
+        eprintln!("skipping {} completed rows", rows.len() - pending.len());
+    }
+
+    let progress = Progress::new(pending.len());
+    let results = parallel_map(pending.len(), jobs, |i| {
+        let row = pending[i];
+        progress.stage(&row.name, "synthesizing");
+        let result = render_row(&core, row, args.format);
+        progress.inc();
+        let record = result?;
+        journal.complete(&row.path)?;
+        Ok(record)
+    });
+    progress.finish();
+
+    // 警告もエラーも、並行に合成した順ではなく台本の順に表示する
+    let mut failures = Failures::default();
+    for (row, result) in pending.into_iter().zip(results) {
+        match result {
+            Ok(record) => record.print()?,
+            Err(err) => {
+                eprintln!("{}: {err}", row.name);
+                failures.add(&err);
+            }
+        }
+    }
+
+    failures.finish(rows.len(), |failures| {
+        i18n::pick(
+            format!("{failures} of {} rows failed", rows.len()),
+            format!("{} 行中 {failures} 行の合成に失敗しました", rows.len()),
+        )
+    })
+}
+
+fn render_row(core: &VoicevoxCore, row: &Row, format: Option<OutputFormat>) -> Result<Record> {
+    if let Some(parent) = row.path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = core.default_options(row.speaker_id);
+    options.speed_scale = row.speed.unwrap_or(options.speed_scale);
+    options.pitch_scale = row.pitch.unwrap_or(options.pitch_scale);
+    let params = Params {
+        speaker_id: row.speaker_id,
+        options,
+        ..Default::default()
+    };
+    let synthesis = render(core, &row.text, &params, &row.path, format, false, false)?;
+    Ok(Record::new(&row.name, &row.path, &synthesis))
+}
+
+fn parse_script(args: &Args) -> Result<Vec<Row>> {
+    let csv = args
+        .script
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let split = |line: &str| {
+        if csv {
+            split_csv(line)
+        } else {
+            Ok(line.split('\t').map(str::to_string).collect())
+        }
+    };
+
+    let script = fs::read_to_string(&args.script)?;
+    // 空行と#で始まる行は読み飛ばす
+    let mut lines = script
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+    let Some((_, header)) = lines.next() else {
+        bail!("{}: empty script", args.script.display());
+    };
+    let columns: Vec<String> = split(header)?
+        .into_iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    for column in &columns {
+        if !["text", "speaker", "speed", "pitch", "output"].contains(&column.as_str()) {
+            bail!("{}: unknown column: {column}", args.script.display());
+        }
+    }
+    if !columns.iter().any(|column| column == "text") {
+        bail!("{}: missing text column", args.script.display());
+    }
+
+    let extension = args.format.map_or("wav", OutputFormat::extension);
+    let mut rows = Vec::new();
+    for (i, line) in lines {
+        let name = format!("{}:{}", args.script.display(), i + 1);
+        let fields = split(line).map_err(|err| anyhow!("{name}: {err}"))?;
+        let row = parse_row(&columns, &fields, args, rows.len() + 1, extension)
+            .map_err(|err| anyhow!("{name}: {err}"))?;
+        rows.push(Row { name, ..row });
+    }
+    Ok(rows)
+}
+
+fn parse_row(
+    columns: &[String],
+    fields: &[String],
+    args: &Args,
+    number: usize,
+    extension: &str,
+) -> Result<Row> {
+    if fields.len() > columns.len() {
+        bail!("{} fields for {} columns", fields.len(), columns.len());
+    }
+
+    let mut row = Row {
+        name: String::new(),
+        text: String::new(),
+        speaker_id: args.speaker,
+        speed: None,
+        pitch: None,
+        path: args.output.join(format!("{number:03}.{extension}")),
+    };
+    // 足りない列や空の欄は既定値のまま
+    for (column, field) in columns.iter().zip(fields) {
+        let field = field.trim();
+        if field.is_empty() {
+            continue;
+        }
+        match column.as_str() {
+            "text" => row.text = field.to_string(),
+            "speaker" => row.speaker_id = field.parse()?,
+            "speed" => row.speed = Some(ranged(0.5, 2.)(field).map_err(|err| anyhow!(err))?),
+            "pitch" => row.pitch = Some(ranged(-0.15, 0.15)(field).map_err(|err| anyhow!(err))?),
+            "output" => {
+                let path = args.output.join(field);
+                row.path = if path.extension().is_none() {
+                    path.with_extension(extension)
+                } else {
+                    path
+                };
+            }
+            _ => unreachable!(),
+        }
+    }
+    if row.text.is_empty() {
+        bail!("empty text");
+    }
+    Ok(row)
+}
+
+// ダブルクォートで囲んだ欄と、その中の""によるエスケープに対応する
+// 欄の中の改行には対応しない
+fn split_csv(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote");
+    }
+    fields.push(field);
+    Ok(fields)
+}
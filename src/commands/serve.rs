@@ -0,0 +1,1119 @@
+// VOICEVOX ENGINEと同じ形のAPI
+mod cache;
+mod cancel;
+pub mod control;
+mod engine;
+mod manifest;
+mod metrics;
+mod openapi;
+#[cfg(feature = "model-store")]
+mod store;
+mod version;
+mod zip;
+
+#[cfg(not(feature = "unstable"))]
+use super::load_core;
+use super::{
+    config,
+    errors::{self, AtStage, Stage},
+    i18n, parse_duration,
+    presets::{self, InvalidPresetError, Presets},
+    ranged, stats, terms, watermark, Lap,
+};
+#[cfg(feature = "unstable")]
+use super::{initialize_options, warn_rule_based};
+use anyhow::{anyhow, Context, Result};
+use cache::{Cache, Entry};
+use cancel::{CancelledError, Requests, Token};
+#[cfg(feature = "unstable")]
+use chibivox::Device;
+use chibivox::{
+    available_cpus, frame_count,
+    wav::{self, SampleFormat},
+    AudioBuffer, AudioQueryModel, InitializeOptions, InvalidAccentPhraseError, StyleType,
+    TermsNotAcknowledgedError, UnknownSpeakerError, UnsupportedStyleError, UserDict, UserDictError,
+    VoicevoxCore, Warnings,
+};
+use control::Defaults;
+use metrics::Queue;
+use serde::Serialize;
+#[cfg(unix)]
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use std::{
+    fmt,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TrySendError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Request, Response, ResponseBox, Server, StatusCode};
+use version::Version;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// Port to listen on
+    #[arg(long, default_value_t = 50021)]
+    port: u16,
+    /// Requests to analyze concurrently (0 = available CPUs); decoding runs on one thread
+    /// per decode device
+    #[arg(long, default_value_t = 0)]
+    workers: usize,
+    /// Most requests to hold while all workers are busy; beyond that, requests are turned
+    /// away with 429 and Retry-After instead of piling up in memory
+    #[arg(long, default_value_t = 64)]
+    max_queue: usize,
+    /// Most pending decodes to run back to back, shortest first, before taking new ones
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+    max_batch: u16,
+    /// Devices to load a decode session on, e.g. cuda:0,cuda:1; decodes go to the least
+    /// loaded one (experimental, CUDA requires the `cuda` feature)
+    #[cfg(feature = "unstable")]
+    #[arg(long, value_delimiter = ',', default_value = "cpu")]
+    devices: Vec<Device>,
+    /// Release the models and dictionary after this long without requests (e.g. 10m) and
+    /// reload them on the next one, so an always-on daemon does not hold the memory
+    #[arg(long, value_parser = parse_duration)]
+    idle_unload: Option<Duration>,
+    /// Directory with VOICEVOX ENGINE style speaker_info (<speaker_uuid>/policy.md, icons/,
+    /// ...) served at /speaker_info [default: <model-dir>/speaker_info]
+    #[arg(long)]
+    speaker_info_dir: Option<PathBuf>,
+    /// File to keep the presets of /presets, /add_preset and so on in, shared with --preset
+    /// [default: presets.toml next to the config file]
+    #[arg(long, env = presets::PATH_ENV)]
+    presets: Option<PathBuf>,
+    /// File to keep the user dictionary of /user_dict and /user_dict_word in, in the format of
+    /// VOICEVOX ENGINE's user_dict.json [default: user_dict.json next to the config file]
+    #[arg(long, env = "CHIBIVOX_USER_DICT")]
+    user_dict: Option<PathBuf>,
+    /// Require this key on every request, as `Authorization: Bearer <KEY>` or `X-API-Key:
+    /// <KEY>`; repeat or separate with commas to accept several
+    #[arg(
+        long = "api-key",
+        env = "CHIBIVOX_API_KEYS",
+        value_delimiter = ',',
+        hide_env_values = true
+    )]
+    api_keys: Vec<String>,
+    /// Accept `chibivox ctl` on a UNIX socket to change the default speaker, speed and
+    /// volume of /tts without a restart
+    #[cfg(unix)]
+    #[arg(long)]
+    control: bool,
+    /// Path of the control socket [default: $XDG_RUNTIME_DIR/chibivox.sock, otherwise in
+    /// the temp directory]
+    #[cfg(unix)]
+    #[arg(long, env = control::SOCKET_ENV)]
+    control_socket: Option<PathBuf>,
+    /// Cache /tts results in this directory, or in Redis with redis://[:PASSWORD@]HOST:PORT[/DB]
+    /// so that several servers with the same models share them
+    #[arg(long, env = "CHIBIVOX_CACHE")]
+    cache: Option<String>,
+    /// Fetch the models into --model-dir on startup from this http(s):// or s3://BUCKET/PREFIX
+    /// location, as listed with their sha256 in its manifest.json; files already present
+    /// and matching are kept (requires the `model-store` feature)
+    #[cfg(feature = "model-store")]
+    #[arg(long, env = "CHIBIVOX_MODEL_URL")]
+    model_url: Option<String>,
+}
+
+// モデルを読み込まずに返せる、モデルに添えられた情報
+struct Resources {
+    metas: PathBuf,
+    speaker_info_dir: PathBuf,
+    // /add_presetなどで書き換え、ファイルに保存する
+    presets: Mutex<Presets>,
+    // /user_dict_wordで書き換えたユーザー辞書の保存先。辞書そのものはModelsが持つ
+    user_dict: PathBuf,
+}
+
+// decodeスレッドに渡す仕事。結果はreplyに返す
+struct DecodeJob {
+    core: Arc<VoicevoxCore>,
+    audio_query: AudioQueryModel,
+    speaker_id: u32,
+    enable_interrogative_upspeak: bool,
+    // 待っている間に取り消されたものは、decodeせずに返す
+    cancelled: Token,
+    reply: Sender<Result<AudioBuffer>>,
+}
+
+// 読み込んだモデル。手放した後は、次に要るときに読み込み直す
+struct Models<F> {
+    load: F,
+    core: Mutex<Option<Arc<VoicevoxCore>>>,
+    last_used: Mutex<Instant>,
+    // 読み込み直したときにも使えるように、ここで持っておく
+    user_dict: Mutex<UserDict>,
+}
+
+impl<F: Fn() -> Result<VoicevoxCore>> Models<F> {
+    fn new(load: F, user_dict: UserDict) -> Self {
+        Self {
+            load,
+            core: Mutex::new(None),
+            last_used: Mutex::new(Instant::now()),
+            user_dict: Mutex::new(user_dict),
+        }
+    }
+
+    fn user_dict(&self) -> MutexGuard<'_, UserDict> {
+        self.user_dict.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // 写しを書き換え、updateが成功したときだけ読み込んでいるモデルと差し替える
+    // updateの中で保存するので、保存に失敗すれば元のまま
+    fn update_user_dict<T>(&self, update: impl FnOnce(&mut UserDict) -> Result<T>) -> Result<T> {
+        let result = {
+            let mut user_dict = self.user_dict();
+            let mut updated = user_dict.clone();
+            let result = update(&mut updated)?;
+            *user_dict = updated;
+            result
+        };
+        // getと同じくcore、user_dictの順に取る。その時点の最新を渡すので、更新が重なっても古い方に戻らない
+        if let Some(core) = &*self.core.lock().unwrap_or_else(|err| err.into_inner()) {
+            core.set_user_dict(self.user_dict().clone());
+        }
+        Ok(result)
+    }
+
+    // 読み込み直している間は、他のリクエストもここで待つ
+    fn get(&self) -> Result<Arc<VoicevoxCore>> {
+        *self.last_used.lock().unwrap_or_else(|err| err.into_inner()) = Instant::now();
+        let mut core = self.core.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(core) = &*core {
+            return Ok(core.clone());
+        }
+        let loaded = (self.load)()?;
+        loaded.set_user_dict(self.user_dict().clone());
+        let loaded = Arc::new(loaded);
+        *core = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    // 新しいモデルを読み込み終えてから差し替える。それまでのリクエストは今のモデルで答え続け、
+    // 処理中のリクエストが持っている古いモデルは、それが終わったときに解放される
+    // 読み込みに失敗すれば、今のモデルを使い続ける
+    fn reload(&self) -> Result<()> {
+        let loaded = (self.load)()?;
+        warm_up(&loaded);
+        let mut core = self.core.lock().unwrap_or_else(|err| err.into_inner());
+        loaded.set_user_dict(self.user_dict().clone());
+        *core = Some(Arc::new(loaded));
+        Ok(())
+    }
+
+    // 処理中のリクエストが持っている分は、それが終わったときに解放される
+    fn unload_if_idle(&self, idle: Duration) {
+        let last_used = *self.last_used.lock().unwrap_or_else(|err| err.into_inner());
+        if last_used.elapsed() < idle {
+            return;
+        }
+        if self
+            .core
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+            .is_some()
+        {
+            eprintln!("unloaded models after {} s idle", idle.as_secs());
+        }
+    }
+}
+
+// モデルは起動時に読み込み、--idle-unloadが無ければそのまま持ち続ける
+// 読み込みと一度目の合成が終わるまでは全てのリクエストに503を返すので、GET /readyで準備ができたかを確かめられる
+// GET /healthはプロセスが応答できれば、読み込み中でも200を返す
+// テキスト解析と長さ・音高の予測はworkersで並行に行い、decodeだけはデバイスごとのスレッドにまとめる
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let user_dict_path = args
+        .user_dict
+        .clone()
+        .or_else(|| Some(config::dir()?.join("user_dict.json")))
+        .ok_or(anyhow!("cannot locate user_dict.json; give --user-dict"))?;
+    let user_dict = UserDict::load(&user_dict_path)
+        .with_context(|| format!("user dictionary: {}", user_dict_path.display()))?;
+    #[cfg(feature = "unstable")]
+    let (models, decode_threads) = {
+        let devices = args.devices.clone();
+        let load = move || -> Result<VoicevoxCore> {
+            let options = initialize_options(&InitializeOptions::default());
+            let core = VoicevoxCore::new_with_decode_devices(model_dir, &options, &devices)
+                .at_stage(Stage::Load, &model_dir.display().to_string())?;
+            warn_rule_based(&core, model_dir);
+            Ok(core)
+        };
+        (Models::new(load, user_dict), args.devices.len())
+    };
+    #[cfg(not(feature = "unstable"))]
+    let (models, decode_threads) = (
+        Models::new(
+            || load_core(model_dir, &InitializeOptions::default()),
+            user_dict,
+        ),
+        1,
+    );
+    let presets_path = args
+        .presets
+        .clone()
+        .or_else(presets::path)
+        .ok_or(anyhow!("cannot locate presets.toml; give --presets"))?;
+    let resources = Resources {
+        metas: model_dir.join("metas.json"),
+        speaker_info_dir: args
+            .speaker_info_dir
+            .clone()
+            .unwrap_or_else(|| model_dir.join("speaker_info")),
+        presets: Mutex::new(Presets::load(&presets_path)?),
+        user_dict: user_dict_path,
+    };
+    let server = Server::http((args.host.as_str(), args.port)).map_err(|err| anyhow!(err))?;
+    eprintln!("listening on http://{}:{}", args.host, args.port);
+    if args.api_keys.is_empty() && !is_loopback(&args.host) {
+        eprintln!("warning: no --api-key; anyone who can reach this address can synthesize");
+    }
+
+    let (cache, version) = thread::scope(|scope| {
+        let loaded = AtomicBool::new(false);
+        let (server, loaded) = (&server, &loaded);
+        scope.spawn(move || {
+            while !loaded.load(Ordering::Relaxed) {
+                if let Ok(Some(request)) = server.recv_timeout(Duration::from_millis(100)) {
+                    let received = Instant::now();
+                    if is_health(&request) {
+                        respond(request, text_response(200, "ok").boxed(), received);
+                        continue;
+                    }
+                    let retry_after = RETRY_AFTER.as_secs().to_string();
+                    let response = text_response(503, "loading models")
+                        .with_header(Header::from_bytes("Retry-After", retry_after).unwrap());
+                    respond(request, response.boxed(), received);
+                }
+            }
+        });
+        let result = load(&args, model_dir, &models);
+        loaded.store(true, Ordering::Relaxed);
+        result
+    })?;
+    eprintln!("ready");
+    // 読み込み直したときだけ変わるので、書き出したものを持っておく
+    let version = Mutex::new(serde_json::to_vec(&version)?);
+    let openapi = openapi::json()?;
+    let engine_manifest = manifest::json()?;
+    // SIGHUPでも読み込み直す。登録すると、SIGHUPで終了しなくなる
+    #[cfg(unix)]
+    let mut signals = Signals::new([SIGHUP])?;
+
+    let defaults = Mutex::new(Defaults::default());
+    #[cfg(unix)]
+    let control_listener = if args.control {
+        let path = args
+            .control_socket
+            .clone()
+            .unwrap_or_else(control::default_socket);
+        let listener = control::bind(&path)?;
+        eprintln!("control socket at {}", path.display());
+        Some(listener)
+    } else {
+        None
+    };
+
+    let workers = if args.workers == 0 {
+        available_cpus()
+    } else {
+        args.workers
+    };
+    let (decode_sender, decode_receiver) = mpsc::channel();
+    let decode_receiver = Mutex::new(decode_receiver);
+    let requests = Requests::default();
+    let (request_sender, request_receiver) = mpsc::sync_channel(args.max_queue);
+    let request_receiver = Mutex::new(request_receiver);
+    // Noneならシグナルによるもので、応答する相手はいない
+    let (reload_sender, reload_receiver) = mpsc::channel::<Option<(Request, Instant)>>();
+    let execution_providers = execution_providers(&args);
+    let (models, resources, defaults, cache, decode_receiver, request_receiver, version) = (
+        &models,
+        &resources,
+        &defaults,
+        cache.as_ref(),
+        &decode_receiver,
+        &request_receiver,
+        &version,
+    );
+    thread::scope(|scope| {
+        if let Some(idle) = args.idle_unload {
+            scope.spawn(move || loop {
+                thread::sleep(idle.min(Duration::from_secs(10)));
+                models.unload_if_idle(idle);
+            });
+        }
+        #[cfg(unix)]
+        if let Some(listener) = control_listener {
+            scope.spawn(move || control::listen(listener, defaults));
+        }
+        // 重なった読み込み直しは、順に行う
+        let execution_providers = &execution_providers;
+        scope.spawn(move || {
+            for request in reload_receiver {
+                let response = match reload(model_dir, models, cache, execution_providers) {
+                    Ok(reloaded) => {
+                        eprintln!("reloaded models");
+                        *version.lock().unwrap_or_else(|err| err.into_inner()) = reloaded.clone();
+                        Response::from_data(reloaded).with_header(content_type("application/json"))
+                    }
+                    Err(err) => {
+                        eprintln!("warning: reload failed; keeping the current models: {err:#}");
+                        metrics::error(errors::kind(&err));
+                        error_response(&err)
+                    }
+                };
+                if let Some((request, received)) = request {
+                    respond(request, response.boxed(), received);
+                }
+            }
+        });
+        #[cfg(unix)]
+        {
+            let reload_sender = reload_sender.clone();
+            scope.spawn(move || {
+                for _ in signals.forever() {
+                    let _ = reload_sender.send(None);
+                }
+            });
+        }
+        for _ in 0..decode_threads {
+            scope.spawn(move || decode_loop(decode_receiver, args.max_batch.into()));
+        }
+        for _ in 0..workers {
+            let decode_sender = decode_sender.clone();
+            scope.spawn(move || loop {
+                let request = request_receiver
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .recv();
+                let Ok((mut request, received, ticket)) = request else {
+                    return;
+                };
+                metrics::dequeued(Queue::Requests);
+                let response = ticket
+                    .token
+                    .check()
+                    .and_then(|()| {
+                        handle(
+                            models,
+                            resources,
+                            defaults,
+                            cache,
+                            &decode_sender,
+                            &ticket.token,
+                            &mut request,
+                        )
+                    })
+                    .unwrap_or_else(|err| {
+                        // 取り消しはクライアントの都合なので、誤りとして数えない
+                        if err.downcast_ref::<CancelledError>().is_none() {
+                            metrics::error(errors::kind(&err));
+                        }
+                        error_response(&err).boxed()
+                    });
+                let response = response
+                    .with_header(Header::from_bytes("X-Request-Id", ticket.id.as_str()).unwrap());
+                respond(request, response, received);
+            });
+        }
+        // workersが全て終われば、decodeスレッドも終わる
+        drop(decode_sender);
+
+        // 受け付けたリクエストをworkersに渡す。鍵の確認と混雑時の断りは、モデルに触れないのでここで済ませる
+        for request in server.incoming_requests() {
+            let received = Instant::now();
+            let is_get = request.method() == &Method::Get;
+            // ロードバランサーなどの確認には、鍵を求めない
+            if is_get && metrics::route(request.url()) == "/ready" {
+                respond(request, text_response(200, "ready").boxed(), received);
+                continue;
+            }
+            if is_health(&request) {
+                respond(request, text_response(200, "ok").boxed(), received);
+                continue;
+            }
+            if !authorized(&request, &args.api_keys) {
+                let response = text_response(401, "unauthorized")
+                    .with_header(Header::from_bytes("WWW-Authenticate", "Bearer").unwrap());
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            // 混んでいるときこそ見たいので、workersを待たせずに返す
+            if is_get && metrics::route(request.url()) == "/metrics" {
+                let response = Response::from_data(metrics::render())
+                    .with_header(content_type("text/plain; version=0.0.4"));
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            if is_get && metrics::route(request.url()) == "/version" {
+                let version = version
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .clone();
+                let response =
+                    Response::from_data(version).with_header(content_type("application/json"));
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            if is_get && metrics::route(request.url()) == "/engine_manifest" {
+                let response = Response::from_data(engine_manifest.as_slice())
+                    .with_header(content_type("application/json"));
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            if is_get && metrics::route(request.url()) == "/openapi.json" {
+                let response = Response::from_data(openapi.as_slice())
+                    .with_header(content_type("application/json"));
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            // 取り消す相手はworkersの中にいるので、空くのを待たずに返す
+            if request.method() == &Method::Post && metrics::route(request.url()) == "/cancel" {
+                let query = request.url().split_once('?').map_or("", |(_, query)| query);
+                let response = match query_param(query, "id") {
+                    Some(id) if requests.cancel(&id) => text_response(204, ""),
+                    Some(_) => text_response(404, "unknown request id"),
+                    None => text_response(422, "id is required"),
+                };
+                respond(request, response.boxed(), received);
+                continue;
+            }
+            // 読み込み直すには時間がかかるので、workersではなく専用のスレッドに任せる
+            if request.method() == &Method::Post && metrics::route(request.url()) == "/reload" {
+                let _ = reload_sender.send(Some((request, received)));
+                continue;
+            }
+            metrics::enqueued(Queue::Requests);
+            let ticket = requests.start(&request);
+            match request_sender.try_send((request, received, ticket)) {
+                Ok(()) => {}
+                Err(TrySendError::Full((request, _, _))) => {
+                    metrics::dequeued(Queue::Requests);
+                    let retry_after = RETRY_AFTER.as_secs().to_string();
+                    let response = text_response(429, "too many requests")
+                        .with_header(Header::from_bytes("Retry-After", retry_after).unwrap());
+                    respond(request, response.boxed(), received);
+                }
+                Err(TrySendError::Disconnected(_)) => break,
+            }
+        }
+        drop(request_sender);
+        drop(reload_sender);
+    });
+
+    Ok(())
+}
+
+// --model-urlがあれば、モデルを取ってきてから読み込む。キャッシュの鍵と/versionはモデルの中身に依るので、その後で作る
+fn load(
+    args: &Args,
+    model_dir: &Path,
+    models: &Models<impl Fn() -> Result<VoicevoxCore>>,
+) -> Result<(Option<Cache>, Version)> {
+    #[cfg(feature = "model-store")]
+    if let Some(url) = &args.model_url {
+        store::fetch(url, model_dir)?;
+    }
+    warm_up(&models.get()?);
+    let version = Version::new(model_dir, execution_providers(args))?;
+    let cache = args
+        .cache
+        .as_deref()
+        .map(|location| Cache::open(location, &version))
+        .transpose()?;
+    Ok((cache, version))
+}
+
+// 置き換えたモデルを読み込み直し、キャッシュの鍵と/versionを作り直す。新しい/versionのJSONを返す
+// モデルは--model-dirから読み、--model-urlからは取り直さない
+fn reload(
+    model_dir: &Path,
+    models: &Models<impl Fn() -> Result<VoicevoxCore>>,
+    cache: Option<&Cache>,
+    execution_providers: &[String],
+) -> Result<Vec<u8>> {
+    let version = Version::new(model_dir, execution_providers.to_vec())?;
+    models.reload()?;
+    if let Some(cache) = cache {
+        cache.reload(&version);
+    }
+    Ok(serde_json::to_vec(&version)?)
+}
+
+#[cfg(feature = "unstable")]
+fn execution_providers(args: &Args) -> Vec<String> {
+    args.devices.iter().map(ToString::to_string).collect()
+}
+
+#[cfg(not(feature = "unstable"))]
+fn execution_providers(_args: &Args) -> Vec<String> {
+    vec!["cpu".to_string()]
+}
+
+// 一度目の合成はセッションの初期化などで遅いので、/readyが200を返す前に済ませておく
+// 失敗しても、そのスタイルが使えないだけかもしれないので警告に留める
+fn warm_up(core: &VoicevoxCore) {
+    let Some(style) = core
+        .metas()
+        .iter()
+        .flat_map(|speaker| &speaker.styles)
+        .find(|style| style.style_type == StyleType::Talk)
+    else {
+        return;
+    };
+    if let Err(err) = core.tts("あ", style.id) {
+        eprintln!("warning: warm-up failed: {err}");
+    }
+}
+
+// プロセスが生きているかの確認。鍵もモデルも要らない
+fn is_health(request: &Request) -> bool {
+    request.method() == &Method::Get && metrics::route(request.url()) == "/health"
+}
+
+// 溜まっているdecodeをまとめて取り出し、セッションを取り合わずに続けて実行する
+// モデルにバッチの次元が無くても、スレッドの切り替えとセッションの競合が無くなる分だけ速い
+// デバイスが複数あれば、スレッドごとに空いているセッションが選ばれる
+fn decode_loop(jobs: &Mutex<Receiver<DecodeJob>>, max_batch: usize) {
+    loop {
+        let mut batch = {
+            let jobs = jobs.lock().unwrap_or_else(|err| err.into_inner());
+            let Ok(job) = jobs.recv() else {
+                return;
+            };
+            let mut batch = vec![job];
+            batch.extend(jobs.try_iter().take(max_batch - 1));
+            batch
+        };
+        // 短いものから返して、待ち時間の合計を減らす
+        batch.sort_by_key(|job| frame_count(&job.audio_query, job.audio_query.speed_scale));
+        for job in batch {
+            metrics::dequeued(Queue::Decode);
+            if let Err(err) = job.cancelled.check() {
+                let _ = job.reply.send(Err(err));
+                continue;
+            }
+            let mut lap = Lap::new();
+            let result = if job.enable_interrogative_upspeak {
+                job.core.synthesis(&job.audio_query, job.speaker_id)
+            } else {
+                job.core
+                    .synthesis_decode_only(&job.audio_query, job.speaker_id)
+            };
+            metrics::stage(Stage::Decode, lap.split());
+            // 応答を待たずに切れた接続の分は捨てる
+            let _ = job.reply.send(result);
+        }
+    }
+}
+
+fn decode(
+    decode_sender: &Sender<DecodeJob>,
+    cancelled: &Token,
+    core: Arc<VoicevoxCore>,
+    audio_query: AudioQueryModel,
+    speaker_id: u32,
+    enable_interrogative_upspeak: bool,
+) -> Result<AudioBuffer> {
+    cancelled.check()?;
+    let (reply, result) = mpsc::channel();
+    // 取り出される前に数えておく
+    metrics::enqueued(Queue::Decode);
+    decode_sender
+        .send(DecodeJob {
+            core,
+            audio_query,
+            speaker_id,
+            enable_interrogative_upspeak,
+            cancelled: cancelled.clone(),
+            reply,
+        })
+        .map_err(|_| {
+            metrics::dequeued(Queue::Decode);
+            anyhow!("decode thread has stopped")
+        })?;
+    result
+        .recv()
+        .map_err(|_| anyhow!("decode thread has stopped"))?
+}
+
+fn handle(
+    models: &Models<impl Fn() -> Result<VoicevoxCore>>,
+    resources: &Resources,
+    defaults: &Mutex<Defaults>,
+    cache: Option<&Cache>,
+    decode_sender: &Sender<DecodeJob>,
+    cancelled: &Token,
+    request: &mut Request,
+) -> Result<ResponseBox> {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((url.as_str(), ""));
+
+    match (&method, path) {
+        // 本文のテキストを合成してWAVを返す
+        (Method::Post, "/tts") => {
+            let defaults = *defaults.lock().unwrap_or_else(|err| err.into_inner());
+            let Some(speaker_id) = speaker_param(query).or(defaults.speaker) else {
+                return Ok(text_response(422, "speaker is required").boxed());
+            };
+            let stream = match query_param(query, "stream").as_deref() {
+                None => None,
+                Some("wav") => Some(StreamFormat::Wav),
+                Some("pcm") => Some(StreamFormat::Pcm),
+                Some(_) => return Ok(text_response(422, "stream must be wav or pcm").boxed()),
+            };
+            let text = read_body(request, MAX_TEXT_BYTES)?;
+
+            let core = models.get()?;
+            let mut options = core.default_options(speaker_id);
+            defaults.apply(&mut options);
+            let credit = terms::credit(&core, speaker_id);
+            // 少しずつ送るものは、途中で切れることがあるのでキャッシュしない
+            // /user_dict_wordで読みを変えた語が、前の読みのまま返らないようにユーザー辞書も含める
+            let cache_key = cache.filter(|_| stream.is_none()).map(|cache| {
+                cache.key(serde_json::json!({
+                    "text": text,
+                    "speaker": speaker_id,
+                    "speed": options.speed_scale,
+                    "pitch": options.pitch_scale,
+                    "intonation": options.intonation_scale,
+                    "volume": options.volume_scale,
+                    "pre_phoneme_length": options.pre_phoneme_length,
+                    "post_phoneme_length": options.post_phoneme_length,
+                    "interrogative_upspeak": options.enable_interrogative_upspeak,
+                    "watermark": watermark::enabled(),
+                    "credit": credit,
+                    "user_dict": *models.user_dict(),
+                }))
+            });
+            if let Some((cache, key)) = cache.zip(cache_key.as_ref()) {
+                if let Some(entry) = cache.get(key) {
+                    let mut response = Response::from_data(entry.body)
+                        .with_header(content_type("audio/wav"))
+                        .with_header(cache_header("hit"));
+                    if !entry.warnings.is_empty() {
+                        response = response
+                            .with_header(Header::from_bytes("X-Warnings", entry.warnings).unwrap());
+                    }
+                    return Ok(response.boxed());
+                }
+            }
+            // /metricsに段階ごとの時間を出せるように、一段ずつ進める
+            let mut warnings = Warnings::new();
+            let mut lap = Lap::new();
+            let accent_phrases = core
+                .create_accent_phrases_with_warnings(&text, &mut warnings)
+                .at_stage(Stage::Analysis, &text)?;
+            metrics::stage(Stage::Analysis, lap.split());
+            cancelled.check()?;
+            let accent_phrases = core
+                .replace_mora_data_with_warnings(accent_phrases, speaker_id, &mut warnings)
+                .at_stage(Stage::Prediction, &text)?;
+            metrics::stage(Stage::Prediction, lap.split());
+            cancelled.check()?;
+            let audio_query = core.audio_query_from_accent_phrases(accent_phrases, &options);
+            if let Some(format) = stream {
+                let stream = Stream::new(
+                    decode_sender.clone(),
+                    cancelled.clone(),
+                    core,
+                    &audio_query,
+                    speaker_id,
+                    options.enable_interrogative_upspeak,
+                    format,
+                );
+                return Ok(with_warnings(stream.into_response(), &warnings).boxed());
+            }
+            let wav = decode(
+                decode_sender,
+                cancelled,
+                core.clone(),
+                audio_query,
+                speaker_id,
+                options.enable_interrogative_upspeak,
+            )?;
+            let Some((cache, key)) = cache.zip(cache_key) else {
+                return Ok(wav_response(speaker_id, &wav, warnings, credit).boxed());
+            };
+            let body = encode_wav(speaker_id, &wav, &mut warnings, credit);
+            let warnings_value = if warnings.is_empty() {
+                String::new()
+            } else {
+                warnings_header(&warnings).value.to_string()
+            };
+            let entry = Entry {
+                warnings: warnings_value,
+                body,
+            };
+            cache.put(&key, &entry);
+            let response = Response::from_data(entry.body)
+                .with_header(content_type("audio/wav"))
+                .with_header(cache_header("miss"));
+            Ok(with_warnings(response, &warnings).boxed())
+        }
+        _ => Ok(engine::handle(
+            models,
+            resources,
+            decode_sender,
+            cancelled,
+            request,
+            path,
+            query,
+        )?
+        .unwrap_or_else(|| text_response(404, "not found"))
+        .boxed()),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum StreamFormat {
+    // 長さを決めずにヘッダーを書いた16bitのWAV
+    Wav,
+    // ヘッダーの無い16bit little-endianのPCM
+    Pcm,
+}
+
+// 息継ぎごとにdecodeし、できたそばからchunkedで送り出す
+// 送り始めた後はヘッダーを変えられないので、音割れの警告とクレジットは付けない
+struct Stream {
+    decode_sender: Sender<DecodeJob>,
+    cancelled: Token,
+    core: Arc<VoicevoxCore>,
+    groups: std::vec::IntoIter<AudioQueryModel>,
+    speaker_id: u32,
+    enable_interrogative_upspeak: bool,
+    format: StreamFormat,
+    watermark: Option<chibivox::watermark::Embedder>,
+    sample_rate: u32,
+    channels: u16,
+    seconds: f64,
+    buffer: Cursor<Vec<u8>>,
+}
+
+impl Stream {
+    fn new(
+        decode_sender: Sender<DecodeJob>,
+        cancelled: Token,
+        core: Arc<VoicevoxCore>,
+        audio_query: &AudioQueryModel,
+        speaker_id: u32,
+        enable_interrogative_upspeak: bool,
+        format: StreamFormat,
+    ) -> Self {
+        let sample_rate = audio_query.output_sampling_rate;
+        let channels = if audio_query.output_stereo { 2 } else { 1 };
+        let header = match format {
+            StreamFormat::Wav => wav::wav_header(
+                sample_rate,
+                channels,
+                SampleFormat::I16,
+                wav::STREAMING_DATA_SIZE,
+            )
+            .to_vec(),
+            StreamFormat::Pcm => Vec::new(),
+        };
+        Self {
+            decode_sender,
+            cancelled,
+            core,
+            groups: audio_query.split_breath_groups().into_iter(),
+            speaker_id,
+            enable_interrogative_upspeak,
+            format,
+            watermark: watermark::embedder(),
+            sample_rate,
+            channels,
+            seconds: 0.,
+            buffer: Cursor::new(header),
+        }
+    }
+
+    fn into_response(self) -> Response<Self> {
+        let content_type = match self.format {
+            StreamFormat::Wav => content_type("audio/wav"),
+            StreamFormat::Pcm => content_type("application/octet-stream"),
+        };
+        let format = Header::from_bytes(
+            "X-Audio-Format",
+            format!("s16le;rate={};channels={}", self.sample_rate, self.channels),
+        )
+        .unwrap();
+        // 長さを渡さなければ、tiny_httpがchunkedで送る
+        Response::new(
+            StatusCode(200),
+            vec![content_type, format],
+            self,
+            None,
+            None,
+        )
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.buffer.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let Some(audio_query) = self.groups.next() else {
+                return Ok(0);
+            };
+            // 途中で失敗した場合は接続を切る。クライアントからは音声が途切れたように見える
+            let wav = decode(
+                &self.decode_sender,
+                &self.cancelled,
+                self.core.clone(),
+                audio_query,
+                self.speaker_id,
+                self.enable_interrogative_upspeak,
+            )
+            .map_err(std::io::Error::other)?;
+            let wav = match &mut self.watermark {
+                Some(embedder) => embedder.embed(&wav),
+                None => wav,
+            };
+            self.seconds += wav.duration() as f64;
+            self.buffer = Cursor::new(wav::encode_samples(&wav.samples, SampleFormat::I16));
+        }
+    }
+}
+
+// 接続が途中で切れた場合も、送り出した分だけを記録する
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if self.seconds > 0. {
+            stats::record_seconds(self.speaker_id, self.seconds);
+            metrics::audio(self.seconds);
+        }
+    }
+}
+
+// 混雑して断ったときに、再び送るまで待ってほしい時間
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+fn respond(request: Request, response: ResponseBox, received: Instant) {
+    let route = metrics::route(request.url());
+    let status = response.status_code().0;
+    if let Err(err) = request.respond(response) {
+        eprintln!("{err}");
+    }
+    metrics::request(route, status, received.elapsed());
+}
+
+// 鍵が設定されていなければ誰でも使える
+fn authorized(request: &Request, api_keys: &[String]) -> bool {
+    if api_keys.is_empty() {
+        return true;
+    }
+    request.headers().iter().any(|header| {
+        let value = header.value.as_str();
+        let key = if header.field.equiv("Authorization") {
+            value.strip_prefix("Bearer ")
+        } else if header.field.equiv("X-API-Key") {
+            Some(value)
+        } else {
+            None
+        };
+        key.is_some_and(|key| {
+            api_keys
+                .iter()
+                .any(|api_key| constant_time_eq(key.trim().as_bytes(), api_key.as_bytes()))
+        })
+    })
+}
+
+// 一致するまでの時間から鍵を推測されないように、長さが同じなら最後まで比べる
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+// 書き出す前の最後の処理をして、16bitのWAVで返す
+fn wav_response(
+    speaker_id: u32,
+    wav: &AudioBuffer,
+    mut warnings: Warnings,
+    credit: Option<&str>,
+) -> Response<Cursor<Vec<u8>>> {
+    let data = encode_wav(speaker_id, wav, &mut warnings, credit);
+    with_warnings(
+        Response::from_data(data).with_header(content_type("audio/wav")),
+        &warnings,
+    )
+}
+
+fn encode_wav(
+    speaker_id: u32,
+    wav: &AudioBuffer,
+    warnings: &mut Warnings,
+    credit: Option<&str>,
+) -> Vec<u8> {
+    let wav = watermark::apply(wav);
+    warnings.check_clipping(&wav);
+    stats::record(speaker_id, &wav);
+    metrics::audio(wav.duration() as f64);
+    let mut data = wav.to_wav(SampleFormat::I16);
+    if let Some(credit) = credit {
+        wav::append_chunk(&mut data, &wav::info_chunk(credit));
+    }
+    data
+}
+
+fn json_response(value: &impl Serialize) -> Result<Response<Cursor<Vec<u8>>>> {
+    Ok(Response::from_data(serde_json::to_vec(value)?)
+        .with_header(content_type("application/json")))
+}
+
+fn with_warnings<R: Read>(response: Response<R>, warnings: &Warnings) -> Response<R> {
+    if warnings.is_empty() {
+        response
+    } else {
+        response.with_header(warnings_header(warnings))
+    }
+}
+
+// 誤って大きなファイルを送られても、メモリを使い果たさないように
+const MAX_TEXT_BYTES: usize = 1 << 20;
+
+// 本文が上限を超えた。413で断る
+#[derive(Debug)]
+struct BodyTooLargeError {
+    limit: usize,
+}
+
+impl fmt::Display for BodyTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body too large (at most {} bytes)", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLargeError {}
+
+// Content-Lengthで分かれば読む前に断り、無くてもlimitを超えたところで読むのをやめる
+fn read_body(request: &mut Request, limit: usize) -> Result<String> {
+    if request.body_length().is_some_and(|length| length > limit) {
+        return Err(BodyTooLargeError { limit }.into());
+    }
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)?;
+    if body.len() > limit {
+        return Err(BodyTooLargeError { limit }.into());
+    }
+    Ok(String::from_utf8(body)?)
+}
+
+fn speaker_param(query: &str) -> Option<u32> {
+    query_param(query, "speaker")?.parse().ok()
+}
+
+// 値はパーセントエンコーディングを戻して返す
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(k, v)| (k == key).then(|| percent_decode(v)))
+}
+
+// +は空白にする。壊れた%はそのまま残す
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).unwrap()
+}
+
+// --cacheを付けた場合に、キャッシュから返したかどうか
+fn cache_header(value: &str) -> Header {
+    Header::from_bytes("X-Cache", value).unwrap()
+}
+
+// 警告をJSONの配列で返す。ヘッダーにはASCIIしか書けないので、それ以外は\uXXXXにする
+fn warnings_header(warnings: &Warnings) -> Header {
+    let json = serde_json::to_string(warnings).unwrap();
+    let mut value = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c.is_ascii() {
+            value.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                value.push_str(&format!("\\u{unit:04x}"));
+            }
+        }
+    }
+    Header::from_bytes("X-Warnings", value).unwrap()
+}
+
+// 話者の指定の誤りは、リクエストの誤りとして返す
+fn error_response(err: &anyhow::Error) -> Response<Cursor<Vec<u8>>> {
+    let status = if err.downcast_ref::<UnknownSpeakerError>().is_some()
+        || err.downcast_ref::<UnsupportedStyleError>().is_some()
+        || err.downcast_ref::<InvalidAccentPhraseError>().is_some()
+        || err.downcast_ref::<InvalidPresetError>().is_some()
+        || err.downcast_ref::<UserDictError>().is_some()
+    {
+        422
+    } else if err.downcast_ref::<TermsNotAcknowledgedError>().is_some() {
+        403
+    } else if err.downcast_ref::<BodyTooLargeError>().is_some() {
+        413
+    } else if err.downcast_ref::<CancelledError>().is_some() {
+        // nginxと同じく、クライアントが取り下げたリクエスト
+        499
+    } else {
+        500
+    };
+    text_response(status, &i18n::message(err.as_ref()))
+}
+
+fn text_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(message).with_status_code(status)
+}
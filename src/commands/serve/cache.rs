@@ -0,0 +1,269 @@
+// /ttsの結果を、同じ入力に対して合成し直さずに返すためのキャッシュ
+// 複数のサーバーで共有できるように、置き場所はCacheBackendとして差し替えられる
+use super::version::Version;
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    fn put(&self, key: &str, value: &[u8]) -> Result<()>;
+}
+
+// キャッシュした応答。warningsはX-Warningsヘッダーの値で、警告が無ければ空
+pub struct Entry {
+    pub warnings: String,
+    pub body: Vec<u8>,
+}
+
+impl Entry {
+    // ヘッダーの値は改行を含まないので、一行目に置く
+    fn encode(&self) -> Vec<u8> {
+        let mut value = Vec::with_capacity(self.warnings.len() + 1 + self.body.len());
+        value.extend_from_slice(self.warnings.as_bytes());
+        value.push(b'\n');
+        value.extend_from_slice(&self.body);
+        value
+    }
+
+    fn decode(mut value: Vec<u8>) -> Option<Self> {
+        let newline = value.iter().position(|&b| b == b'\n')?;
+        let body = value.split_off(newline + 1);
+        value.truncate(newline);
+        Some(Self {
+            warnings: String::from_utf8(value).ok()?,
+            body,
+        })
+    }
+}
+
+pub struct Cache {
+    backend: Box<dyn CacheBackend>,
+    // POST /reloadなどでモデルを読み込み直したときに取り直す
+    models: Mutex<String>,
+}
+
+impl Cache {
+    // redis://で始まればRedis、それ以外はディレクトリに置く
+    pub fn open(location: &str, version: &Version) -> Result<Self> {
+        let backend: Box<dyn CacheBackend> = match location.strip_prefix("redis://") {
+            Some(address) => Box::new(RedisCache::connect(address)?),
+            None => Box::new(DiskCache::new(location.into())?),
+        };
+        Ok(Self {
+            backend,
+            models: Mutex::new(fingerprint(version)),
+        })
+    }
+
+    // 読み込み直したモデルの結果を、前のモデルの結果と取り違えないように
+    pub fn reload(&self, version: &Version) {
+        *self.models() = fingerprint(version);
+    }
+
+    fn models(&self) -> MutexGuard<'_, String> {
+        self.models.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // 同じモデルを置いた別のサーバーとは同じ鍵になる。版が変われば結果も変わりうるので、別の鍵にする
+    // 共有したキャッシュに、鍵が衝突する本文で別の音声を置かれないように、sha256にする
+    pub fn key(&self, request: Value) -> String {
+        let material = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "models": *self.models(),
+            "request": request,
+        });
+        format!("{:x}", Sha256::digest(material.to_string()))
+    }
+
+    // キャッシュが使えなくても合成はできるので、失敗は警告に留める
+    pub fn get(&self, key: &str) -> Option<Entry> {
+        match self.backend.get(key) {
+            Ok(value) => value.and_then(Entry::decode),
+            Err(err) => {
+                eprintln!("warning: cache: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, entry: &Entry) {
+        if let Err(err) = self.backend.put(key, &entry.encode()) {
+            eprintln!("warning: cache: {err}");
+        }
+    }
+}
+
+// 鍵ごとに一つのファイル。同じディレクトリを共有するサーバーが読みかけのファイルを拾わないよう、別名で書いてから置き換える
+// 大きさの上限は無いので、古いものは必要に応じて消す
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl CacheBackend for DiskCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let temporary = self.dir.join(format!(".{key}.{}.tmp", std::process::id()));
+        fs::write(&temporary, value)?;
+        fs::rename(&temporary, self.dir.join(key))?;
+        Ok(())
+    }
+}
+
+const REDIS_TIMEOUT: Duration = Duration::from_secs(2);
+const REDIS_PREFIX: &str = "chibivox:tts:";
+
+// redis://[:password@]host:port[/db]。追い出しはRedisのmaxmemory-policyに任せる
+// 取りこぼしても合成し直すだけなので、命令はREDIS_TIMEOUTで諦め、失敗はCacheが警告に留める
+pub struct RedisCache {
+    address: String,
+    password: Option<String>,
+    db: Option<String>,
+    // 空いている接続。workersが並行に使えるように、命令の間だけ取り出す
+    // 同時に使う数までしか増えないので、上限は設けない
+    idle: Mutex<Vec<BufReader<TcpStream>>>,
+}
+
+impl RedisCache {
+    pub fn connect(url: &str) -> Result<Self> {
+        let (password, rest) = match url.rsplit_once('@') {
+            Some((userinfo, rest)) => {
+                let password = userinfo.rsplit(':').next().unwrap_or(userinfo);
+                (Some(password.to_string()), rest)
+            }
+            None => (None, url),
+        };
+        let (address, db) = match rest.split_once('/') {
+            Some((address, db)) if !db.is_empty() => (address, Some(db.to_string())),
+            Some((address, _)) => (address, None),
+            None => (rest, None),
+        };
+        let cache = Self {
+            address: address.to_string(),
+            password,
+            db,
+            idle: Mutex::new(Vec::new()),
+        };
+        // 起動時に繋がらなければ、設定の誤りとして止める
+        cache.command(&[b"PING"])?;
+        Ok(cache)
+    }
+
+    // 失敗した接続は返事の途中かもしれないので戻さずに捨て、次の命令で繋ぎ直す
+    fn command(&self, args: &[&[u8]]) -> Result<Option<Vec<u8>>> {
+        let idle = self.idle().pop();
+        let mut connection = match idle {
+            Some(connection) => connection,
+            None => self.open()?,
+        };
+        let result = exchange(&mut connection, args)?;
+        self.idle().push(connection);
+        Ok(result)
+    }
+
+    fn open(&self) -> Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect_timeout(&self.socket_addr()?, REDIS_TIMEOUT)
+            .map_err(|err| anyhow!("redis {}: {err}", self.address))?;
+        stream.set_read_timeout(Some(REDIS_TIMEOUT))?;
+        stream.set_write_timeout(Some(REDIS_TIMEOUT))?;
+        let mut stream = BufReader::new(stream);
+        if let Some(password) = &self.password {
+            exchange(&mut stream, &[b"AUTH", password.as_bytes()])?;
+        }
+        if let Some(db) = &self.db {
+            exchange(&mut stream, &[b"SELECT", db.as_bytes()])?;
+        }
+        Ok(stream)
+    }
+
+    fn socket_addr(&self) -> Result<SocketAddr> {
+        self.address
+            .to_socket_addrs()
+            .map_err(|err| anyhow!("redis {}: {err}", self.address))?
+            .next()
+            .ok_or_else(|| anyhow!("redis {}: no address", self.address))
+    }
+
+    fn idle(&self) -> MutexGuard<'_, Vec<BufReader<TcpStream>>> {
+        self.idle.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl CacheBackend for RedisCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let key = format!("{REDIS_PREFIX}{key}");
+        self.command(&[b"GET", key.as_bytes()])
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let key = format!("{REDIS_PREFIX}{key}");
+        self.command(&[b"SET", key.as_bytes(), value])?;
+        Ok(())
+    }
+}
+
+// RESPで命令を一つ送り、返事を一つ読む。値の無い返事はNone
+fn exchange(connection: &mut BufReader<TcpStream>, args: &[&[u8]]) -> Result<Option<Vec<u8>>> {
+    let mut request = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        request.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        request.extend_from_slice(arg);
+        request.extend_from_slice(b"\r\n");
+    }
+    connection.get_mut().write_all(&request)?;
+
+    let mut line = String::new();
+    connection.read_line(&mut line)?;
+    let line = line.trim_end();
+    let Some(first) = line.chars().next() else {
+        bail!("redis: connection closed");
+    };
+    let (kind, rest) = line.split_at(first.len_utf8());
+    match kind {
+        "+" | ":" => Ok(Some(rest.as_bytes().to_vec())),
+        "-" => bail!("redis: {rest}"),
+        "$" => {
+            let Ok(len) = usize::try_from(rest.parse::<i64>()?) else {
+                return Ok(None);
+            };
+            // 末尾の\r\nも読み捨てる
+            let mut value = vec![0; len + 2];
+            connection.read_exact(&mut value)?;
+            value.truncate(len);
+            Ok(Some(value))
+        }
+        _ => bail!("redis: unexpected reply {line:?}"),
+    }
+}
+
+// モデルの取り違えを防ぐための、/versionのファイルごとの中身のハッシュから作る値
+// 更新日時は含めないので、同じモデルを配った別のサーバーとは同じ値になる
+fn fingerprint(version: &Version) -> String {
+    let mut hasher = Sha256::new();
+    for (name, hash) in version.models() {
+        hasher.update(format!("{name}:{hash}\n"));
+    }
+    format!("{:x}", hasher.finalize())
+}
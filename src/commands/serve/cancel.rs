@@ -0,0 +1,122 @@
+// 処理中のリクエストの取り消し。クライアントが離れたリクエストにworkersを使い続けないように
+// 受け付けたリクエストにidを振り(X-Request-Idが付いていればそれ)、応答のX-Request-Idで返す
+// POST /cancel?id=...で取り消すと、待っている間なら始めずに、処理中なら次の段階に進む前に止まる
+use anyhow::Result;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+use tiny_http::Request;
+
+// クライアントが付けたidを応答のヘッダーにそのまま返すので、長いものや見えない文字は使わない
+const MAX_ID_LENGTH: usize = 128;
+
+#[derive(Debug)]
+pub struct CancelledError;
+
+impl fmt::Display for CancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request cancelled")
+    }
+}
+
+impl std::error::Error for CancelledError {}
+
+// decodeスレッドにも渡す、取り消されたかの印
+#[derive(Clone, Default)]
+pub struct Token(Arc<AtomicBool>);
+
+impl Token {
+    pub fn check(&self) -> Result<()> {
+        if self.0.load(Ordering::Relaxed) {
+            return Err(CancelledError.into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Requests {
+    running: Mutex<HashMap<String, Token>>,
+}
+
+impl Requests {
+    pub fn start(&self, request: &Request) -> Ticket<'_> {
+        let id = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("X-Request-Id"))
+            .map(|header| header.value.as_str())
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_ID_LENGTH
+                    && id.bytes().all(|byte| byte.is_ascii_graphic())
+            })
+            .map(str::to_string)
+            .unwrap_or_else(new_id);
+        let token = Token::default();
+        // 同じidが重なれば、後のものを取り消せるようにする
+        self.lock().insert(id.clone(), token.clone());
+        Ticket {
+            requests: self,
+            id,
+            token,
+        }
+    }
+
+    // 知らないidや、終わったリクエストならfalse
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.lock().get(id) {
+            Some(token) => {
+                token.0.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Token>> {
+        self.running.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+// 振るidは、数えて他のクライアントのリクエストを取り消せないように、推測できない128bitにする
+// user_dictのUUIDと同じく、OSの乱数で初期化されるRandomStateから作る
+fn new_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    (0..2)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+// 受け付けてから応答するまで持つ。落とせば取り消せなくなる
+pub struct Ticket<'a> {
+    requests: &'a Requests,
+    pub id: String,
+    pub token: Token,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut running = self.requests.lock();
+        // 同じidの後のリクエストが登録し直していれば、そちらを残す
+        if running
+            .get(&self.id)
+            .is_some_and(|token| Arc::ptr_eq(&token.0, &self.token.0))
+        {
+            running.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// 動かしたままのサーバーの既定値を、chibivox ctlから変えるためのUNIXソケット
+// 一行の命令("set speed 1.2"、"get")に、一行で答える。成功すれば今の既定値のJSON、失敗すれば"error: "で始まる
+// UNIXソケットの無い環境では、既定値は変わらないまま使われる
+#![cfg_attr(not(unix), allow(dead_code))]
+use super::ranged;
+use anyhow::{anyhow, bail, Result};
+use chibivox::SynthesisOptions;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+pub const SOCKET_ENV: &str = "CHIBIVOX_CONTROL_SOCKET";
+
+// /ttsの既定値。Noneならスタイルの既定値を使う。リクエストで指定された話者が優先される
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Defaults {
+    pub speaker: Option<u32>,
+    pub speed: Option<f32>,
+    pub volume: Option<f32>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Setting {
+    Speaker,
+    Speed,
+    Volume,
+}
+
+impl Defaults {
+    pub fn apply(&self, options: &mut SynthesisOptions) {
+        if let Some(speed) = self.speed {
+            options.speed_scale = speed;
+        }
+        if let Some(volume) = self.volume {
+            options.volume_scale = volume;
+        }
+    }
+
+    // "default"でスタイルの既定値に戻す。範囲はsynthの--speedや--volumeと同じ
+    fn set(&mut self, setting: Setting, value: &str) -> Result<()> {
+        let value = (value != "default").then_some(value);
+        match setting {
+            Setting::Speaker => self.speaker = value.map(str::parse).transpose()?,
+            Setting::Speed => {
+                self.speed = value
+                    .map(ranged(0.5, 2.))
+                    .transpose()
+                    .map_err(|err| anyhow!(err))?
+            }
+            Setting::Volume => {
+                self.volume = value
+                    .map(ranged(0., 2.))
+                    .transpose()
+                    .map_err(|err| anyhow!(err))?
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, line: &str) -> Result<()> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["get"] => Ok(()),
+            ["set", name, value] => {
+                let setting = Setting::from_str(name, false).map_err(|err| anyhow!(err))?;
+                self.set(setting, value)
+                    .map_err(|err| anyhow!("{name} {value}: {err}"))
+            }
+            _ => bail!("unknown command: {line}"),
+        }
+    }
+}
+
+// $XDG_RUNTIME_DIRがあればそこに、無ければ一時ディレクトリに置く
+pub fn default_socket() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("chibivox.sock")
+}
+
+// 前回落ちたときに残ったソケットは消す。他のサーバーが使っていれば止める
+#[cfg(unix)]
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("{} is already in use by another server", path.display());
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    // 既定値を変えられるのは、サーバーを動かしているユーザーだけ
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+// 命令は短いので、一つずつ順に処理する
+#[cfg(unix)]
+pub fn listen(listener: UnixListener, defaults: &Mutex<Defaults>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        // 何も送らない接続で、他のctlを待たせない
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let mut line = String::new();
+        if BufReader::new(&stream).read_line(&mut line).is_err() {
+            continue;
+        }
+        let reply = {
+            let mut defaults = defaults.lock().unwrap_or_else(|err| err.into_inner());
+            match defaults.execute(line.trim()) {
+                Ok(()) => serde_json::to_string(&*defaults).unwrap(),
+                Err(err) => format!("error: {err}"),
+            }
+        };
+        let _ = writeln!(&stream, "{reply}");
+    }
+}
@@ -0,0 +1,299 @@
+use super::super::presets::{Preset, Presets};
+use super::{
+    content_type, decode, encode_wav, i18n, json_response, query_param, read_body, speaker_param,
+    terms, text_response, wav_response, with_warnings, zip::ZipWriter, BodyTooLargeError,
+    DecodeJob, Models, Resources, Token,
+};
+use anyhow::Result;
+use chibivox::{
+    load_metas, AccentPhraseModel, AudioQueryModel, SpeakerInfo, UserDictWord, VoicevoxCore,
+    Warnings,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    io::Cursor,
+    sync::{mpsc::Sender, Mutex, MutexGuard},
+};
+use tiny_http::{Method, Request, Response};
+
+// VOICEVOX ENGINEのクライアントをそのまま向けられるように、同じパスと入出力で答える
+// 該当しないパスはNone
+pub(super) fn handle(
+    models: &Models<impl Fn() -> Result<VoicevoxCore>>,
+    resources: &Resources,
+    decode_sender: &Sender<DecodeJob>,
+    cancelled: &Token,
+    request: &mut Request,
+    path: &str,
+    query: &str,
+) -> Result<Option<Response<Cursor<Vec<u8>>>>> {
+    let method = request.method().clone();
+    let response = match (&method, path) {
+        // 話者とスタイルの一覧。モデルを手放していても読み込み直さずに返す
+        (Method::Get, "/speakers") => json_response(&load_metas(&resources.metas)?)?,
+        // 話者の規約・立ち絵・アイコン・サンプル音声
+        (Method::Get, "/speaker_info") => {
+            let Some(speaker_uuid) = query_param(query, "speaker_uuid") else {
+                return Ok(Some(text_response(422, "speaker_uuid is required")));
+            };
+            let metas = load_metas(&resources.metas)?;
+            let Some(speaker) = metas
+                .iter()
+                .find(|speaker| speaker.speaker_uuid == speaker_uuid)
+            else {
+                return Ok(Some(text_response(404, "unknown speaker_uuid")));
+            };
+            let style_ids: Vec<u32> = speaker.styles.iter().map(|style| style.id).collect();
+            match SpeakerInfo::load(&resources.speaker_info_dir, &speaker_uuid, &style_ids) {
+                Ok(speaker_info) => json_response(&speaker_info)?,
+                Err(err) => return Ok(Some(text_response(404, &i18n::chain(&err)))),
+            }
+        }
+        // テキストからAudioQueryを作る
+        (Method::Post, "/audio_query") => {
+            let Some(text) = query_param(query, "text") else {
+                return Ok(Some(text_response(422, "text is required")));
+            };
+            let Some(speaker_id) = speaker_param(query) else {
+                return Ok(Some(text_response(422, "speaker is required")));
+            };
+            let core = models.get()?;
+            let options = core.default_options(speaker_id);
+            let (audio_query, warnings) =
+                core.audio_query_with_warnings(&text, speaker_id, &options)?;
+            with_warnings(json_response(&audio_query)?, &warnings)
+        }
+        // プリセットの話者とパラメータで、テキストからAudioQueryを作る
+        (Method::Post, "/audio_query_from_preset") => {
+            let Some(text) = query_param(query, "text") else {
+                return Ok(Some(text_response(422, "text is required")));
+            };
+            let Some(preset_id) = query_param(query, "preset_id").and_then(|id| id.parse().ok())
+            else {
+                return Ok(Some(text_response(422, "preset_id is required")));
+            };
+            let Some(preset) = lock(&resources.presets).get(preset_id).cloned() else {
+                return Ok(Some(text_response(422, "unknown preset_id")));
+            };
+            let core = models.get()?;
+            let mut options = core.default_options(preset.style_id);
+            preset.apply(&mut options);
+            let (audio_query, warnings) =
+                core.audio_query_with_warnings(&text, preset.style_id, &options)?;
+            with_warnings(json_response(&audio_query)?, &warnings)
+        }
+        // プリセットの一覧と追加・更新・削除。追加と更新はidを返す
+        (Method::Get, "/presets") => json_response(&lock(&resources.presets).list())?,
+        (Method::Post, "/add_preset" | "/update_preset") => {
+            let preset = match read_json::<Preset>(request) {
+                Ok(preset) => preset,
+                Err(err) if is_too_large(&err) => return Err(err),
+                Err(err) => return Ok(Some(text_response(422, &err.to_string()))),
+            };
+            let mut presets = lock(&resources.presets);
+            let id = if path == "/add_preset" {
+                presets.add(preset)?
+            } else {
+                presets.update(preset)?
+            };
+            json_response(&id)?
+        }
+        (Method::Post, "/delete_preset") => {
+            let Some(id) = query_param(query, "id").and_then(|id| id.parse().ok()) else {
+                return Ok(Some(text_response(422, "id is required")));
+            };
+            lock(&resources.presets).delete(id)?;
+            text_response(204, "")
+        }
+        // ユーザー辞書。書き換えは保存してから、次のテキスト解析から使う
+        (Method::Get, "/user_dict") => json_response(models.user_dict().words())?,
+        (Method::Post, "/user_dict_word") => {
+            let word = match user_dict_word(query) {
+                Ok(word) => word,
+                Err(message) => return Ok(Some(text_response(422, &message))),
+            };
+            let uuid = models.update_user_dict(|user_dict| {
+                let uuid = user_dict.add_word(word)?;
+                user_dict.save(&resources.user_dict)?;
+                Ok(uuid)
+            })?;
+            json_response(&uuid)?
+        }
+        (Method::Put | Method::Delete, path) if path.starts_with("/user_dict_word/") => {
+            let uuid = &path["/user_dict_word/".len()..];
+            let word = if method == Method::Put {
+                match user_dict_word(query) {
+                    Ok(word) => Some(word),
+                    Err(message) => return Ok(Some(text_response(422, &message))),
+                }
+            } else {
+                None
+            };
+            models.update_user_dict(|user_dict| {
+                match word {
+                    Some(word) => user_dict.update_word(uuid, word)?,
+                    None => {
+                        user_dict.remove_word(uuid)?;
+                    }
+                }
+                user_dict.save(&resources.user_dict)
+            })?;
+            text_response(204, "")
+        }
+        // 本文のAudioQueryを、長さと音高を予測し直さずに合成する
+        (Method::Post, "/synthesis") => {
+            let Some(speaker_id) = speaker_param(query) else {
+                return Ok(Some(text_response(422, "speaker is required")));
+            };
+            let enable_interrogative_upspeak =
+                query_param(query, "enable_interrogative_upspeak").as_deref() != Some("false");
+            let audio_query = match read_audio_query(request) {
+                Ok(audio_query) => audio_query,
+                Err(err) => return Ok(Some(text_response(422, &i18n::chain(&err)))),
+            };
+
+            let core = models.get()?;
+            let wav = decode(
+                decode_sender,
+                cancelled,
+                core.clone(),
+                audio_query,
+                speaker_id,
+                enable_interrogative_upspeak,
+            )?;
+            let credit = terms::credit(&core, speaker_id);
+            wav_response(speaker_id, &wav, Warnings::new(), credit)
+        }
+        // 本文のAudioQueryの配列をそれぞれ合成し、001.wav, 002.wav, ...としてzipにまとめる
+        (Method::Post, "/multi_synthesis") => {
+            let Some(speaker_id) = speaker_param(query) else {
+                return Ok(Some(text_response(422, "speaker is required")));
+            };
+            let audio_queries = match read_audio_queries(request) {
+                Ok(audio_queries) => audio_queries,
+                Err(err) => return Ok(Some(text_response(422, &i18n::chain(&err)))),
+            };
+
+            let core = models.get()?;
+            let credit = terms::credit(&core, speaker_id);
+            let mut warnings = Warnings::new();
+            let mut zip = ZipWriter::default();
+            for (i, audio_query) in audio_queries.into_iter().enumerate() {
+                let wav = decode(
+                    decode_sender,
+                    cancelled,
+                    core.clone(),
+                    audio_query,
+                    speaker_id,
+                    true,
+                )?;
+                let data = encode_wav(speaker_id, &wav, &mut warnings, credit);
+                zip.add(&format!("{:03}.wav", i + 1), &data);
+            }
+            with_warnings(
+                Response::from_data(zip.finish()).with_header(content_type("application/zip")),
+                &warnings,
+            )
+        }
+        // テキストからアクセント句を作り、長さと音高を予測する。is_kana=trueならAquesTalk風記法として読む
+        (Method::Post, "/accent_phrases") => {
+            let Some(text) = query_param(query, "text") else {
+                return Ok(Some(text_response(422, "text is required")));
+            };
+            let Some(speaker_id) = speaker_param(query) else {
+                return Ok(Some(text_response(422, "speaker is required")));
+            };
+            let is_kana = query_param(query, "is_kana").as_deref() == Some("true");
+
+            let core = models.get()?;
+            let mut warnings = Warnings::new();
+            let accent_phrases = if is_kana {
+                match core.create_accent_phrases_from_kana(&text) {
+                    Ok(accent_phrases) => accent_phrases,
+                    Err(err) => return Ok(Some(text_response(400, &i18n::chain(&err)))),
+                }
+            } else {
+                core.create_accent_phrases_with_warnings(&text, &mut warnings)?
+            };
+            let accent_phrases =
+                core.replace_mora_data_with_warnings(accent_phrases, speaker_id, &mut warnings)?;
+            with_warnings(json_response(&accent_phrases)?, &warnings)
+        }
+        // 編集したアクセント句の長さ・音高(またはその両方)を予測し直す
+        (Method::Post, "/mora_data" | "/mora_length" | "/mora_pitch") => {
+            let Some(speaker_id) = speaker_param(query) else {
+                return Ok(Some(text_response(422, "speaker is required")));
+            };
+            let accent_phrases: Vec<AccentPhraseModel> = match read_json(request) {
+                Ok(accent_phrases) => accent_phrases,
+                Err(err) if is_too_large(&err) => return Err(err),
+                Err(err) => return Ok(Some(text_response(422, &i18n::chain(&err)))),
+            };
+
+            let core = models.get()?;
+            let mut warnings = Warnings::new();
+            let accent_phrases = match path {
+                "/mora_data" => {
+                    core.replace_mora_data_with_warnings(accent_phrases, speaker_id, &mut warnings)?
+                }
+                "/mora_length" => core.replace_phoneme_length(accent_phrases, speaker_id)?,
+                _ => core.replace_mora_pitch(accent_phrases, speaker_id)?,
+            };
+            with_warnings(json_response(&accent_phrases)?, &warnings)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(response))
+}
+
+fn read_audio_query(request: &mut Request) -> Result<AudioQueryModel> {
+    let audio_query: AudioQueryModel = read_json(request)?;
+    audio_query.validate()?;
+    Ok(audio_query)
+}
+
+fn read_audio_queries(request: &mut Request) -> Result<Vec<AudioQueryModel>> {
+    let audio_queries: Vec<AudioQueryModel> = read_json(request)?;
+    for (i, audio_query) in audio_queries.iter().enumerate() {
+        audio_query
+            .validate()
+            .map_err(|err| err.context(format!("audio query {}", i + 1)))?;
+    }
+    Ok(audio_queries)
+}
+
+// VOICEVOX ENGINEと同じく、語はクエリで受け取る。word_typeは受け取っても使わない
+fn user_dict_word(query: &str) -> Result<UserDictWord, String> {
+    let param = |key: &str| query_param(query, key).ok_or_else(|| format!("{key} is required"));
+    let mut word = UserDictWord::new(
+        &param("surface")?,
+        &param("pronunciation")?,
+        param("accent_type")?
+            .parse()
+            .map_err(|_| "accent_type must be a non-negative integer")?,
+    );
+    if let Some(priority) = query_param(query, "priority") {
+        word.priority = priority
+            .parse()
+            .map_err(|_| "priority must be a non-negative integer")?;
+    }
+    Ok(word)
+}
+
+// 他のリクエストがpanicしても、プリセットは使い続ける
+fn lock(presets: &Mutex<Presets>) -> MutexGuard<'_, Presets> {
+    presets.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// /multi_synthesisなどで長いAudioQueryを並べても収まるように、/ttsのテキストより大きくする
+const MAX_JSON_BYTES: usize = 16 << 20;
+
+fn read_json<T: DeserializeOwned>(request: &mut Request) -> Result<T> {
+    let body = read_body(request, MAX_JSON_BYTES)?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+// 読めなかった本文のうち、大きすぎたものは422ではなく413で返す
+fn is_too_large(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<BodyTooLargeError>().is_some()
+}
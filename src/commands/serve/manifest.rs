@@ -0,0 +1,82 @@
+// GET /engine_manifestで返す、VOICEVOX ENGINEと同じ形のエンジンの情報
+// 複数のエンジンを扱うクライアントが、chibivoxで使える機能を確かめられるように
+use chibivox::{FRAME_RATE, SAMPLING_RATE};
+use serde::Serialize;
+
+// 別のエンジンと区別するためのもので、版が変わっても変えない
+const UUID: &str = "dffa48e3-9866-4889-a450-382fb079e660";
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineManifest {
+    // 準拠しているVOICEVOX ENGINEのマニフェストの版
+    manifest_version: &'static str,
+    name: &'static str,
+    brand_name: &'static str,
+    uuid: &'static str,
+    version: &'static str,
+    url: &'static str,
+    // 以下はマニフェストに必ずある項目。中身の無いものは空にしておく
+    // base64のPNG
+    icon: &'static str,
+    default_sampling_rate: u32,
+    frame_rate: f32,
+    terms_of_service: &'static str,
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    update_infos: Vec<serde_json::Value>,
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    dependency_licenses: Vec<serde_json::Value>,
+    supported_features: SupportedFeatures,
+}
+
+// 無い機能はfalse。VOICEVOXのエディターは、falseのものの操作を隠す
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SupportedFeatures {
+    adjust_mora_pitch: bool,
+    adjust_phoneme_length: bool,
+    adjust_speed_scale: bool,
+    adjust_pitch_scale: bool,
+    adjust_intonation_scale: bool,
+    adjust_volume_scale: bool,
+    interrogative_upspeak: bool,
+    synthesis_morphing: bool,
+    sing: bool,
+    manage_library: bool,
+}
+
+impl EngineManifest {
+    fn new() -> Self {
+        Self {
+            manifest_version: "0.13.1",
+            name: "chibivox",
+            brand_name: "chibivox",
+            uuid: UUID,
+            version: env!("CARGO_PKG_VERSION"),
+            url: "https://github.com/eyr1n/chibivox",
+            icon: "",
+            default_sampling_rate: SAMPLING_RATE,
+            frame_rate: FRAME_RATE,
+            terms_of_service: "",
+            update_infos: Vec::new(),
+            dependency_licenses: Vec::new(),
+            supported_features: SupportedFeatures {
+                adjust_mora_pitch: true,
+                adjust_phoneme_length: true,
+                adjust_speed_scale: true,
+                adjust_pitch_scale: true,
+                adjust_intonation_scale: true,
+                adjust_volume_scale: true,
+                interrogative_upspeak: true,
+                synthesis_morphing: false,
+                sing: false,
+                manage_library: false,
+            },
+        }
+    }
+}
+
+// 変わらないので、serveの起動時に一度だけ書き出す
+pub fn json() -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&EngineManifest::new())
+}
@@ -0,0 +1,243 @@
+// GET /metricsで返す、Prometheusのテキスト形式の集計
+// サーバー全体で一つなので、どこからでも記録できるようにstaticに置く
+use super::errors::{ErrorKind, Stage};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+// 秒。短い文のanalysisから、長い文のdecodeまでが収まるように
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30.,
+];
+
+// ラベルの値が際限なく増えないように、知っているパス以外はまとめる
+const ROUTES: &[&str] = &[
+    "/tts",
+    "/audio_query",
+    "/audio_query_from_preset",
+    "/synthesis",
+    "/multi_synthesis",
+    "/accent_phrases",
+    "/mora_data",
+    "/mora_length",
+    "/mora_pitch",
+    "/speakers",
+    "/speaker_info",
+    "/presets",
+    "/add_preset",
+    "/update_preset",
+    "/delete_preset",
+    "/user_dict",
+    "/user_dict_word",
+    "/cancel",
+    "/reload",
+    "/ready",
+    "/health",
+    "/version",
+    "/engine_manifest",
+    "/openapi.json",
+    "/metrics",
+];
+
+#[derive(Clone, Copy)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS.len()],
+            sum: 0.,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= le {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        for (bucket, le) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{le}\"}} {bucket}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {}", self.count);
+        let labels = labels.trim_end_matches(',');
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+struct State {
+    requests: BTreeMap<(&'static str, u16), u64>,
+    request_seconds: BTreeMap<&'static str, Histogram>,
+    // Stageの順。Loadは数えない
+    stage_seconds: [Histogram; 3],
+    audio_seconds: f64,
+    errors: BTreeMap<String, u64>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    requests: BTreeMap::new(),
+    request_seconds: BTreeMap::new(),
+    stage_seconds: [Histogram::new(); 3],
+    audio_seconds: 0.,
+    errors: BTreeMap::new(),
+});
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+static DECODES_QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+pub fn route(url: &str) -> &'static str {
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+    // uuidごとに分けると、語の数だけ系列が増えてしまう
+    if path.starts_with("/user_dict_word/") {
+        return "/user_dict_word/{word_uuid}";
+    }
+    ROUTES
+        .iter()
+        .find(|&&route| route == path)
+        .copied()
+        .unwrap_or("other")
+}
+
+pub fn request(route: &'static str, status: u16, elapsed: Duration) {
+    let mut state = state();
+    *state.requests.entry((route, status)).or_default() += 1;
+    state
+        .request_seconds
+        .entry(route)
+        .or_insert_with(Histogram::new)
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn stage(stage: Stage, elapsed: Duration) {
+    let index = match stage {
+        Stage::Load => return,
+        Stage::Analysis => 0,
+        Stage::Prediction => 1,
+        Stage::Decode => 2,
+    };
+    state().stage_seconds[index].observe(elapsed.as_secs_f64());
+}
+
+pub fn audio(seconds: f64) {
+    state().audio_seconds += seconds;
+}
+
+pub fn error(kind: ErrorKind) {
+    let kind = serde_json::to_value(kind).unwrap();
+    *state()
+        .errors
+        .entry(kind.as_str().unwrap_or_default().to_string())
+        .or_default() += 1;
+}
+
+// workersを待っているリクエストと、decodeスレッドを待っている仕事
+#[derive(Clone, Copy)]
+pub enum Queue {
+    Requests,
+    Decode,
+}
+
+impl Queue {
+    fn gauge(self) -> &'static AtomicUsize {
+        match self {
+            Self::Requests => &QUEUED,
+            Self::Decode => &DECODES_QUEUED,
+        }
+    }
+}
+
+pub fn enqueued(queue: Queue) {
+    queue.gauge().fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn dequeued(queue: Queue) {
+    queue.gauge().fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn render() -> String {
+    let state = state();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_requests_total Requests answered, by route and status.\n\
+         # TYPE chibivox_requests_total counter"
+    );
+    for ((route, status), count) in &state.requests {
+        let _ = writeln!(
+            out,
+            "chibivox_requests_total{{route=\"{route}\",status=\"{status}\"}} {count}"
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_request_duration_seconds Time from accepting a request to answering it, including the wait for a worker.\n\
+         # TYPE chibivox_request_duration_seconds histogram"
+    );
+    for (route, histogram) in &state.request_seconds {
+        histogram.write(
+            &mut out,
+            "chibivox_request_duration_seconds",
+            &format!("route=\"{route}\","),
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_stage_duration_seconds Time spent in each synthesis stage; analysis and prediction are measured for /tts only.\n\
+         # TYPE chibivox_stage_duration_seconds histogram"
+    );
+    for (stage, histogram) in ["analysis", "prediction", "decode"]
+        .iter()
+        .zip(&state.stage_seconds)
+    {
+        histogram.write(
+            &mut out,
+            "chibivox_stage_duration_seconds",
+            &format!("stage=\"{stage}\","),
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_audio_seconds_total Seconds of audio synthesized.\n\
+         # TYPE chibivox_audio_seconds_total counter\n\
+         chibivox_audio_seconds_total {}",
+        state.audio_seconds
+    );
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_errors_total Failed requests, by the same kinds as the exit codes.\n\
+         # TYPE chibivox_errors_total counter"
+    );
+    for (kind, count) in &state.errors {
+        let _ = writeln!(out, "chibivox_errors_total{{kind=\"{kind}\"}} {count}");
+    }
+    let _ = writeln!(
+        out,
+        "# HELP chibivox_queue_depth Requests waiting for a worker, or for the decode thread.\n\
+         # TYPE chibivox_queue_depth gauge\n\
+         chibivox_queue_depth{{queue=\"requests\"}} {}\n\
+         chibivox_queue_depth{{queue=\"decode\"}} {}",
+        QUEUED.load(Ordering::Relaxed),
+        DECODES_QUEUED.load(Ordering::Relaxed)
+    );
+    out
+}
@@ -0,0 +1,422 @@
+// GET /openapi.jsonで返す、VOICEVOX ENGINEと同じ形のOpenAPIの定義
+// クライアントの生成や、VOICEVOX向けの道具との互換の確認に使えるように
+// 下の関数は#[utoipa::path]で定義を書くためだけのもので、呼ばれない。処理はserve.rsとengine.rsにある
+#![allow(dead_code)]
+
+use super::super::presets::Preset;
+use super::manifest::{EngineManifest, SupportedFeatures};
+use chibivox::{
+    AccentPhraseModel, AudioQueryModel, MoraModel, SpeakerInfo, SpeakerMeta, StyleDefaults,
+    StyleInfo, StyleMeta, StyleType, UsageTerms, UserDictWord,
+};
+use utoipa::{OpenApi, ToSchema};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "chibivox", description = "VOICEVOX ENGINE互換のHTTP API"),
+    paths(
+        tts,
+        audio_query,
+        audio_query_from_preset,
+        synthesis,
+        multi_synthesis,
+        accent_phrases,
+        mora_data,
+        mora_length,
+        mora_pitch,
+        speakers,
+        speaker_info,
+        presets,
+        add_preset,
+        update_preset,
+        delete_preset,
+        user_dict,
+        add_user_dict_word,
+        update_user_dict_word,
+        delete_user_dict_word,
+        cancel,
+        reload,
+        ready,
+        health,
+        version,
+        engine_manifest,
+        metrics,
+    ),
+    components(schemas(
+        AudioQueryModel,
+        AccentPhraseModel,
+        MoraModel,
+        SpeakerMeta,
+        StyleMeta,
+        StyleDefaults,
+        StyleType,
+        UsageTerms,
+        SpeakerInfo,
+        StyleInfo,
+        Preset,
+        UserDictWord,
+        EngineManifest,
+        SupportedFeatures,
+        Binary,
+    )),
+    modifiers(&BearerAuth),
+)]
+struct ApiDoc;
+
+// --api-keyを付けたときの、Authorization: Bearer
+struct BearerAuth;
+
+impl utoipa::Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+// 変わらないので、serveの起動時に一度だけ書き出す
+pub fn json() -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&ApiDoc::openapi())
+}
+
+// WAVやzipなどの、JSONではない本文
+#[derive(ToSchema)]
+struct Binary(#[schema(value_type = String, format = Binary)] Vec<u8>);
+
+#[utoipa::path(
+    post,
+    path = "/tts",
+    description = "本文のテキストを合成してWAVを返す。streamを付けると、合成した分から少しずつ送る",
+    params(
+        ("speaker" = Option<u32>, Query, description = "話者(スタイル)のid。無ければコントロールソケットで決めた既定の話者"),
+        ("stream" = Option<String>, Query, description = "wavかpcm"),
+    ),
+    request_body(content = String, content_type = "text/plain"),
+    responses(
+        (status = 200, body = Binary, content_type = "audio/wav"),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn tts() {}
+
+#[utoipa::path(
+    post,
+    path = "/audio_query",
+    description = "テキストからAudioQueryを作る",
+    params(
+        ("text" = String, Query),
+        ("speaker" = u32, Query),
+    ),
+    responses(
+        (status = 200, body = AudioQueryModel),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn audio_query() {}
+
+#[utoipa::path(
+    post,
+    path = "/audio_query_from_preset",
+    description = "プリセットの話者とパラメータで、テキストからAudioQueryを作る",
+    params(
+        ("text" = String, Query),
+        ("preset_id" = u32, Query),
+    ),
+    responses(
+        (status = 200, body = AudioQueryModel),
+        (status = 422, description = "引数の誤りか、知らないpreset_id"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn audio_query_from_preset() {}
+
+#[utoipa::path(
+    post,
+    path = "/synthesis",
+    description = "本文のAudioQueryを、長さと音高を予測し直さずに合成する",
+    params(
+        ("speaker" = u32, Query),
+        ("enable_interrogative_upspeak" = Option<bool>, Query),
+    ),
+    request_body = AudioQueryModel,
+    responses(
+        (status = 200, body = Binary, content_type = "audio/wav"),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn synthesis() {}
+
+#[utoipa::path(
+    post,
+    path = "/multi_synthesis",
+    description = "本文のAudioQueryの配列をそれぞれ合成し、001.wav, 002.wav, ...としてzipにまとめる",
+    params(("speaker" = u32, Query)),
+    request_body = Vec<AudioQueryModel>,
+    responses(
+        (status = 200, body = Binary, content_type = "application/zip"),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn multi_synthesis() {}
+
+#[utoipa::path(
+    post,
+    path = "/accent_phrases",
+    description = "テキストからアクセント句を作り、長さと音高を予測する。is_kana=trueならAquesTalk風記法として読む",
+    params(
+        ("text" = String, Query),
+        ("speaker" = u32, Query),
+        ("is_kana" = Option<bool>, Query),
+    ),
+    responses(
+        (status = 200, body = Vec<AccentPhraseModel>),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn accent_phrases() {}
+
+#[utoipa::path(
+    post,
+    path = "/mora_data",
+    description = "本文のアクセント句の、長さと音高を予測し直す",
+    params(("speaker" = u32, Query)),
+    request_body = Vec<AccentPhraseModel>,
+    responses(
+        (status = 200, body = Vec<AccentPhraseModel>),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn mora_data() {}
+
+#[utoipa::path(
+    post,
+    path = "/mora_length",
+    description = "本文のアクセント句の、長さだけを予測し直す",
+    params(("speaker" = u32, Query)),
+    request_body = Vec<AccentPhraseModel>,
+    responses(
+        (status = 200, body = Vec<AccentPhraseModel>),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn mora_length() {}
+
+#[utoipa::path(
+    post,
+    path = "/mora_pitch",
+    description = "本文のアクセント句の、音高だけを予測し直す",
+    params(("speaker" = u32, Query)),
+    request_body = Vec<AccentPhraseModel>,
+    responses(
+        (status = 200, body = Vec<AccentPhraseModel>),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn mora_pitch() {}
+
+#[utoipa::path(
+    get,
+    path = "/speakers",
+    responses((status = 200, body = Vec<SpeakerMeta>)),
+    security((), ("api_key" = [])),
+)]
+fn speakers() {}
+
+#[utoipa::path(
+    get,
+    path = "/speaker_info",
+    params(("speaker_uuid" = String, Query)),
+    responses(
+        (status = 200, body = SpeakerInfo),
+        (status = 404, description = "知らないspeaker_uuid"),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn speaker_info() {}
+
+#[utoipa::path(
+    get,
+    path = "/presets",
+    responses((status = 200, body = Vec<Preset>)),
+    security((), ("api_key" = [])),
+)]
+fn presets() {}
+
+#[utoipa::path(
+    post,
+    path = "/add_preset",
+    description = "プリセットを加えて、振ったidを返す。idが使われていれば、最大のものの次を振る",
+    request_body = Preset,
+    responses(
+        (status = 200, body = u32),
+        (status = 422, description = "プリセットの誤りか、使われている名前"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn add_preset() {}
+
+#[utoipa::path(
+    post,
+    path = "/update_preset",
+    description = "同じidのプリセットを置き換えて、そのidを返す",
+    request_body = Preset,
+    responses(
+        (status = 200, body = u32),
+        (status = 422, description = "プリセットの誤りか、知らないid"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn update_preset() {}
+
+#[utoipa::path(
+    post,
+    path = "/delete_preset",
+    params(("id" = u32, Query)),
+    responses(
+        (status = 204),
+        (status = 422, description = "知らないid"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn delete_preset() {}
+
+#[utoipa::path(
+    get,
+    path = "/user_dict",
+    description = "uuidからユーザー辞書の語へのオブジェクト",
+    responses((status = 200, body = HashMap<String, UserDictWord>)),
+    security((), ("api_key" = [])),
+)]
+fn user_dict() {}
+
+#[utoipa::path(
+    post,
+    path = "/user_dict_word",
+    description = "ユーザー辞書に語を加えて、振ったuuidを返す",
+    params(
+        ("surface" = String, Query),
+        ("pronunciation" = String, Query, description = "カタカナの読み"),
+        ("accent_type" = usize, Query, description = "アクセント核のモーラの位置。0なら平板"),
+        ("priority" = Option<u32>, Query, description = "0から10。既定は5"),
+    ),
+    responses(
+        (status = 200, body = String),
+        (status = 422, description = "引数の誤りか、読めない読み"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn add_user_dict_word() {}
+
+#[utoipa::path(
+    put,
+    path = "/user_dict_word/{word_uuid}",
+    params(
+        ("word_uuid" = String, Path),
+        ("surface" = String, Query),
+        ("pronunciation" = String, Query, description = "カタカナの読み"),
+        ("accent_type" = usize, Query, description = "アクセント核のモーラの位置。0なら平板"),
+        ("priority" = Option<u32>, Query, description = "0から10。既定は5"),
+    ),
+    responses(
+        (status = 204),
+        (status = 422, description = "引数の誤りか、知らないuuid"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn update_user_dict_word() {}
+
+#[utoipa::path(
+    delete,
+    path = "/user_dict_word/{word_uuid}",
+    params(("word_uuid" = String, Path)),
+    responses(
+        (status = 204),
+        (status = 422, description = "知らないuuid"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn delete_user_dict_word() {}
+
+#[utoipa::path(
+    post,
+    path = "/cancel",
+    description = "待っているか処理中のリクエストを取り消す。取り消されたリクエストは、次の段階に進む前に499で終わる",
+    params(("id" = String, Query, description = "リクエストに付けたか、応答のX-Request-Idで返されたid")),
+    responses(
+        (status = 204),
+        (status = 404, description = "知らないidか、終わったリクエスト"),
+        (status = 422, description = "引数の誤り"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn cancel() {}
+
+#[utoipa::path(
+    post,
+    path = "/reload",
+    description = "--model-dirのモデルとメタデータを、待ち受けを止めずに読み込み直す。読み込み終えるまでは今のモデルで答え、失敗すれば今のモデルを使い続ける。SIGHUPでも同じ",
+    responses(
+        (status = 200, description = "新しい/versionと同じJSON", body = Object, content_type = "application/json"),
+        (status = 500, description = "読み込みの失敗"),
+    ),
+    security((), ("api_key" = [])),
+)]
+fn reload() {}
+
+#[utoipa::path(
+    get,
+    path = "/ready",
+    description = "モデルを読み込み終えていれば200。鍵は要らない",
+    responses(
+        (status = 200, body = String, content_type = "text/plain"),
+        (status = 503, description = "読み込み中"),
+    ),
+)]
+fn ready() {}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    description = "動いていれば、読み込み中でも200。鍵は要らない",
+    responses((status = 200, body = String, content_type = "text/plain")),
+)]
+fn health() {}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    description = "chibivoxとONNX Runtimeの版、実行環境、モデルごとのハッシュ",
+    responses((status = 200, body = Object, content_type = "application/json")),
+    security((), ("api_key" = [])),
+)]
+fn version() {}
+
+#[utoipa::path(
+    get,
+    path = "/engine_manifest",
+    description = "VOICEVOX ENGINEと同じ形の、エンジンの名前と版、使える機能、既定のサンプリングレート",
+    responses((status = 200, body = EngineManifest)),
+    security((), ("api_key" = [])),
+)]
+fn engine_manifest() {}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    description = "Prometheusのテキスト形式の、要求数と段階ごとの時間",
+    responses((status = 200, body = String, content_type = "text/plain; version=0.0.4")),
+    security((), ("api_key" = [])),
+)]
+fn metrics() {}
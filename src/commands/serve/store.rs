@@ -0,0 +1,103 @@
+// 起動時にオブジェクトストレージからモデルを取ってくる。自動で増減させるサーバーのイメージに、モデルを焼き込まずに済む
+// <URL>/manifest.jsonに並べたファイルを、sha256を確かめながら--model-dirに置く。同じものが既にあれば取り直さない
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Component, Path},
+};
+
+#[derive(Deserialize)]
+struct Manifest {
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Deserialize)]
+struct ManifestFile {
+    // URLとmodel_dirからの相対パス。speaker_info/<uuid>/policy.mdのような下の階層も置ける
+    name: String,
+    sha256: String,
+}
+
+pub fn fetch(url: &str, model_dir: &Path) -> Result<()> {
+    let base = resolve(url);
+    let manifest: Manifest = serde_json::from_reader(get(&format!("{base}/manifest.json"))?)
+        .context("invalid manifest.json")?;
+    for file in &manifest.files {
+        // manifestの書き誤りで、model_dirの外に書かないように
+        let name = Path::new(&file.name);
+        if !name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            bail!("invalid file name in manifest.json: {}", file.name);
+        }
+        let path = model_dir.join(name);
+        if path.exists() && sha256(File::open(&path)?, io::sink())? == file.sha256 {
+            continue;
+        }
+
+        eprintln!("fetching {}", file.name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // 途中で落ちても、壊れたファイルが本来の名前で残らないように
+        let temporary = path.with_file_name(format!(
+            ".{}.tmp",
+            name.file_name().unwrap().to_string_lossy()
+        ));
+        let digest = sha256(
+            get(&format!("{base}/{}", file.name))?,
+            File::create(&temporary)?,
+        )?;
+        if digest != file.sha256 {
+            fs::remove_file(&temporary)?;
+            bail!(
+                "{}: checksum mismatch (expected {}, got {digest})",
+                file.name,
+                file.sha256
+            );
+        }
+        fs::rename(&temporary, &path)?;
+    }
+    Ok(())
+}
+
+// s3://bucket/prefixは公開バケットのURLにする。非公開のものは、署名するプロキシかVPCエンドポイントの先に置く
+fn resolve(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    match url.strip_prefix("s3://") {
+        Some(path) => {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            format!("https://{bucket}.s3.amazonaws.com/{prefix}")
+                .trim_end_matches('/')
+                .to_string()
+        }
+        None => url.to_string(),
+    }
+}
+
+fn get(url: &str) -> Result<impl Read> {
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("GET {url}"))?;
+    Ok(response.into_reader())
+}
+
+// 読みながらwriterに写し、16進のsha256を返す
+fn sha256(mut reader: impl Read, mut writer: impl Write) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+    }
+    writer.flush()?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,69 @@
+// GET /versionで返す、動いているものの版。どのモデルをどの実行環境で動かしているかを、外から確かめられるように
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+#[derive(Serialize)]
+pub struct Version {
+    version: &'static str,
+    // リンクしたONNX RuntimeのC APIの版。1.16なら16
+    #[cfg(not(feature = "wasm"))]
+    onnxruntime_api: u32,
+    execution_providers: Vec<String>,
+    // model_dirの直下のファイルごとの、中身のFNV-1a-128
+    models: BTreeMap<String, String>,
+}
+
+impl Version {
+    // モデルの中身を読むので、起動時に一度だけ作る
+    pub fn new(model_dir: &Path, execution_providers: Vec<String>) -> Result<Self> {
+        let mut models = BTreeMap::new();
+        for entry in fs::read_dir(model_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.metadata()?.is_file() && !name.starts_with('.') {
+                models.insert(name, hash(File::open(entry.path())?)?);
+            }
+        }
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION"),
+            #[cfg(not(feature = "wasm"))]
+            onnxruntime_api: chibivox::ONNXRUNTIME_API_VERSION,
+            execution_providers,
+            models,
+        })
+    }
+
+    // --cacheの鍵にも使う
+    pub fn models(&self) -> &BTreeMap<String, String> {
+        &self.models
+    }
+}
+
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+
+// 大きなファイルも、全てを読み込まずに求める
+fn hash(mut reader: impl Read) -> Result<String> {
+    let mut hash = FNV_OFFSET;
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hash = fnv1a128_update(hash, &buffer[..read]);
+    }
+    Ok(format!("{hash:032x}"))
+}
+
+fn fnv1a128_update(hash: u128, bytes: &[u8]) -> u128 {
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    bytes
+        .iter()
+        .fold(hash, |hash, &b| (hash ^ b as u128).wrapping_mul(PRIME))
+}
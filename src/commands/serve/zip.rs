@@ -0,0 +1,101 @@
+// /multi_synthesisの応答に使う、無圧縮(stored)のzip
+// WAVはほとんど縮まないので、圧縮はしない
+
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    pub fn add(&mut self, name: &str, contents: &[u8]) {
+        let offset = self.data.len() as u32;
+        let crc = crc32(contents);
+        let size = contents.len() as u32;
+
+        // local file header
+        self.data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        self.data.extend_from_slice(&common_header(name, crc, size));
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        // central directory file header
+        let header = &mut self.central_directory;
+        header.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes()); // version made by
+        header.extend_from_slice(&common_header(name, crc, size));
+        header.extend_from_slice(&0u16.to_le_bytes()); // file comment length
+        header.extend_from_slice(&0u16.to_le_bytes()); // disk number start
+        header.extend_from_slice(&0u16.to_le_bytes()); // internal file attributes
+        header.extend_from_slice(&0u32.to_le_bytes()); // external file attributes
+        header.extend_from_slice(&offset.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+
+        self.entries += 1;
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.central_directory.len() as u32;
+        self.data.append(&mut self.central_directory);
+
+        // end of central directory record
+        self.data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // number of this disk
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // disk with the central directory
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.data
+    }
+}
+
+// 2.0: storedとディレクトリを扱える最低限の版
+const VERSION: u16 = 20;
+// 1980-01-01 00:00。日時は持たないので、DOS形式の最小値にする
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+// ファイル名がUTF-8であることを示す
+const FLAG_UTF8: u16 = 1 << 11;
+
+// local file headerとcentral directory file headerで共通の部分
+fn common_header(name: &str, crc: u32, size: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(26);
+    header.extend_from_slice(&VERSION.to_le_bytes()); // version needed to extract
+    header.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes()); // compression method: stored
+    header.extend_from_slice(&DOS_TIME.to_le_bytes());
+    header.extend_from_slice(&DOS_DATE.to_le_bytes());
+    header.extend_from_slice(&crc.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes()); // compressed size
+    header.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+    header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+    header
+}
+
+// CRC-32(IEEE 802.3)。応答ごとに数MB程度なので、表を引くだけの実装で足りる
+fn crc32(bytes: &[u8]) -> u32 {
+    static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xedb88320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        table
+    });
+    !bytes.iter().fold(!0, |crc: u32, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
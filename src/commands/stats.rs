@@ -0,0 +1,118 @@
+use super::output;
+use anyhow::{Context, Result};
+use chibivox::{load_metas, AudioBuffer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// 利用状況はローカルのファイルにだけ記録し、どこにも送らない
+// CHIBIVOX_STATSにファイルのパスを設定した場合のみ記録する
+const STATS_ENV: &str = "CHIBIVOX_STATS";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Stats file written while CHIBIVOX_STATS is set
+    #[arg(long, env = STATS_ENV)]
+    file: PathBuf,
+    /// Delete the stats file
+    #[arg(long)]
+    reset: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Stats {
+    syntheses: u64,
+    audio_seconds: f64,
+    speakers: BTreeMap<u32, SpeakerStats>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SpeakerStats {
+    syntheses: u64,
+    audio_seconds: f64,
+}
+
+impl Stats {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).with_context(|| format!("broken stats file {}", path.display()))
+    }
+
+    // 書きかけのファイルが残らないよう、別名で書いてから置き換える
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+// 合成が一回終わるごとに呼ぶ。記録に失敗しても合成自体は失敗させない
+pub fn record(speaker_id: u32, wav: &AudioBuffer) {
+    record_seconds(speaker_id, wav.duration() as f64);
+}
+
+// 少しずつ送り出した音声のように、まとまったAudioBufferが無い場合
+pub fn record_seconds(speaker_id: u32, seconds: f64) {
+    // 同じプロセス内で並行して書き換えないようにする
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let Some(path) = env::var_os(STATS_ENV).map(PathBuf::from) else {
+        return;
+    };
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let result = Stats::load(&path).and_then(|mut stats| {
+        stats.syntheses += 1;
+        stats.audio_seconds += seconds;
+        let speaker = stats.speakers.entry(speaker_id).or_default();
+        speaker.syntheses += 1;
+        speaker.audio_seconds += seconds;
+        stats.save(&path)
+    });
+    if let Err(err) = result {
+        eprintln!("warning: failed to record stats: {err}");
+    }
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    if args.reset {
+        if args.file.exists() {
+            fs::remove_file(&args.file)?;
+        }
+        return Ok(());
+    }
+
+    let stats = Stats::load(&args.file)?;
+    if output::json() {
+        return output::emit(&stats);
+    }
+
+    // metas.jsonがあれば、idにスタイル名を添える
+    let metas = load_metas(&model_dir.join("metas.json")).unwrap_or_default();
+    let style_name = |id: u32| {
+        metas.iter().find_map(|speaker| {
+            let style = speaker.styles.iter().find(|style| style.id == id)?;
+            Some(format!("{} ({})", speaker.name, style.name))
+        })
+    };
+
+    println!("syntheses: {}", stats.syntheses);
+    println!("audio:     {:.1} s", stats.audio_seconds);
+    for (id, speaker) in &stats.speakers {
+        println!(
+            "  {id:>4}  {:>8}  {:>10.1} s  {}",
+            speaker.syntheses,
+            speaker.audio_seconds,
+            style_name(*id).unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use chibivox::{AudioBuffer, AudioQueryModel};
+use serde::Serialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// --statsで最後に出す、スクリプトから読むための集計
+// startを呼んだ場合のみ記録する
+static SUMMARY: Mutex<Option<Summary>> = Mutex::new(None);
+
+#[derive(Serialize)]
+struct Summary {
+    #[serde(skip)]
+    started: Instant,
+    inputs: usize,
+    chars: usize,
+    phonemes: usize,
+    audio_seconds: f64,
+    // 段階ごとの経過時間(秒)。並行に合成した場合は各スレッドの合計
+    stages: Stages,
+    wall_seconds: f64,
+    // 経過時間を音声の長さで割ったもの。1未満なら実時間より速い
+    realtime_factor: Option<f64>,
+}
+
+#[derive(Default, Serialize)]
+struct Stages {
+    analysis: f64,
+    prediction: f64,
+    decode: f64,
+}
+
+// 各段階の経過時間。--jsonでは秒で書く
+#[derive(Clone, Copy, Serialize)]
+pub struct Timings {
+    #[serde(serialize_with = "seconds")]
+    pub analysis: Duration,
+    #[serde(serialize_with = "seconds")]
+    pub prediction: Duration,
+    #[serde(serialize_with = "seconds")]
+    pub decode: Duration,
+}
+
+fn seconds<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Summary>> {
+    SUMMARY.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+pub fn start() {
+    *lock() = Some(Summary {
+        started: Instant::now(),
+        inputs: 0,
+        chars: 0,
+        phonemes: 0,
+        audio_seconds: 0.,
+        stages: Stages::default(),
+        wall_seconds: 0.,
+        realtime_factor: None,
+    });
+}
+
+pub fn record(text: &str, audio_query: &AudioQueryModel, wav: &AudioBuffer, timings: &Timings) {
+    let mut summary = lock();
+    let Some(summary) = summary.as_mut() else {
+        return;
+    };
+    let moras = audio_query
+        .accent_phrases
+        .iter()
+        .flat_map(|accent_phrase| accent_phrase.moras.iter().chain(&accent_phrase.pause_mora));
+    summary.inputs += 1;
+    summary.chars += text.chars().count();
+    summary.phonemes += moras
+        .map(|mora| 1 + mora.consonant.is_some() as usize)
+        .sum::<usize>();
+    summary.audio_seconds += wav.duration() as f64;
+    summary.stages.analysis += timings.analysis.as_secs_f64();
+    summary.stages.prediction += timings.prediction.as_secs_f64();
+    summary.stages.decode += timings.decode.as_secs_f64();
+}
+
+// 集計を一行のJSONとして標準出力に書く
+pub fn finish() -> Result<()> {
+    let Some(mut summary) = lock().take() else {
+        return Ok(());
+    };
+    summary.wall_seconds = summary.started.elapsed().as_secs_f64();
+    summary.realtime_factor =
+        (summary.audio_seconds > 0.).then(|| summary.wall_seconds / summary.audio_seconds);
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
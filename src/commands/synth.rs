@@ -0,0 +1,346 @@
+use super::{
+    errors::{AtStage, Failures, Stage},
+    i18n,
+    journal::ResumeArgs,
+    load_core, numbered_path,
+    output::{self, Record},
+    output_paths, parallel_map, play,
+    progress::Progress,
+    ranged, render, resolve_jobs, stats, summary, synthesize_smoothed, terms, write_audio, Lap,
+    OutputFormat, Params, ProsodyArgs, Synthesis,
+};
+use anyhow::{bail, Result};
+use chibivox::{
+    AudioBuffer, InitializeOptions, ProsodySmoother, VoicevoxCore, Warnings, SAMPLING_RATE,
+};
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Speaker (style) id
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    /// Texts to synthesize; each is written to its own file. "-" reads lines from stdin
+    #[arg(
+        required_unless_present_any = ["text_file", "stdin", "from_query"],
+        conflicts_with_all = ["text_file", "stdin", "from_query"]
+    )]
+    texts: Vec<String>,
+    /// Read lines from standard input and synthesize each as it arrives
+    #[arg(long, conflicts_with_all = ["text_file", "concat"])]
+    stdin: bool,
+    /// Synthesize each non-empty line of a UTF-8 text file
+    #[arg(long)]
+    text_file: Option<PathBuf>,
+    /// Decode an AudioQuery JSON file (e.g. edited output of `query`) as-is
+    #[arg(long, conflicts_with_all = ["text_file", "stdin", "concat", "save_query"])]
+    from_query: Option<PathBuf>,
+    /// Write all inputs into a single concatenated WAV instead of one file each
+    #[arg(long)]
+    concat: bool,
+    /// Pull each input's mean pitch and speaking rate toward the preceding ones so the
+    /// narration does not reset at every input (0 = off, 1 = match fully)
+    #[arg(
+        long,
+        requires = "concat",
+        value_parser = ranged(0., 1.),
+        num_args = 0..=1,
+        default_missing_value = "0.5"
+    )]
+    smooth_prosody: Option<f32>,
+    /// Output file, or directory to write numbered files into
+    #[arg(short, long, default_value = "audio.wav")]
+    output: PathBuf,
+    /// Output format [default: inferred from the --output extension, otherwise wav]
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+    /// Shift the pitch of the waveform by this many semitones while keeping the formants,
+    /// e.g. for a deeper or younger character; unlike --pitch this bypasses the model
+    #[arg(
+        long,
+        default_value_t = 0.,
+        value_parser = ranged(-12., 12.),
+        allow_negative_numbers = true
+    )]
+    pitch_shift: f32,
+    /// Also write the AudioQuery actually used for each input as a .json next to the WAV
+    #[arg(long)]
+    save_query: bool,
+    /// Also write the full-context labels, phoneme ids, predicted durations and f0 of each
+    /// input as a .intermediates.json next to the WAV, to debug how a sentence was read
+    #[arg(long, conflicts_with = "from_query")]
+    dump_intermediates: bool,
+    /// Print a JSON summary to stdout when done: characters, phonemes, audio seconds,
+    /// per-stage time and real-time factor
+    #[arg(long, conflicts_with = "from_query")]
+    stats: bool,
+    /// Also play each result on the default audio device (requires the `play` feature)
+    #[arg(long)]
+    play: bool,
+    /// Pin ONNX Runtime threading so the same input always yields identical audio
+    #[arg(long)]
+    deterministic: bool,
+    /// ONNX Runtime intra-op threads (0 = available CPUs, honoring container limits)
+    #[arg(long, default_value_t = 0)]
+    cpu_num_threads: usize,
+    /// Inputs to synthesize concurrently (0 = as many as the CPUs allow); files and warnings
+    /// come out in input order, and with --deterministic are identical for any value
+    #[arg(short, long, default_value_t = 1, conflicts_with_all = ["stdin", "from_query"])]
+    jobs: usize,
+    #[command(flatten)]
+    resume: ResumeArgs,
+}
+
+impl Args {
+    // 連番のファイル名を作るときの拡張子
+    fn extension(&self) -> &'static str {
+        self.format.map_or("wav", OutputFormat::extension)
+    }
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    if args.stats {
+        summary::start();
+    }
+    // 失敗した入力があっても、それまでの集計は出す
+    let result = run_inputs(&args, model_dir);
+    if args.stats {
+        summary::finish()?;
+    }
+    result
+}
+
+fn run_inputs(args: &Args, model_dir: &Path) -> Result<()> {
+    let mut initialize_options = InitializeOptions {
+        deterministic: args.deterministic,
+        cpu_num_threads: args.cpu_num_threads,
+        ..Default::default()
+    };
+    // --smooth-prosodyは前の入力の結果に寄せるので、順に合成する
+    let jobs = if args.smooth_prosody.is_some() {
+        1
+    } else {
+        args.jobs
+    };
+    let jobs = resolve_jobs(jobs, &mut initialize_options);
+    if args.resume.is_enabled() && (args.concat || args.stdin || args.from_query.is_some()) {
+        bail!("--skip-existing and --journal only apply to one file per input");
+    }
+    let core = load_core(model_dir, &initialize_options)?;
+    let speaker_id = args.prosody.speaker(args.speaker);
+    let params = Params {
+        speaker_id,
+        options: args.prosody.to_options(&core, speaker_id),
+        pitch_shift: args.pitch_shift,
+    };
+
+    // queryの出力を手で直してから鳴らし直すための経路。durationやf0は予測し直さない
+    if let Some(from_query) = &args.from_query {
+        let json = fs::read_to_string(from_query)?;
+        let name = from_query.display().to_string();
+        let mut lap = Lap::new();
+        let wav = core
+            .synthesis_from_query(&json, speaker_id)
+            .at_stage(Stage::Decode, &name)?
+            .pitch_shift(args.pitch_shift);
+        let synthesis = Synthesis {
+            wav,
+            warnings: Warnings::new(),
+            timings: summary::Timings {
+                analysis: Duration::ZERO,
+                prediction: Duration::ZERO,
+                decode: lap.split(),
+            },
+        };
+        stats::record(speaker_id, &synthesis.wav);
+        let credit = terms::credit(&core, speaker_id);
+        write_audio(&args.output, &synthesis.wav, args.format, credit)?;
+        Record::new(&name, &args.output, &synthesis).print()?;
+        if args.play {
+            play(&synthesis.wav)?;
+        }
+        return Ok(());
+    }
+
+    if args.stdin || args.texts == ["-"] {
+        return run_stdin(&core, args, &params);
+    }
+
+    // 失敗した行を報告できるように、入力ごとに名前を付けておく
+    let inputs: Vec<(String, String)> = match &args.text_file {
+        Some(text_file) => fs::read_to_string(text_file)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let name = format!("{}:{}", text_file.display(), i + 1);
+                (name, line.trim().to_string())
+            })
+            .collect(),
+        None => args
+            .texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| (format!("input {}", i + 1), text.clone()))
+            .collect(),
+    };
+
+    let mut failures = Failures::default();
+    if args.concat {
+        let progress = Progress::new(inputs.len());
+        let smoother = args
+            .smooth_prosody
+            .map(|strength| Mutex::new(ProsodySmoother::new(strength)));
+        let syntheses = parallel_map(inputs.len(), jobs, |i| {
+            let (name, text) = &inputs[i];
+            progress.stage(name, "synthesizing");
+            // 一つにまとめたWAVでも、行ごとに調整し直せるようにAudioQueryは入力ごとに分けて書き出す
+            let numbered = numbered_path(&args.output, i + 1, "json")?;
+            let query_path = args.save_query.then(|| numbered.with_extension("json"));
+            let intermediates_path = args
+                .dump_intermediates
+                .then(|| numbered.with_extension("intermediates.json"));
+            let result = synthesize_smoothed(
+                &core,
+                text,
+                &params,
+                query_path.as_deref(),
+                intermediates_path.as_deref(),
+                smoother.as_ref(),
+            );
+            progress.inc();
+            result
+        });
+        // 並行に合成しても、つなげる順序は入力の順のまま
+        let mut wav = AudioBuffer::mono(Vec::new(), SAMPLING_RATE);
+        for ((name, _), result) in inputs.iter().zip(syntheses) {
+            match result {
+                Ok(synthesis) => {
+                    let offset = wav.duration() as f64;
+                    let record = Record::new(name, &args.output, &synthesis).at_offset(offset);
+                    progress.suspend(|| record.print())?;
+                    wav.samples.extend(synthesis.wav.samples);
+                }
+                Err(err) => {
+                    progress.eprintln(format!("{name}: {err}"));
+                    failures.add(&err);
+                }
+            }
+        }
+        progress.stage(&args.output.display().to_string(), "writing");
+        let credit = terms::credit(&core, params.speaker_id);
+        write_audio(&args.output, &wav, args.format, credit)?;
+        progress.finish();
+        if args.play {
+            play(&wav)?;
+        }
+    } else {
+        let paths = output_paths(&args.output, inputs.len(), args.extension())?;
+        let journal = args.resume.open()?;
+        let pending: Vec<usize> = (0..inputs.len())
+            .filter(|&i| !journal.should_skip(&paths[i]))
+            .collect();
+        // --jsonでは書き出した分だけを結果として出すので、飛ばした数は知らせない
+        let skipped = inputs.len() - pending.len();
+        if skipped > 0 && !output::json() {
+            eprintln!(
+                "{}",
+                i18n::pick(
+                    format!("skipping {skipped} completed inputs"),
+                    format!("書き出し済みの{skipped}件を飛ばします"),
+                )
+            );
+        }
+
+        let progress = Progress::new(pending.len());
+        let results = parallel_map(pending.len(), jobs, |j| -> Result<_> {
+            let i = pending[j];
+            let (name, text) = &inputs[i];
+            progress.stage(name, "synthesizing");
+            let result = render(
+                &core,
+                text,
+                &params,
+                &paths[i],
+                args.format,
+                args.save_query,
+                args.dump_intermediates,
+            );
+            progress.inc();
+            let synthesis = result?;
+            journal.complete(&paths[i])?;
+            // 結果の表示と再生は、並行に合成した順ではなく入力の順に行う
+            let record = Record::new(name, &paths[i], &synthesis);
+            Ok((record, args.play.then_some(synthesis.wav)))
+        });
+        progress.finish();
+        for (i, result) in pending.into_iter().zip(results) {
+            let result = result.and_then(|(record, wav)| {
+                record.print()?;
+                wav.map_or(Ok(()), |wav| play(&wav))
+            });
+            if let Err(err) = result {
+                eprintln!("{}: {err}", inputs[i].0);
+                failures.add(&err);
+            }
+        }
+    }
+
+    failures.finish(inputs.len(), |failures| {
+        inputs_failed(failures, inputs.len())
+    })
+}
+
+fn inputs_failed(failures: usize, total: usize) -> String {
+    i18n::pick(
+        format!("{failures} of {total} inputs failed"),
+        format!("{total} 件中 {failures} 件の合成に失敗しました"),
+    )
+}
+
+// パイプの末尾で使えるように、一行届くたびに合成して連番で書き出す
+fn run_stdin(core: &VoicevoxCore, args: &Args, params: &Params) -> Result<()> {
+    let mut count = 0;
+    let mut failures = Failures::default();
+    for (i, line) in io::stdin().lock().lines().enumerate() {
+        let line = line?;
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        count += 1;
+        let path = numbered_path(&args.output, count, args.extension())?;
+        let result = render(
+            core,
+            text,
+            params,
+            &path,
+            args.format,
+            args.save_query,
+            args.dump_intermediates,
+        )
+        .and_then(|synthesis| {
+            Record::new(&format!("stdin:{}", i + 1), &path, &synthesis).print()?;
+            if args.play {
+                play(&synthesis.wav)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            eprintln!("stdin:{}: {err}", i + 1);
+            failures.add(&err);
+        }
+    }
+
+    failures.finish(count, |failures| inputs_failed(failures, count))
+}
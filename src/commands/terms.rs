@@ -0,0 +1,27 @@
+use chibivox::{InitializeOptions, VoicevoxCore};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// --acknowledge-terms と --embed-credit。起動時に一度だけ設定する
+static ACKNOWLEDGED: AtomicBool = AtomicBool::new(false);
+static EMBED_CREDIT: AtomicBool = AtomicBool::new(false);
+
+pub fn set(acknowledged: bool, embed_credit: bool) {
+    ACKNOWLEDGED.store(acknowledged, Ordering::Relaxed);
+    EMBED_CREDIT.store(embed_credit, Ordering::Relaxed);
+}
+
+// 規約への同意を、読み込むモデルに伝える
+pub fn initialize_options(options: &InitializeOptions) -> InitializeOptions {
+    InitializeOptions {
+        acknowledge_terms: ACKNOWLEDGED.load(Ordering::Relaxed),
+        ..options.clone()
+    }
+}
+
+// --embed-credit が付いていて、metas.jsonにクレジット表記があれば、それを出力に添える
+pub fn credit(core: &VoicevoxCore, speaker_id: u32) -> Option<&str> {
+    if !EMBED_CREDIT.load(Ordering::Relaxed) {
+        return None;
+    }
+    core.usage_terms(speaker_id)?.credit.as_deref()
+}
@@ -0,0 +1,61 @@
+use super::{load_core, output, ranged, stats, terms, write_audio, OutputFormat};
+use anyhow::{Context, Result};
+use chibivox::{AudioBuffer, InitializeOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Recording (WAV) whose timing and intonation are re-spoken
+    recording: PathBuf,
+    /// Phoneme alignment of the recording in .lab format (`start end phoneme` per line)
+    /// [default: the recording path with a .lab extension]
+    #[arg(long)]
+    lab: Option<PathBuf>,
+    /// Speaker (style) id to re-speak with
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    /// Shift the recorded pitch by this many semitones to fit the speaker's range
+    #[arg(
+        long,
+        default_value_t = 0.,
+        value_parser = ranged(-24., 24.),
+        allow_negative_numbers = true
+    )]
+    shift: f32,
+    /// Output file
+    #[arg(short, long, default_value = "audio.wav")]
+    output: PathBuf,
+    /// Output format [default: inferred from the --output extension, otherwise wav]
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let lab_path = args
+        .lab
+        .clone()
+        .unwrap_or_else(|| args.recording.with_extension("lab"));
+    let recording = fs::read(&args.recording)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| AudioBuffer::from_wav(&bytes))
+        .with_context(|| format!("failed to read {}", args.recording.display()))?;
+    let lab = fs::read_to_string(&lab_path)
+        .with_context(|| format!("failed to read {}", lab_path.display()))?;
+
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let wav = core.voice_change(&recording, &lab, args.speaker, args.shift)?;
+    stats::record(args.speaker, &wav);
+    let credit = terms::credit(&core, args.speaker);
+    write_audio(&args.output, &wav, args.format, credit)?;
+    if output::json() {
+        output::emit(&serde_json::json!({
+            "input": args.recording,
+            "path": args.output,
+            "duration": wav.duration(),
+        }))?;
+    }
+    Ok(())
+}
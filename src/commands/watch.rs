@@ -0,0 +1,147 @@
+use super::{
+    load_core, output::Record, synthesize, terms, write_audio, OutputFormat, Params, ProsodyArgs,
+};
+use anyhow::{bail, Result};
+use chibivox::{AudioBuffer, InitializeOptions, VoicevoxCore, SAMPLING_RATE};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+// ファイルを置くことしかできないツールと連携するため、dirに置かれた.txtを順に合成する
+#[derive(clap::Args)]
+pub struct Args {
+    /// Directory to watch for new .txt files
+    dir: PathBuf,
+    /// Speaker (style) id
+    #[arg(short, long, default_value_t = 0)]
+    speaker: u32,
+    #[command(flatten)]
+    prosody: ProsodyArgs,
+    /// Output format of the file written next to each .txt
+    #[arg(long, value_enum, default_value_t = OutputFormat::Wav)]
+    format: OutputFormat,
+    /// Where to move .txt files once synthesized [default: <DIR>/processed]
+    #[arg(long)]
+    processed_dir: Option<PathBuf>,
+    /// Where to move .txt files that failed to synthesize [default: <DIR>/failed]
+    #[arg(long)]
+    failed_dir: Option<PathBuf>,
+    /// Polling interval in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    interval: u64,
+    /// Process the files already in the directory and exit
+    #[arg(long)]
+    once: bool,
+}
+
+pub fn run(args: Args, model_dir: &Path) -> Result<()> {
+    let core = load_core(model_dir, &InitializeOptions::default())?;
+    let speaker_id = args.prosody.speaker(args.speaker);
+    let params = Params {
+        speaker_id,
+        options: args.prosody.to_options(&core, speaker_id),
+        ..Default::default()
+    };
+    let processed_dir = args
+        .processed_dir
+        .clone()
+        .unwrap_or_else(|| args.dir.join("processed"));
+    let failed_dir = args
+        .failed_dir
+        .clone()
+        .unwrap_or_else(|| args.dir.join("failed"));
+
+    if !args.once {
+        eprintln!("watching {}", args.dir.display());
+    }
+    // 書き込み途中のファイルを拾わないよう、前回見たときから大きさが変わっていないものだけを処理する
+    let mut sizes = HashMap::new();
+    loop {
+        let mut pending = HashMap::new();
+        for path in text_files(&args.dir)? {
+            // 一覧を取ってから消されたものは飛ばす
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if !args.once && sizes.get(&path) != Some(&metadata.len()) {
+                pending.insert(path, metadata.len());
+                continue;
+            }
+
+            let output = path.with_extension(args.format.extension());
+            let destination = match render_file(&core, &params, &path, &output, args.format) {
+                Ok(()) => {
+                    eprintln!("{}", output.display());
+                    &processed_dir
+                }
+                Err(err) => {
+                    eprintln!("{}: {err}", path.display());
+                    &failed_dir
+                }
+            };
+            // 移せないと同じファイルを何度も合成してしまうので、ここで止める
+            move_into(&path, destination)?;
+        }
+        sizes = pending;
+
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(args.interval));
+    }
+}
+
+// 直下の.txtだけを見る。processedなどのサブディレクトリには立ち入らない
+fn text_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "txt") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// 空でない行ごとに合成して、一つのファイルにつなげる
+fn render_file(
+    core: &VoicevoxCore,
+    params: &Params,
+    path: &Path,
+    output: &Path,
+    format: OutputFormat,
+) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        bail!("no text to synthesize");
+    }
+
+    let mut wav = AudioBuffer::mono(Vec::new(), SAMPLING_RATE);
+    for line in lines {
+        let synthesis = synthesize(core, line, params, None, None)?;
+        let offset = wav.duration() as f64;
+        Record::new(&path.display().to_string(), output, &synthesis)
+            .at_offset(offset)
+            .print()?;
+        wav.samples.extend(synthesis.wav.samples);
+    }
+    let credit = terms::credit(core, params.speaker_id);
+    write_audio(output, &wav, Some(format), credit)
+}
+
+fn move_into(path: &Path, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let file_name = path.file_name().expect("listed files have a name");
+    fs::rename(path, dir.join(file_name))?;
+    Ok(())
+}
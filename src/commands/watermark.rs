@@ -0,0 +1,68 @@
+use super::output;
+use anyhow::{bail, Result};
+use chibivox::{watermark, AudioBuffer};
+use std::{borrow::Cow, fs, path::PathBuf, sync::OnceLock};
+
+// 鍵を設定した場合のみ、書き出す音声に透かしを入れる
+pub const WATERMARK_ENV: &str = "CHIBIVOX_WATERMARK_KEY";
+
+static KEY: OnceLock<String> = OnceLock::new();
+
+pub fn set_key(key: String) {
+    let _ = KEY.set(key);
+}
+
+pub fn enabled() -> bool {
+    KEY.get().is_some()
+}
+
+// 少しずつ送り出す場合に、続けて透かしを入れるためのもの
+pub fn embedder() -> Option<watermark::Embedder> {
+    KEY.get().map(|key| watermark::Embedder::new(key))
+}
+
+pub fn apply(wav: &AudioBuffer) -> Cow<'_, AudioBuffer> {
+    match KEY.get() {
+        Some(key) => Cow::Owned(watermark::embed(wav, key)),
+        None => Cow::Borrowed(wav),
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// WAV files to check
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Key the files were marked with
+    #[arg(long, env = WATERMARK_ENV)]
+    key: String,
+}
+
+// 透かしの有無をファイルごとに表示し、無いものがあれば失敗にする
+pub fn run(args: Args) -> Result<()> {
+    let mut unmarked = 0;
+    for path in &args.files {
+        let wav = AudioBuffer::from_wav(&fs::read(path)?)?;
+        let score = watermark::detect(&wav, &args.key);
+        let marked = score > watermark::DETECTION_THRESHOLD;
+        if !marked {
+            unmarked += 1;
+        }
+        if output::json() {
+            output::emit(&serde_json::json!({ "path": path, "marked": marked, "score": score }))?;
+        } else {
+            println!(
+                "{}\t{}\t{score:.1}",
+                path.display(),
+                if marked { "marked" } else { "unmarked" }
+            );
+        }
+    }
+    if unmarked > 0 {
+        bail!(
+            "{unmarked} of {} files carry no watermark",
+            args.files.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+// decodeセッションを置くデバイスと、複数のセッションへの振り分け
+
+use crate::inference::Session;
+use anyhow::{bail, Result};
+use std::{
+    fmt,
+    ops::Deref,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    Cpu,
+    // CUDAのデバイス番号
+    Cuda(i32),
+}
+
+impl FromStr for Device {
+    type Err = anyhow::Error;
+
+    // cpu、cuda、cuda:N の形式
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "cpu" => Ok(Self::Cpu),
+            None if s == "cuda" => Ok(Self::Cuda(0)),
+            Some(("cuda", device_id)) => Ok(Self::Cuda(device_id.parse()?)),
+            _ => bail!("unknown device: {s} (expected cpu or cuda:N)"),
+        }
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cpu => write!(f, "cpu"),
+            Self::Cuda(device_id) => write!(f, "cuda:{device_id}"),
+        }
+    }
+}
+
+// 同じモデルを読み込んだセッションの組
+// 実行中の数が最も少ないものを選び、同じ数なら順番に回す
+pub(crate) struct SessionPool {
+    sessions: Vec<(Session, AtomicUsize)>,
+    next: AtomicUsize,
+}
+
+impl SessionPool {
+    pub fn new(sessions: Vec<Session>) -> Self {
+        assert!(!sessions.is_empty());
+        Self {
+            sessions: sessions
+                .into_iter()
+                .map(|session| (session, AtomicUsize::new(0)))
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn acquire(&self) -> PooledSession<'_> {
+        let len = self.sessions.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let index = (0..len)
+            .map(|i| (start + i) % len)
+            .min_by_key(|&i| self.sessions[i].1.load(Ordering::Relaxed))
+            .unwrap();
+        let (session, running) = &self.sessions[index];
+        running.fetch_add(1, Ordering::Relaxed);
+        PooledSession { session, running }
+    }
+}
+
+// 使い終わると実行中の数を戻す
+pub(crate) struct PooledSession<'a> {
+    session: &'a Session,
+    running: &'a AtomicUsize,
+}
+
+impl Deref for PooledSession<'_> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        self.session
+    }
+}
+
+impl Drop for PooledSession<'_> {
+    fn drop(&mut self) {
+        self.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
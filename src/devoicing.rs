@@ -0,0 +1,48 @@
+use crate::synthesis_engine::{AccentPhraseModel, MoraModel};
+
+const DEVOICEABLE_VOWELS: &[&str] = &["i", "u"];
+const VOICELESS_CONSONANTS: &[&str] = &[
+    "k", "ky", "s", "sh", "t", "ts", "ch", "h", "hy", "f", "p", "py",
+];
+
+// 無声子音に挟まれた「い」「う」を無声化する(例:「きかい」の「き」)
+// 連続して無声化すると不自然になるので、直前のモーラが無声化された場合は見送る
+pub fn devoice(mut accent_phrases: Vec<AccentPhraseModel>) -> Vec<AccentPhraseModel> {
+    let mut positions = Vec::new();
+    for (i, accent_phrase) in accent_phrases.iter().enumerate() {
+        positions.extend((0..accent_phrase.moras.len()).map(|j| Some((i, j))));
+        if accent_phrase.pause_mora.is_some() {
+            positions.push(None);
+        }
+    }
+
+    let mut previous_devoiced = false;
+    for k in 0..positions.len() {
+        let devoiced = match (positions[k], positions.get(k + 1).copied().flatten()) {
+            (Some((i, j)), Some((next_i, next_j))) if !previous_devoiced => {
+                is_devoiceable(&accent_phrases[i].moras[j])
+                    && is_voiceless(&accent_phrases[next_i].moras[next_j])
+            }
+            _ => false,
+        };
+        if devoiced {
+            let (i, j) = positions[k].unwrap();
+            let mora = &mut accent_phrases[i].moras[j];
+            mora.vowel = mora.vowel.to_uppercase();
+            mora.pitch = 0.;
+        }
+        previous_devoiced = devoiced;
+    }
+
+    accent_phrases
+}
+
+fn is_devoiceable(mora: &MoraModel) -> bool {
+    is_voiceless(mora) && DEVOICEABLE_VOWELS.contains(&mora.vowel.as_str())
+}
+
+fn is_voiceless(mora: &MoraModel) -> bool {
+    mora.consonant
+        .as_deref()
+        .is_some_and(|consonant| VOICELESS_CONSONANTS.contains(&consonant))
+}
@@ -0,0 +1,218 @@
+// FLACの書き出し
+// 16bit固定で、固定予測(0〜4次)とライス符号だけを使う簡易なエンコーダ
+
+const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 16;
+// 4bitのライスパラメータのうち、15はエスケープなので使わない
+const MAX_RICE_PARAMETER: u32 = 14;
+
+pub fn encode_flac(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let samples: Vec<i32> = samples
+        .iter()
+        .map(|sample| (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i32)
+        .collect();
+
+    let mut flac = b"fLaC".to_vec();
+    flac.extend_from_slice(&stream_info(sample_rate, channels, frames));
+
+    for (frame_number, start) in (0..frames).step_by(BLOCK_SIZE).enumerate() {
+        let block_size = BLOCK_SIZE.min(frames - start);
+        let block = &samples[start * channels..(start + block_size) * channels];
+        flac.extend_from_slice(&frame(block, channels, frame_number as u32));
+    }
+    flac
+}
+
+fn stream_info(sample_rate: u32, channels: usize, frames: usize) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // 最後のメタデータブロック、種類はSTREAMINFO、長さは34バイト
+    writer.write(1, 1);
+    writer.write(0, 7);
+    writer.write(34, 24);
+
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(BLOCK_SIZE as u64, 16);
+    // フレームの最小・最大サイズは不明として0
+    writer.write(0, 24);
+    writer.write(0, 24);
+    writer.write(sample_rate as u64, 20);
+    writer.write(channels as u64 - 1, 3);
+    writer.write(BITS_PER_SAMPLE as u64 - 1, 5);
+    writer.write(frames as u64, 36);
+    // MD5も省略できる
+    writer.write(0, 64);
+    writer.write(0, 64);
+    writer.bytes
+}
+
+fn frame(block: &[i32], channels: usize, frame_number: u32) -> Vec<u8> {
+    let block_size = block.len() / channels;
+
+    let mut writer = BitWriter::default();
+    // 同期コードと固定ブロックサイズ
+    writer.write(0b1111_1111_1111_1000, 16);
+    // ブロックサイズは末尾に16bitで、サンプリングレートはSTREAMINFOに従う
+    writer.write(0b0111, 4);
+    writer.write(0b0000, 4);
+    // 各チャンネルを独立に符号化する
+    writer.write(channels as u64 - 1, 4);
+    writer.write(0b100, 3);
+    writer.write(0, 1);
+    writer.write_utf8(frame_number);
+    writer.write(block_size as u64 - 1, 16);
+    let crc = crc8(&writer.bytes);
+    writer.write(crc as u64, 8);
+
+    for channel in 0..channels {
+        let samples: Vec<i32> = block
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect();
+        subframe(&mut writer, &samples);
+    }
+
+    writer.align();
+    let crc = crc16(&writer.bytes);
+    writer.write(crc as u64, 16);
+    writer.bytes
+}
+
+// 残差の絶対値の和が最小になる次数の固定予測で書き出す
+fn subframe(writer: &mut BitWriter, samples: &[i32]) {
+    let (order, residuals) = (0..=4.min(samples.len()))
+        .map(|order| (order, fixed_residuals(samples, order)))
+        .min_by_key(|(_, residuals)| {
+            residuals
+                .iter()
+                .map(|r| r.unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap();
+
+    writer.write(0, 1);
+    writer.write(0b001000 | order as u64, 6);
+    writer.write(0, 1);
+    for &sample in &samples[..order] {
+        writer.write_signed(sample, BITS_PER_SAMPLE);
+    }
+
+    // パーティションは分けず、ブロック全体で一つのライスパラメータを使う
+    let residuals: Vec<u64> = residuals.iter().map(|&r| zigzag(r)).collect();
+    let parameter = (0..=MAX_RICE_PARAMETER)
+        .min_by_key(|&k| {
+            residuals.iter().map(|&u| u >> k).sum::<u64>() + residuals.len() as u64 * k as u64
+        })
+        .unwrap();
+    writer.write(0b00, 2);
+    writer.write(0, 4);
+    writer.write(parameter as u64, 4);
+    for u in residuals {
+        writer.write_unary(u >> parameter);
+        writer.write(u & ((1 << parameter) - 1), parameter);
+    }
+}
+
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+    let x = |i: usize| samples[i];
+    (order..samples.len())
+        .map(|n| match order {
+            0 => x(n),
+            1 => x(n) - x(n - 1),
+            2 => x(n) - 2 * x(n - 1) + x(n - 2),
+            3 => x(n) - 3 * x(n - 1) + 3 * x(n - 2) - x(n - 3),
+            _ => x(n) - 4 * x(n - 1) + 6 * x(n - 2) - 4 * x(n - 3) + x(n - 4),
+        })
+        .collect()
+}
+
+fn zigzag(residual: i32) -> u64 {
+    ((residual << 1) ^ (residual >> 31)) as u32 as u64
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+// 上位ビットから詰めていく
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.bits % 8 == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+        }
+        self.bits += 1;
+    }
+
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn write_unary(&mut self, value: u64) {
+        for _ in 0..value {
+            self.write_bit(false);
+        }
+        self.write_bit(true);
+    }
+
+    // フレーム番号はUTF-8と同じ方式の可変長で書く
+    fn write_utf8(&mut self, value: u32) {
+        if value < 0x80 {
+            self.write(value as u64, 8);
+            return;
+        }
+        let extra: u32 = match value {
+            ..=0x7ff => 1,
+            ..=0xffff => 2,
+            ..=0x1f_ffff => 3,
+            ..=0x3ff_ffff => 4,
+            _ => 5,
+        };
+        let prefix = (0xffu32 << (7 - extra)) & 0xff;
+        self.write((prefix | (value >> (6 * extra))) as u64, 8);
+        for i in (0..extra).rev() {
+            self.write((0x80 | ((value >> (6 * i)) & 0x3f)) as u64, 8);
+        }
+    }
+
+    fn align(&mut self) {
+        self.bits = self.bytes.len() as u32 * 8;
+    }
+}
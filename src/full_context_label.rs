@@ -73,16 +73,20 @@ pub struct AccentPhrase {
     pub moras: Vec<Mora>,
     pub accent: usize,
     pub is_interrogative: bool,
+    // ラベルが扱える長さを超え、残りのモーラを捨てた
+    pub truncated: bool,
 }
 
 impl AccentPhrase {
     pub fn from_phonemes(mut phonemes: Vec<Phoneme>) -> Result<Self> {
         let mut moras = Vec::with_capacity(phonemes.len());
         let mut mora_phonemes = Vec::with_capacity(phonemes.len());
+        let mut truncated = false;
         for i in 0..phonemes.len() {
             {
                 let phoneme = phonemes.get_mut(i).unwrap();
                 if phoneme.contexts.get("a2").map(|s| s.as_str()) == Some("49") {
+                    truncated = true;
                     break;
                 }
                 mora_phonemes.push(phoneme.clone());
@@ -134,6 +138,7 @@ impl AccentPhrase {
             moras,
             accent,
             is_interrogative,
+            truncated,
         })
     }
 }
@@ -1,10 +1,39 @@
+use crate::synthesis_engine::FRAME_RATE;
 use anyhow::Result;
-use ort::Session;
+use std::{fmt, ops::Range};
+#[cfg(feature = "wasm")]
+use tract_onnx::prelude::{tensor0, tensor1, Tensor};
 
-const PHONEME_LENGTH_MINIMAL: f32 = 0.01;
+#[cfg(feature = "wasm")]
+pub(crate) use crate::tract::Session;
+#[cfg(not(feature = "wasm"))]
+pub(crate) use ort::Session;
 
+pub(crate) const PHONEME_LENGTH_MINIMAL: f32 = 0.01;
+
+// decoderの出力が、前後に足したパディングより短かった場合のエラー
+#[derive(Debug)]
+pub struct PaddingTrimError {
+    pub output_size: usize,
+    pub padding_size: usize,
+}
+
+impl fmt::Display for PaddingTrimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "decoder output of {} samples is shorter than its padding ({} samples on each side)",
+            self.output_size, self.padding_size
+        )
+    }
+}
+
+impl std::error::Error for PaddingTrimError {}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 pub fn predict_duration(
-    session: Session,
+    session: &Session,
     phoneme_vector: &[i64],
     speaker_id: u32,
 ) -> Result<Vec<f32>> {
@@ -13,24 +42,36 @@ pub fn predict_duration(
         "speaker_id" => ndarray::arr1(&[speaker_id as i64])
     ]?;
     let output_tensors = session.run(input_tensors)?;
+    // 下限への引き上げは、警告を出せるように呼び出し側で行う
     let output = output_tensors["phoneme_length"]
         .extract_tensor::<f32>()?
         .view()
-        .iter()
-        .map(|output_item| {
-            if *output_item < PHONEME_LENGTH_MINIMAL {
-                PHONEME_LENGTH_MINIMAL
-            } else {
-                *output_item
-            }
-        })
-        .collect();
+        .to_owned()
+        .into_raw_vec();
 
     Ok(output)
 }
 
+#[cfg(feature = "wasm")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn predict_duration(
+    session: &Session,
+    phoneme_vector: &[i64],
+    speaker_id: u32,
+) -> Result<Vec<f32>> {
+    session.run(
+        vec![
+            ("phoneme_list", tensor1(phoneme_vector)),
+            ("speaker_id", tensor1(&[speaker_id as i64])),
+        ],
+        "phoneme_length",
+    )
+}
+
+#[cfg(not(feature = "wasm"))]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 pub fn predict_intonation(
-    session: Session,
+    session: &Session,
     length: usize,
     vowel_phoneme_vector: &[i64],
     consonant_phoneme_vector: &[i64],
@@ -60,27 +101,78 @@ pub fn predict_intonation(
     Ok(output)
 }
 
+#[cfg(feature = "wasm")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn predict_intonation(
+    session: &Session,
+    length: usize,
+    vowel_phoneme_vector: &[i64],
+    consonant_phoneme_vector: &[i64],
+    start_accent_vector: &[i64],
+    end_accent_vector: &[i64],
+    start_accent_phrase_vector: &[i64],
+    end_accent_phrase_vector: &[i64],
+    speaker_id: u32,
+) -> Result<Vec<f32>> {
+    session.run(
+        vec![
+            ("length", tensor0(length as i64)),
+            ("vowel_phoneme_list", tensor1(vowel_phoneme_vector)),
+            ("consonant_phoneme_list", tensor1(consonant_phoneme_vector)),
+            ("start_accent_list", tensor1(start_accent_vector)),
+            ("end_accent_list", tensor1(end_accent_vector)),
+            (
+                "start_accent_phrase_list",
+                tensor1(start_accent_phrase_vector),
+            ),
+            ("end_accent_phrase_list", tensor1(end_accent_phrase_vector)),
+            ("speaker_id", tensor1(&[speaker_id as i64])),
+        ],
+        "f0_list",
+    )
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 pub fn decode(
-    session: Session,
+    session: &Session,
     length: usize,
     phoneme_size: usize,
     f0: Vec<f32>,
     phoneme_vector: Vec<f32>,
+    padding: f32,
     speaker_id: u32,
 ) -> Result<Vec<f32>> {
-    const PADDING_SIZE: f64 = 0.4;
-    const DEFAULT_SAMPLING_RATE: f64 = 24000.0;
-
-    let padding_size = ((PADDING_SIZE * DEFAULT_SAMPLING_RATE) / 256.0).round() as usize;
+    let padding_size = (padding * FRAME_RATE).round() as usize;
     let start_and_end_padding_size = 2 * padding_size;
     let length_with_padding = length + start_and_end_padding_size;
     let f0_with_padding = make_f0_with_padding(f0.to_vec(), padding_size);
     let phoneme_with_padding =
         make_phoneme_with_padding(phoneme_vector, phoneme_size, padding_size);
 
+    let output = run_decode(
+        session,
+        length_with_padding,
+        phoneme_size,
+        f0_with_padding,
+        phoneme_with_padding,
+        speaker_id,
+    )?;
+
+    Ok(trim_padding_from_output(output, padding_size)?)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run_decode(
+    session: &Session,
+    length: usize,
+    phoneme_size: usize,
+    f0: Vec<f32>,
+    phoneme: Vec<f32>,
+    speaker_id: u32,
+) -> Result<Vec<f32>> {
     let input_tensors = ort::inputs![
-        "f0" => ndarray::arr1(&f0_with_padding).into_shape([length_with_padding, 1])?,
-        "phoneme" => ndarray::arr1(&phoneme_with_padding).into_shape([length_with_padding, phoneme_size])?,
+        "f0" => ndarray::arr1(&f0).into_shape([length, 1])?,
+        "phoneme" => ndarray::arr1(&phoneme).into_shape([length, phoneme_size])?,
         "speaker_id" => ndarray::arr1(&[speaker_id as i64])
     ]?;
     let output_tensors = session.run(input_tensors)?;
@@ -90,7 +182,29 @@ pub fn decode(
         .to_owned()
         .into_raw_vec();
 
-    Ok(trim_padding_from_output(output, padding_size))
+    Ok(output)
+}
+
+#[cfg(feature = "wasm")]
+fn run_decode(
+    session: &Session,
+    length: usize,
+    phoneme_size: usize,
+    f0: Vec<f32>,
+    phoneme: Vec<f32>,
+    speaker_id: u32,
+) -> Result<Vec<f32>> {
+    session.run(
+        vec![
+            ("f0", Tensor::from_shape(&[length, 1], &f0)?),
+            (
+                "phoneme",
+                Tensor::from_shape(&[length, phoneme_size], &phoneme)?,
+            ),
+            ("speaker_id", tensor1(&[speaker_id as i64])),
+        ],
+        "wave",
+    )
 }
 
 fn make_f0_with_padding(f0: Vec<f32>, padding_size: usize) -> Vec<f32> {
@@ -117,9 +231,26 @@ fn make_phoneme_with_padding(
         .collect()
 }
 
-fn trim_padding_from_output(mut output: Vec<f32>, padding_f0_size: usize) -> Vec<f32> {
-    let padding_sampling_size = padding_f0_size * 256;
-    output
-        .drain(padding_sampling_size..output.len() - padding_sampling_size)
-        .collect()
+// 新たに確保せず、元のVecを切り詰めて前に詰める
+fn trim_padding_from_output(
+    mut output: Vec<f32>,
+    padding_f0_size: usize,
+) -> Result<Vec<f32>, PaddingTrimError> {
+    let range = unpadded_range(output.len(), padding_f0_size * 256)?;
+    output.truncate(range.end);
+    output.drain(..range.start);
+    Ok(output)
+}
+
+fn unpadded_range(
+    output_size: usize,
+    padding_size: usize,
+) -> Result<Range<usize>, PaddingTrimError> {
+    if output_size < 2 * padding_size {
+        return Err(PaddingTrimError {
+            output_size,
+            padding_size,
+        });
+    }
+    Ok(padding_size..output_size - padding_size)
 }
@@ -0,0 +1,159 @@
+use crate::{
+    mora_list::MORA_LIST_MINIMUM,
+    synthesis_engine::{AccentPhraseModel, MoraModel},
+};
+use anyhow::{anyhow, Context, Result};
+
+const PAUSE_DELIMITER: char = '、';
+const ACCENT_PHRASE_DELIMITERS: &[char] = &['/', ' ', '　'];
+const ACCENT_SYMBOL: char = '\'';
+const LONG_VOWEL_SYMBOL: char = 'ー';
+const INTERROGATION_MARKS: &[char] = &['?', '？'];
+
+// 読みが確定しているカタカナ列からAccentPhraseを組み立てる
+// 「、」で息継ぎ、「/」か空白でアクセント句を区切り、「'」の直前のモーラをアクセント核とする
+pub fn parse_kana(text: &str) -> Result<Vec<AccentPhraseModel>> {
+    let breath_groups: Vec<&str> = text.split(PAUSE_DELIMITER).collect();
+
+    let mut accent_phrases = Vec::new();
+    for (i, breath_group) in breath_groups.iter().enumerate() {
+        let phrases: Vec<&str> = breath_group
+            .split(ACCENT_PHRASE_DELIMITERS)
+            .filter(|phrase| !phrase.is_empty())
+            .collect();
+        if phrases.is_empty() {
+            return Err(anyhow!("KanaParseError::EmptyPhrase"));
+        }
+
+        for (j, phrase) in phrases.iter().enumerate() {
+            let mut accent_phrase = text_to_accent_phrase(phrase)?;
+            if i != breath_groups.len() - 1 && j == phrases.len() - 1 {
+                accent_phrase.pause_mora = Some(pause_mora());
+            }
+            accent_phrases.push(accent_phrase);
+        }
+    }
+
+    Ok(accent_phrases)
+}
+
+// ユーザー辞書の語の読み。accent_typeが0なら平板で、アクセント核を最後のモーラに置く
+pub(crate) fn parse_word(pronunciation: &str, accent_type: usize) -> Result<AccentPhraseModel> {
+    let mut accent_phrase = text_to_accent_phrase(pronunciation)?;
+    if accent_type != 0 {
+        accent_phrase.accent = accent_type;
+    }
+    Ok(accent_phrase)
+}
+
+pub(crate) fn pause_mora() -> MoraModel {
+    MoraModel {
+        text: "、".into(),
+        consonant: None,
+        consonant_length: None,
+        vowel: "pau".into(),
+        vowel_length: 0.,
+        pitch: 0.,
+        gain: None,
+    }
+}
+
+fn text_to_accent_phrase(phrase: &str) -> Result<AccentPhraseModel> {
+    let (phrase, is_interrogative) = match phrase.strip_suffix(INTERROGATION_MARKS) {
+        Some(phrase) => (phrase, true),
+        None => (phrase, false),
+    };
+
+    let chars: Vec<char> = phrase.chars().collect();
+    let mut moras: Vec<MoraModel> = Vec::with_capacity(chars.len());
+    let mut accent = None;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == ACCENT_SYMBOL {
+            if moras.is_empty() || accent.is_some() {
+                return Err(anyhow!("KanaParseError::InvalidAccent"));
+            }
+            accent = Some(moras.len());
+            i += 1;
+            continue;
+        }
+
+        if chars[i] == LONG_VOWEL_SYMBOL {
+            let vowel = moras
+                .last()
+                .map(|mora| mora.vowel.clone())
+                .context("KanaParseError::UnknownText")?;
+            let [text, _, _] = MORA_LIST_MINIMUM
+                .iter()
+                .find(|[_, consonant, mora_vowel]| consonant.is_empty() && *mora_vowel == vowel)
+                .context("KanaParseError::UnknownText")?;
+            moras.push(new_mora(text, "", &vowel));
+            i += 1;
+            continue;
+        }
+
+        // 拗音などの2文字のモーラを優先して照合する
+        let (len, [text, consonant, vowel]) = [2, 1]
+            .into_iter()
+            .filter(|len| i + len <= chars.len())
+            .find_map(|len| {
+                let text: String = chars[i..i + len].iter().collect();
+                MORA_LIST_MINIMUM
+                    .iter()
+                    .find(|[mora_text, _, _]| *mora_text == text)
+                    .map(|mora| (len, mora))
+            })
+            .ok_or_else(|| anyhow!("KanaParseError::UnknownText"))?;
+        moras.push(new_mora(text, consonant, vowel));
+        i += len;
+    }
+
+    if moras.is_empty() {
+        return Err(anyhow!("KanaParseError::EmptyPhrase"));
+    }
+    let accent = accent.unwrap_or(moras.len());
+
+    Ok(AccentPhraseModel {
+        moras,
+        accent,
+        pause_mora: None,
+        is_interrogative,
+    })
+}
+
+fn new_mora(text: &str, consonant: &str, vowel: &str) -> MoraModel {
+    let consonant = (!consonant.is_empty()).then(|| consonant.to_string());
+    MoraModel {
+        text: text.into(),
+        consonant_length: consonant.as_ref().map(|_| 0.),
+        consonant,
+        vowel: vowel.into(),
+        vowel_length: 0.,
+        pitch: 0.,
+        gain: None,
+    }
+}
+
+// parse_kanaの逆変換
+pub fn create_kana(accent_phrases: &[AccentPhraseModel]) -> String {
+    let mut text = String::new();
+    for (i, accent_phrase) in accent_phrases.iter().enumerate() {
+        for (j, mora) in accent_phrase.moras.iter().enumerate() {
+            text.push_str(&mora.text);
+            if j + 1 == accent_phrase.accent {
+                text.push(ACCENT_SYMBOL);
+            }
+        }
+        if accent_phrase.is_interrogative {
+            text.push(INTERROGATION_MARKS[1]);
+        }
+        if i != accent_phrases.len() - 1 {
+            if accent_phrase.pause_mora.is_some() {
+                text.push(PAUSE_DELIMITER);
+            } else {
+                text.push(ACCENT_PHRASE_DELIMITERS[0]);
+            }
+        }
+    }
+    text
+}
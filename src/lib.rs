@@ -0,0 +1,68 @@
+mod acoustic_feature_extractor;
+mod audio_buffer;
+mod audio_features;
+#[cfg(feature = "capi")]
+pub mod capi;
+// unstableが無効だと外からデバイスを選べず、CPUしか使われない
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+mod device;
+mod devoicing;
+pub mod flac;
+mod full_context_label;
+mod inference;
+mod kana_parser;
+mod metas;
+mod mora_list;
+#[cfg(feature = "ogg")]
+pub mod ogg;
+// unstableが無効だと外から設定できず、使われない
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+mod particle_softening;
+mod pitch_shift;
+pub mod prelude;
+mod prosody_smoothing;
+mod publish;
+mod rule_based;
+mod speaker_info;
+mod synthesis;
+// 合成の各段階を直接呼ぶための低水準なAPI。semverの対象外
+#[cfg(feature = "unstable")]
+pub mod synthesis_engine;
+#[cfg(not(feature = "unstable"))]
+mod synthesis_engine;
+pub mod test_model;
+mod threads;
+#[cfg(feature = "wasm")]
+mod tract;
+mod user_dict;
+mod validation;
+#[cfg(feature = "unstable")]
+mod voice_changer;
+mod warnings;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watermark;
+pub mod wav;
+
+pub use audio_buffer::AudioBuffer;
+pub use audio_features::AudioFeatures;
+#[cfg(feature = "unstable")]
+pub use device::Device;
+pub use inference::PaddingTrimError;
+pub use metas::{
+    load_metas, SpeakerMeta, StyleDefaults, StyleMeta, StyleType, TermsNotAcknowledgedError,
+    UnknownSpeakerError, UnsupportedStyleError, UsageTerms,
+};
+#[cfg(feature = "unstable")]
+pub use particle_softening::ParticleSoftening;
+pub use prosody_smoothing::ProsodySmoother;
+pub use publish::*;
+pub use speaker_info::{SpeakerInfo, StyleInfo};
+pub use synthesis::{Progress, Synthesis};
+pub use synthesis_engine::{
+    frame_count, AccentPhraseModel, AudioQueryModel, MoraModel, FRAME_RATE,
+};
+pub use threads::{available_cpus, default_worker_count};
+pub use user_dict::{UserDict, UserDictError, UserDictWord};
+pub use validation::{InvalidAccentPhraseError, InvalidAccentPhraseReason, ValidationMode};
+pub use warnings::{Warning, Warnings};
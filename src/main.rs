@@ -1,50 +1,204 @@
-mod acoustic_feature_extractor;
-mod full_context_label;
-mod inference;
-mod mora_list;
-mod synthesis_engine;
+mod commands;
 
-use anyhow::{anyhow, Result};
-use jpreprocess::{
-    kind::JPreprocessDictionaryKind, JPreprocess, JPreprocessConfig, SystemDictionaryConfig,
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+#[cfg(all(unix, feature = "server"))]
+use commands::ctl;
+#[cfg(unix)]
+use commands::daemon;
+#[cfg(feature = "server")]
+use commands::serve;
+#[cfg(feature = "unstable")]
+use commands::voice_change;
+use commands::{
+    ab, bench,
+    config::{self, Config},
+    errors::{self, ErrorFormat},
+    eval,
+    i18n::{self, Lang},
+    list_speakers, output, query, read, rerender, rpc, script, stats, synth, terms, watch,
+    watermark,
 };
-use ort::Session;
-use std::fs::File;
+use std::{path::PathBuf, process::ExitCode};
 
-const SAMPLING_RATE: u32 = 24000;
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Minimal VOICEVOX CORE text-to-speech",
+    after_help = errors::EXIT_CODES
+)]
+struct Cli {
+    /// Directory containing the ONNX models and metas.json
+    #[arg(
+        long,
+        global = true,
+        env = "CHIBIVOX_MODEL_DIR",
+        default_value = "model"
+    )]
+    model_dir: PathBuf,
+    /// How to print errors on stderr; the exit code tells the kind of error either way
+    #[arg(
+        long,
+        global = true,
+        env = "CHIBIVOX_ERROR_FORMAT",
+        value_enum,
+        default_value_t = ErrorFormat::Text
+    )]
+    error_format: ErrorFormat,
+    /// Print results (written files, durations, warnings, timings) as one JSON object per
+    /// line on stdout instead of human-oriented text; implies --error-format json
+    #[arg(long, global = true)]
+    json: bool,
+    /// Language of error and warning messages
+    #[arg(
+        long,
+        global = true,
+        env = "CHIBIVOX_LANG",
+        value_enum,
+        default_value_t = Lang::En
+    )]
+    lang: Lang,
+    /// Embed an inaudible watermark derived from this key into every written file, so it
+    /// can later be checked with `verify-watermark`
+    #[arg(long, global = true, env = watermark::WATERMARK_ENV, hide_env_values = true)]
+    watermark_key: Option<String>,
+    /// Use speakers whose metas.json terms do not allow commercial use, after reviewing
+    /// those terms
+    #[arg(long, global = true, env = "CHIBIVOX_ACKNOWLEDGE_TERMS")]
+    acknowledge_terms: bool,
+    /// Write the speaker's credit from metas.json into WAV output (LIST/INFO chunk)
+    #[arg(long, global = true)]
+    embed_credit: bool,
+    /// Save the optimized ONNX graphs next to the models (as dotfiles) and load those on
+    /// later starts, which skips most of the session build time for large models
+    #[arg(long, global = true, env = "CHIBIVOX_CACHE_OPTIMIZED_MODELS")]
+    cache_optimized_models: bool,
+    #[command(subcommand)]
+    command: Command,
+}
 
-fn main() -> Result<()> {
-    let text = std::env::args().nth(1).ok_or(anyhow!("invalid args"))?;
+#[derive(Subcommand)]
+enum Command {
+    /// Synthesize text into a WAV file
+    Synth(synth::Args),
+    /// Render a TSV/CSV script with per-row speaker, speed, pitch and output file
+    Script(script::Args),
+    /// Synthesize .txt files dropped into a directory, moving each aside when done
+    Watch(watch::Args),
+    /// Print the AudioQuery JSON for text without synthesizing
+    Query(query::Args),
+    /// Render the same text with two parameter sets for comparison
+    Ab(ab::Args),
+    /// Render a fixed sentence list for listening tests
+    Eval(eval::Args),
+    /// Read a long text file aloud sentence by sentence, resumable with --resume
+    Read(read::Args),
+    /// Re-synthesize sidecar AudioQueries with the current models
+    Rerender(rerender::Args),
+    /// Measure per-stage latency over sample sentences
+    Bench(bench::Args),
+    /// Re-speak a recording with another speaker, keeping its timing and intonation
+    /// (experimental, requires the `unstable` feature)
+    #[cfg(feature = "unstable")]
+    VoiceChange(voice_change::Args),
+    /// Check files for the watermark embedded with --watermark-key
+    VerifyWatermark(watermark::Args),
+    /// Show the local usage stats recorded while CHIBIVOX_STATS is set
+    Stats(stats::Args),
+    /// List the speakers and style ids available in the model
+    ListSpeakers(list_speakers::Args),
+    /// Keep the models loaded and answer synthesis requests over HTTP
+    #[cfg(feature = "server")]
+    Serve(serve::Args),
+    /// Change the defaults of a running `serve --control` without restarting it
+    #[cfg(all(unix, feature = "server"))]
+    Ctl(ctl::Args),
+    /// Keep the models loaded and answer length-prefixed text with PCM on a UNIX socket,
+    /// for local tools that want low latency without HTTP
+    #[cfg(unix)]
+    Daemon(daemon::Args),
+    /// Answer JSON-RPC (synthesize, audio_query, cancel) on standard input and output, for
+    /// editors that embed chibivox as a child process like a language server
+    Rpc(rpc::Args),
+}
 
-    // JPreprocess
-    let config = JPreprocessConfig {
-        dictionary: SystemDictionaryConfig::Bundled(JPreprocessDictionaryKind::NaistJdic),
-        user_dictionary: None,
+fn main() -> ExitCode {
+    let command = match command() {
+        Ok(command) => command,
+        Err(err) => {
+            let error_format = if errors::json_requested() {
+                ErrorFormat::Json
+            } else {
+                ErrorFormat::Text
+            };
+            return errors::report(&err, error_format);
+        }
     };
-    let jpreprocess = JPreprocess::from_config(config)?;
-    let labels = jpreprocess.extract_fullcontext(text.as_ref())?;
-
-    // Session生成
-    let predict_duration =
-        Session::builder()?.with_model_from_file("model/predict_duration-0.onnx")?;
-    let predict_intonation =
-        Session::builder()?.with_model_from_file("model/predict_intonation-0.onnx")?;
-    let decode = Session::builder()?.with_model_from_file("model/decode-0.onnx")?;
-
-    // AudioQuery生成
-    let accent_phrases = synthesis_engine::create_accent_phrases(labels)?;
-    let accent_phrases =
-        synthesis_engine::replace_phoneme_length(predict_duration, accent_phrases, 0)?;
-    let accent_phrases =
-        synthesis_engine::replace_mora_pitch(predict_intonation, accent_phrases, 0)?;
-
-    // 合成
-    let wav = synthesis_engine::synthesis(decode, accent_phrases, 1., 0., 1., 0.1, 0.1, true, 0)?;
+    let cli = match command.try_get_matches() {
+        Ok(matches) => Cli::from_arg_matches(&matches),
+        Err(err) => Err(err),
+    };
+    let cli = match cli {
+        Ok(cli) => cli,
+        // --helpや--versionもここに来るので、それらはclapに任せる
+        Err(err) if err.use_stderr() && errors::json_requested() => {
+            return errors::report_argument_error(&err);
+        }
+        Err(err) => err.exit(),
+    };
+    let error_format = if cli.json {
+        ErrorFormat::Json
+    } else {
+        cli.error_format
+    };
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => errors::report(&err, error_format),
+    }
+}
 
-    // 保存
-    let head = wav_io::new_header(SAMPLING_RATE, 32, true, true);
-    let mut file = File::create("audio.wav")?;
-    wav_io::write_to_file(&mut file, &head, &wav).map_err(|_| anyhow!("wav output error"))?;
+// 設定ファイルの値を既定値にする
+// 読めない場合に設定ファイル無しで続けると、意図しない設定で合成してしまうので止める
+fn command() -> anyhow::Result<clap::Command> {
+    let command = Cli::command();
+    let Some(path) = config::path() else {
+        return Ok(command);
+    };
+    Ok(match Config::load(&path)? {
+        Some(config) => config.apply(command),
+        None => command,
+    })
+}
 
-    Ok(())
+fn run(cli: Cli) -> anyhow::Result<()> {
+    i18n::set_lang(cli.lang);
+    output::set_json(cli.json);
+    if let Some(key) = cli.watermark_key {
+        watermark::set_key(key);
+    }
+    terms::set(cli.acknowledge_terms, cli.embed_credit);
+    commands::set_cache_optimized_models(cli.cache_optimized_models);
+    let model_dir = &cli.model_dir;
+    match cli.command {
+        Command::Synth(args) => synth::run(args, model_dir),
+        Command::Script(args) => script::run(args, model_dir),
+        Command::Watch(args) => watch::run(args, model_dir),
+        Command::Query(args) => query::run(args, model_dir),
+        Command::Ab(args) => ab::run(args, model_dir),
+        Command::Eval(args) => eval::run(args, model_dir),
+        Command::Read(args) => read::run(args, model_dir),
+        Command::Rerender(args) => rerender::run(args, model_dir),
+        Command::Bench(args) => bench::run(args, model_dir),
+        #[cfg(feature = "unstable")]
+        Command::VoiceChange(args) => voice_change::run(args, model_dir),
+        Command::VerifyWatermark(args) => watermark::run(args),
+        Command::Stats(args) => stats::run(args, model_dir),
+        Command::ListSpeakers(args) => list_speakers::run(args, model_dir),
+        #[cfg(feature = "server")]
+        Command::Serve(args) => serve::run(args, model_dir),
+        #[cfg(all(unix, feature = "server"))]
+        Command::Ctl(args) => ctl::run(args),
+        #[cfg(unix)]
+        Command::Daemon(args) => daemon::run(args, model_dir),
+        Command::Rpc(args) => rpc::run(args, model_dir),
+    }
 }
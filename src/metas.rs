@@ -0,0 +1,197 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SpeakerMeta {
+    pub name: String,
+    pub styles: Vec<StyleMeta>,
+    pub speaker_uuid: String,
+    pub version: String,
+    // chibivox独自の拡張。配布元が定めた利用規約のうち、機械的に扱えるもの
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms: Option<UsageTerms>,
+}
+
+// 話者ごとの利用規約
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UsageTerms {
+    // 音声に添えるクレジット表記。「VOICEVOX:ずんだもん」など
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit: Option<String>,
+    // falseなら商用利用を認めていない。使うには規約への同意(acknowledge_terms)が要る
+    #[serde(default = "default_commercial")]
+    pub commercial: bool,
+}
+
+impl Default for UsageTerms {
+    fn default() -> Self {
+        Self {
+            credit: None,
+            commercial: default_commercial(),
+        }
+    }
+}
+
+fn default_commercial() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StyleMeta {
+    pub name: String,
+    pub id: u32,
+    #[serde(rename = "type", default)]
+    pub style_type: StyleType,
+    // chibivox独自の拡張。学習の都合で遅い・小さい声などを、既定値の側で補正する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<StyleDefaults>,
+}
+
+// 利用者が値を指定しなかったときに使う、スタイルごとの既定値
+// 指定が無い項目はSynthesisOptionsの既定値のまま
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StyleDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intonation_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_scale: Option<f32>,
+}
+
+// VOICEVOXのスタイルの種類。singing_teacherはハミング用
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum StyleType {
+    #[default]
+    Talk,
+    SingingTeacher,
+    FrameDecode,
+    Sing,
+}
+
+#[derive(Debug)]
+pub struct UnsupportedStyleError {
+    pub speaker_id: u32,
+    pub style_type: StyleType,
+    pub supported_speaker_ids: Vec<u32>,
+}
+
+impl fmt::Display for UnsupportedStyleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "style {} is of type {:?} and cannot be used for talk synthesis (talk styles: {:?})",
+            self.speaker_id, self.style_type, self.supported_speaker_ids
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedStyleError {}
+
+// metas.jsonに無いスタイルid
+#[derive(Debug)]
+pub struct UnknownSpeakerError {
+    pub speaker_id: u32,
+    pub available_speaker_ids: Vec<u32>,
+}
+
+impl fmt::Display for UnknownSpeakerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown speaker id {} (available: {:?})",
+            self.speaker_id, self.available_speaker_ids
+        )
+    }
+}
+
+impl std::error::Error for UnknownSpeakerError {}
+
+#[derive(Debug)]
+pub struct TermsNotAcknowledgedError {
+    pub speaker_id: u32,
+    pub speaker_name: String,
+}
+
+impl fmt::Display for TermsNotAcknowledgedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "speaker {:?} (style {}) is not licensed for commercial use; review its terms and acknowledge them to use it",
+            self.speaker_name, self.speaker_id
+        )
+    }
+}
+
+impl std::error::Error for TermsNotAcknowledgedError {}
+
+// metas.jsonが無いモデルもあるので、その場合は空として扱う
+pub fn load_metas(path: &Path) -> Result<Vec<SpeakerMeta>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn check_talk_style(metas: &[SpeakerMeta], speaker_id: u32) -> Result<()> {
+    let styles = metas.iter().flat_map(|speaker| &speaker.styles);
+    let Some(style) = styles.clone().find(|style| style.id == speaker_id) else {
+        // metas.jsonが無ければ範囲が分からないので、モデルに任せる
+        if metas.is_empty() {
+            return Ok(());
+        }
+        return Err(UnknownSpeakerError {
+            speaker_id,
+            available_speaker_ids: styles.map(|style| style.id).collect(),
+        }
+        .into());
+    };
+    if style.style_type == StyleType::Talk {
+        return Ok(());
+    }
+
+    Err(UnsupportedStyleError {
+        speaker_id,
+        style_type: style.style_type,
+        supported_speaker_ids: styles
+            .filter(|style| style.style_type == StyleType::Talk)
+            .map(|style| style.id)
+            .collect(),
+    }
+    .into())
+}
+
+// スタイルidが属する話者の利用規約
+pub fn usage_terms(metas: &[SpeakerMeta], speaker_id: u32) -> Option<&UsageTerms> {
+    find_speaker(metas, speaker_id)?.terms.as_ref()
+}
+
+// 商用利用を認めていない話者は、規約に同意した場合だけ使える
+pub fn check_terms(metas: &[SpeakerMeta], speaker_id: u32, acknowledged: bool) -> Result<()> {
+    let Some(speaker) = find_speaker(metas, speaker_id) else {
+        return Ok(());
+    };
+    match &speaker.terms {
+        Some(terms) if !terms.commercial && !acknowledged => Err(TermsNotAcknowledgedError {
+            speaker_id,
+            speaker_name: speaker.name.clone(),
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn find_speaker(metas: &[SpeakerMeta], speaker_id: u32) -> Option<&SpeakerMeta> {
+    metas
+        .iter()
+        .find(|speaker| speaker.styles.iter().any(|style| style.id == speaker_id))
+}
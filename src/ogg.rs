@@ -0,0 +1,32 @@
+// Ogg Vorbisの書き出し
+// エンコーダはlibvorbis(vorbis_rs)に任せる
+
+use anyhow::{anyhow, Result};
+use std::num::{NonZeroU32, NonZeroU8};
+use vorbis_rs::VorbisEncoderBuilder;
+
+pub fn encode_ogg(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    let channels = channels.max(1) as usize;
+    // libvorbisはチャンネルごとに分かれたサンプルを受け取る
+    let planar: Vec<Vec<f32>> = (0..channels)
+        .map(|channel| {
+            samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect()
+        })
+        .collect();
+
+    let mut ogg = Vec::new();
+    let mut encoder = VorbisEncoderBuilder::new(
+        NonZeroU32::new(sample_rate).ok_or(anyhow!("sampling rate must not be 0"))?,
+        NonZeroU8::new(channels as u8).ok_or(anyhow!("too many channels: {channels}"))?,
+        &mut ogg,
+    )?
+    .build()?;
+    encoder.encode_audio_block(&planar)?;
+    encoder.finish()?;
+    Ok(ogg)
+}
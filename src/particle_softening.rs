@@ -0,0 +1,63 @@
+use crate::synthesis_engine::{AccentPhraseModel, FRAME_RATE};
+
+const DEFAULT_PARTICLES: &[&str] = &["ネ", "ヨ", "カ"];
+const FADE_SIZE: usize = 256;
+
+#[derive(Clone)]
+pub struct ParticleSoftening {
+    pub particles: Vec<String>,
+    pub length_scale: f32,
+    pub volume_scale: f32,
+}
+
+impl Default for ParticleSoftening {
+    fn default() -> Self {
+        Self {
+            particles: DEFAULT_PARTICLES.iter().map(|s| s.to_string()).collect(),
+            length_scale: 0.8,
+            volume_scale: 0.7,
+        }
+    }
+}
+
+impl ParticleSoftening {
+    // 文末の助詞モーラを縮め、そのモーラが占めるフレーム数を返す
+    pub fn apply(
+        &self,
+        accent_phrases: &mut [AccentPhraseModel],
+        speed_scale: f32,
+    ) -> Option<usize> {
+        let accent_phrase = accent_phrases.last_mut()?;
+        // 疑問文は語尾上げのモーラが後ろに付くので対象外
+        if accent_phrase.is_interrogative {
+            return None;
+        }
+        let mora = accent_phrase.moras.last_mut()?;
+        if !self.particles.iter().any(|particle| *particle == mora.text) {
+            return None;
+        }
+
+        mora.consonant_length = mora
+            .consonant_length
+            .map(|consonant_length| consonant_length * self.length_scale);
+        mora.vowel_length *= self.length_scale;
+
+        let frames = mora
+            .consonant_length
+            .into_iter()
+            .chain(std::iter::once(mora.vowel_length))
+            .map(|length| (length * FRAME_RATE / speed_scale).ceil() as usize)
+            .sum();
+        Some(frames)
+    }
+
+    // applyで得たフレーム数をもとに、末尾の無音の直前にある助詞の音量を下げる
+    pub fn apply_volume(&self, wave: &mut [f32], mora_frames: usize, post_frames: usize) {
+        let end = wave.len().saturating_sub(post_frames * 256);
+        let start = end.saturating_sub(mora_frames * 256);
+        for (i, sample) in wave[start..end].iter_mut().enumerate() {
+            let fade = (i as f32 / FADE_SIZE as f32).min(1.);
+            *sample *= 1. - (1. - self.volume_scale) * fade;
+        }
+    }
+}
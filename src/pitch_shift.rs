@@ -0,0 +1,102 @@
+// フォルマントを保ったまま波形の音高をずらす(TD-PSOLA)
+// 1周期分ずつ切り出した波形を伸び縮みさせずに間隔だけ変えて並べ直すので、
+// 声道の響きはそのままで基本周波数だけが変わる
+
+use std::f32::consts::PI;
+
+const HOP_SIZE: usize = 256;
+const FRAME_SIZE: usize = 1024;
+const F0_MIN: f32 = 60.;
+const F0_MAX: f32 = 500.;
+const VOICED_THRESHOLD: f32 = 0.5;
+const SILENCE_THRESHOLD: f32 = 1e-4;
+// 無声区間は音高が無いので、この間隔のまま並べ直す
+const UNVOICED_PERIOD: f32 = 0.01;
+
+pub fn shift_pitch(wave: &[f32], sampling_rate: u32, semitones: f32) -> Vec<f32> {
+    if semitones == 0. || wave.is_empty() {
+        return wave.to_vec();
+    }
+    let ratio = 2f32.powf(semitones / 12.);
+    let periods = periods(wave, sampling_rate);
+    let unvoiced_period = (UNVOICED_PERIOD * sampling_rate as f32) as usize;
+    let period_at = |position: usize| periods.get(position / HOP_SIZE).copied().flatten();
+
+    // 分析点は元の波形の1周期ごと
+    let mut marks = Vec::new();
+    let mut position = 0;
+    while position < wave.len() {
+        marks.push(position);
+        position += period_at(position).unwrap_or(unvoiced_period);
+    }
+
+    // 合成点は有声区間でだけ間隔を1/ratioにし、それぞれ時刻の最も近い分析点の波形を置く
+    let mut output = vec![0.; wave.len()];
+    let mut weight = vec![0.; wave.len()];
+    let mut position = 0.;
+    while (position as usize) < wave.len() {
+        let center = position as usize;
+        let (period, step) = match period_at(center) {
+            Some(period) => (period, period as f32 / ratio),
+            None => (unvoiced_period, unvoiced_period as f32),
+        };
+        let mark = nearest(&marks, center);
+        for k in 0..2 * period {
+            let (Some(source), Some(target)) = (
+                (mark + k).checked_sub(period),
+                (center + k).checked_sub(period),
+            ) else {
+                continue;
+            };
+            if source >= wave.len() || target >= wave.len() {
+                continue;
+            }
+            let window = 0.5 - 0.5 * (PI * k as f32 / period as f32).cos();
+            output[target] += wave[source] * window;
+            weight[target] += window;
+        }
+        position += step;
+    }
+
+    // 音高を上げると窓が重なって大きくなるので、その分だけ戻す
+    output
+        .iter()
+        .zip(&weight)
+        .map(|(sample, weight)| sample / weight.max(1.))
+        .collect()
+}
+
+// HOP_SIZEごとの周期(サンプル数)。無声や無音ならNone
+pub(crate) fn periods(wave: &[f32], sampling_rate: u32) -> Vec<Option<usize>> {
+    let min_lag = (sampling_rate as f32 / F0_MAX) as usize;
+    let max_lag = (sampling_rate as f32 / F0_MIN) as usize;
+    (0..wave.len().div_ceil(HOP_SIZE))
+        .map(|i| {
+            let start = (i * HOP_SIZE).saturating_sub(FRAME_SIZE / 2);
+            let frame = &wave[start..(start + FRAME_SIZE).min(wave.len())];
+            let energy: f32 = frame.iter().map(|sample| sample * sample).sum();
+            if frame.len() <= max_lag || energy / (frame.len() as f32) < SILENCE_THRESHOLD {
+                return None;
+            }
+
+            let (lag, correlation) = (min_lag..=max_lag)
+                .map(|lag| {
+                    let correlation: f32 =
+                        frame.iter().zip(&frame[lag..]).map(|(a, b)| a * b).sum();
+                    (lag, correlation / energy)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+            (correlation > VOICED_THRESHOLD).then_some(lag)
+        })
+        .collect()
+}
+
+fn nearest(marks: &[usize], position: usize) -> usize {
+    let i = marks.partition_point(|&mark| mark < position);
+    match (i.checked_sub(1).map(|i| marks[i]), marks.get(i)) {
+        (Some(before), Some(&after)) if position - before < after - position => before,
+        (_, Some(&after)) => after,
+        (Some(before), None) => before,
+        (None, None) => position,
+    }
+}
@@ -0,0 +1,6 @@
+// よく使うものをまとめて `use chibivox::prelude::*;` で読み込めるようにする
+
+pub use crate::{
+    AccentPhraseModel, AudioBuffer, AudioQueryModel, InitializeOptions, MoraModel, SpeakerMeta,
+    StyleMeta, StyleType, SynthesisOptions, VoicevoxCore, SAMPLING_RATE,
+};
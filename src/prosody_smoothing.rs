@@ -0,0 +1,79 @@
+use crate::{AudioQueryModel, MoraModel};
+
+// 文ごとに分けて合成した音声をつなぐと、文頭ごとに声の高さと話す速さが予測し直されて揃わない
+// 前までの文の平均に、次の文の平均をstrengthの割合だけ寄せる。抑揚やモーラごとの長さの比は変えない
+pub struct ProsodySmoother {
+    strength: f32,
+    // これまでの文の、有声モーラの平均音高(log f0)と平均モーラ長
+    pitch: Option<f32>,
+    mora_length: Option<f32>,
+}
+
+// 直前の文ほど重く見る。1なら直前の文だけに合わせる
+const DECAY: f32 = 0.5;
+
+impl ProsodySmoother {
+    // strengthは0(何もしない)から1(前までの平均にそのまま揃える)
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength: strength.clamp(0., 1.),
+            pitch: None,
+            mora_length: None,
+        }
+    }
+
+    // replace_mora_dataの後、合成の前に入力の順に呼ぶ
+    pub fn apply(&mut self, audio_query: &mut AudioQueryModel) {
+        let moras = || {
+            audio_query
+                .accent_phrases
+                .iter()
+                .flat_map(|accent_phrase| &accent_phrase.moras)
+        };
+        let pitch = mean(moras().map(|mora| mora.pitch).filter(|pitch| *pitch > 0.));
+        let mora_length = mean(moras().map(mora_length).filter(|length| *length > 0.));
+
+        let pitch_delta = match (pitch, self.pitch) {
+            (Some(pitch), Some(reference)) => (reference - pitch) * self.strength,
+            _ => 0.,
+        };
+        let length_ratio = match (mora_length, self.mora_length) {
+            (Some(mora_length), Some(reference)) => (reference / mora_length).powf(self.strength),
+            _ => 1.,
+        };
+        for mora in audio_query
+            .accent_phrases
+            .iter_mut()
+            .flat_map(|accent_phrase| &mut accent_phrase.moras)
+        {
+            if mora.pitch > 0. {
+                mora.pitch += pitch_delta;
+            }
+            if let Some(consonant_length) = &mut mora.consonant_length {
+                *consonant_length *= length_ratio;
+            }
+            mora.vowel_length *= length_ratio;
+        }
+
+        // 寄せた後の、実際に鳴らす値で覚える
+        if let Some(pitch) = pitch {
+            self.pitch = Some(update(self.pitch, pitch + pitch_delta));
+        }
+        if let Some(mora_length) = mora_length {
+            self.mora_length = Some(update(self.mora_length, mora_length * length_ratio));
+        }
+    }
+}
+
+fn mora_length(mora: &MoraModel) -> f32 {
+    mora.consonant_length.unwrap_or(0.) + mora.vowel_length
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0., 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+fn update(reference: Option<f32>, value: f32) -> f32 {
+    reference.map_or(value, |reference| reference + (value - reference) * DECAY)
+}
@@ -0,0 +1,888 @@
+#[cfg(feature = "unstable")]
+use crate::voice_changer;
+use crate::{
+    acoustic_feature_extractor::OjtPhoneme,
+    audio_buffer::AudioBuffer,
+    device::{Device, SessionPool},
+    devoicing,
+    inference::{self, Session},
+    kana_parser,
+    metas::{self, SpeakerMeta, UsageTerms},
+    particle_softening::ParticleSoftening,
+    rule_based,
+    synthesis_engine::{self, AccentPhraseModel, AudioQueryModel, FRAME_RATE},
+    threads,
+    user_dict::{Segment, UserDict},
+    validation,
+    warnings::{Warning, Warnings},
+};
+use anyhow::{anyhow, bail, Result};
+use jpreprocess::{
+    kind::JPreprocessDictionaryKind, JPreprocess, JPreprocessConfig, SystemDictionaryConfig,
+};
+#[cfg(not(feature = "wasm"))]
+use ort::{CUDAExecutionProvider, GraphOptimizationLevel, SessionBuilder};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+pub const SAMPLING_RATE: u32 = 24000;
+// リンクしたONNX RuntimeのC APIの版。1.16なら16
+#[cfg(not(feature = "wasm"))]
+pub const ONNXRUNTIME_API_VERSION: u32 = ort::sys::ORT_API_VERSION;
+
+const PREDICT_DURATION_MODEL: &str = "predict_duration-0.onnx";
+const PREDICT_INTONATION_MODEL: &str = "predict_intonation-0.onnx";
+const DECODE_MODEL: &str = "decode-0.onnx";
+
+#[derive(Clone)]
+pub struct SynthesisOptions {
+    pub speed_scale: f32,
+    pub pitch_scale: f32,
+    pub intonation_scale: f32,
+    pub volume_scale: f32,
+    pub pre_phoneme_length: f32,
+    pub post_phoneme_length: f32,
+    pub enable_interrogative_upspeak: bool,
+}
+
+impl Default for SynthesisOptions {
+    fn default() -> Self {
+        Self {
+            speed_scale: 1.,
+            pitch_scale: 0.,
+            intonation_scale: 1.,
+            volume_scale: 1.,
+            pre_phoneme_length: 0.1,
+            post_phoneme_length: 0.1,
+            enable_interrogative_upspeak: true,
+        }
+    }
+}
+
+// 合成の途中の値。文がおかしく読まれた原因を調べるために書き出す
+#[derive(Clone, Debug, Serialize)]
+pub struct Intermediates {
+    // jpreprocessのフルコンテキストラベル
+    pub labels: Vec<String>,
+    // 前後の無音(pau)を含む音素列と、その音素ID
+    pub phonemes: Vec<String>,
+    pub phoneme_ids: Vec<i64>,
+    // 音素ごとの長さ(秒)。speed_scaleを掛ける前
+    pub phoneme_lengths: Vec<f32>,
+    // decoderに渡すフレームごとのf0
+    pub f0: Vec<f32>,
+}
+
+#[derive(Clone)]
+pub struct InitializeOptions {
+    // スレッド数を固定して並列実行と最適化を切り、同じ入力から常に同じ波形が得られるようにする
+    pub deterministic: bool,
+    // ORTのintra-opスレッド数。0なら使えるCPU数に合わせる
+    pub cpu_num_threads: usize,
+    // 読み込むONNX Runtimeのライブラリ。load-dynamicが有効な場合のみ使われる
+    // Noneならort既定の探索(ORT_DYLIB_PATHなど)に従う
+    pub ort_library_path: Option<PathBuf>,
+    // decodeの前後に足して後で切り落とす無音(pau)の秒数。モデルによっては不要
+    pub decode_padding: f32,
+    // metas.jsonで商用利用を認めていない話者を、規約に同意したうえで使う
+    pub acknowledge_terms: bool,
+    // CPUのセッションについて、最適化したグラフをモデルの隣に書き出し、次からはそれを読み込む
+    // 大きなdecodeモデルほど、起動のたびの最適化にかかる時間が省ける
+    pub cache_optimized_models: bool,
+}
+
+impl Default for InitializeOptions {
+    fn default() -> Self {
+        Self {
+            deterministic: false,
+            cpu_num_threads: 0,
+            ort_library_path: None,
+            decode_padding: 0.4,
+            acknowledge_terms: false,
+            cache_optimized_models: false,
+        }
+    }
+}
+
+impl InitializeOptions {
+    pub fn intra_threads(&self) -> usize {
+        if self.deterministic {
+            1
+        } else if self.cpu_num_threads == 0 {
+            threads::available_cpus()
+        } else {
+            self.cpu_num_threads
+        }
+    }
+}
+
+pub struct VoicevoxCore {
+    jpreprocess: JPreprocess,
+    // 無ければ長さや音高を規則で代用する
+    predict_duration: Option<Session>,
+    predict_intonation: Option<Session>,
+    decode: SessionPool,
+    metas: Vec<SpeakerMeta>,
+    particle_softening: HashMap<u32, ParticleSoftening>,
+    enable_devoicing: bool,
+    decode_padding: f32,
+    acknowledge_terms: bool,
+    // 合成しながら差し替えられるように、&selfで書き換える
+    user_dict: RwLock<UserDict>,
+}
+
+impl VoicevoxCore {
+    pub fn new(model_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::new_with_options(model_dir, &InitializeOptions::default())
+    }
+
+    pub fn new_with_options(
+        model_dir: impl AsRef<Path>,
+        options: &InitializeOptions,
+    ) -> Result<Self> {
+        Self::new_impl(model_dir.as_ref(), options, &[Device::Cpu])
+    }
+
+    // decodeセッションをデバイスごとに作り、空いているものに振り分ける
+    // 予測モデルは軽いので、CPUに一つだけ置く
+    #[cfg(feature = "unstable")]
+    pub fn new_with_decode_devices(
+        model_dir: impl AsRef<Path>,
+        options: &InitializeOptions,
+        decode_devices: &[Device],
+    ) -> Result<Self> {
+        Self::new_impl(model_dir.as_ref(), options, decode_devices)
+    }
+
+    fn new_impl(
+        model_dir: &Path,
+        options: &InitializeOptions,
+        decode_devices: &[Device],
+    ) -> Result<Self> {
+        if decode_devices.is_empty() {
+            bail!("no decode devices");
+        }
+        if !options.decode_padding.is_finite() || options.decode_padding < 0. {
+            bail!("invalid decode padding: {}", options.decode_padding);
+        }
+
+        #[cfg(all(feature = "load-dynamic", not(feature = "wasm")))]
+        if let Some(ort_library_path) = &options.ort_library_path {
+            ort::init_from(ort_library_path.to_string_lossy()).commit()?;
+        }
+
+        // Session生成
+        // ORTのエラーでは分かりにくいので、足りないファイルを先に報告する
+        // 予測モデルは無くても品質を落として動かせるが、decodeだけは代わりが無い
+        if !model_dir.join(DECODE_MODEL).exists() {
+            bail!(
+                "missing model file in {}: {DECODE_MODEL}",
+                model_dir.display()
+            );
+        }
+        let predict_duration = build_optional_session(model_dir, PREDICT_DURATION_MODEL, options)?;
+        let predict_intonation =
+            build_optional_session(model_dir, PREDICT_INTONATION_MODEL, options)?;
+        let decode = decode_devices
+            .iter()
+            .map(|device| build_session(&model_dir.join(DECODE_MODEL), options, *device))
+            .collect::<Result<_>>()?;
+        let decode = SessionPool::new(decode);
+
+        let metas = metas::load_metas(&model_dir.join("metas.json"))?;
+
+        Self::from_sessions(predict_duration, predict_intonation, decode, metas, options)
+    }
+
+    // ファイルを読まずに、読み込み済みのモデルとmetas.jsonの中身から作る。ブラウザではこちらを使う
+    #[cfg(feature = "wasm")]
+    pub fn from_bytes(
+        decode: &[u8],
+        predict_duration: Option<&[u8]>,
+        predict_intonation: Option<&[u8]>,
+        metas_json: &str,
+        options: &InitializeOptions,
+    ) -> Result<Self> {
+        if !options.decode_padding.is_finite() || options.decode_padding < 0. {
+            bail!("invalid decode padding: {}", options.decode_padding);
+        }
+        let predict_duration = predict_duration.map(Session::from_bytes).transpose()?;
+        let predict_intonation = predict_intonation.map(Session::from_bytes).transpose()?;
+        let decode = SessionPool::new(vec![Session::from_bytes(decode)?]);
+        let metas = serde_json::from_str(metas_json)?;
+        Self::from_sessions(predict_duration, predict_intonation, decode, metas, options)
+    }
+
+    fn from_sessions(
+        predict_duration: Option<Session>,
+        predict_intonation: Option<Session>,
+        decode: SessionPool,
+        metas: Vec<SpeakerMeta>,
+        options: &InitializeOptions,
+    ) -> Result<Self> {
+        // JPreprocess
+        let config = JPreprocessConfig {
+            dictionary: SystemDictionaryConfig::Bundled(JPreprocessDictionaryKind::NaistJdic),
+            user_dictionary: None,
+        };
+        let jpreprocess = JPreprocess::from_config(config)?;
+
+        Ok(Self {
+            jpreprocess,
+            predict_duration,
+            predict_intonation,
+            decode,
+            metas,
+            particle_softening: HashMap::new(),
+            enable_devoicing: false,
+            decode_padding: options.decode_padding,
+            acknowledge_terms: options.acknowledge_terms,
+            user_dict: RwLock::new(UserDict::new()),
+        })
+    }
+
+    // 以後のテキスト解析で使うユーザー辞書。合成中のものは、差し替える前の辞書のまま終わる
+    pub fn set_user_dict(&self, user_dict: UserDict) {
+        *self
+            .user_dict
+            .write()
+            .unwrap_or_else(|err| err.into_inner()) = user_dict;
+    }
+
+    pub fn metas(&self) -> &[SpeakerMeta] {
+        &self.metas
+    }
+
+    // スタイルidが属する話者の利用規約。metas.jsonに無ければNone
+    pub fn usage_terms(&self, speaker_id: u32) -> Option<&UsageTerms> {
+        metas::usage_terms(&self.metas, speaker_id)
+    }
+
+    // 会話に使えるスタイルで、利用規約にも同意しているか
+    fn check_speaker(&self, speaker_id: u32) -> Result<()> {
+        metas::check_talk_style(&self.metas, speaker_id)?;
+        metas::check_terms(&self.metas, speaker_id, self.acknowledge_terms)
+    }
+
+    // 音素長予測モデルが無く、長さを規則で代用しているか
+    pub fn is_rule_based_duration(&self) -> bool {
+        self.predict_duration.is_none()
+    }
+
+    // 抑揚予測モデルが無く、音高を規則で代用しているか
+    pub fn is_rule_based_intonation(&self) -> bool {
+        self.predict_intonation.is_none()
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_particle_softening(
+        &mut self,
+        speaker_id: u32,
+        particle_softening: Option<ParticleSoftening>,
+    ) {
+        match particle_softening {
+            Some(particle_softening) => {
+                self.particle_softening
+                    .insert(speaker_id, particle_softening);
+            }
+            None => {
+                self.particle_softening.remove(&speaker_id);
+            }
+        }
+    }
+
+    // metas.jsonにスタイルごとの既定値があれば、それで上書きしたSynthesisOptions
+    // _with_optionsで渡された値はそのまま使われるので、利用者の指定が常に優先される
+    pub fn default_options(&self, speaker_id: u32) -> SynthesisOptions {
+        let mut options = SynthesisOptions::default();
+        let defaults = self
+            .metas
+            .iter()
+            .flat_map(|speaker| &speaker.styles)
+            .find(|style| style.id == speaker_id)
+            .and_then(|style| style.defaults.as_ref());
+        if let Some(defaults) = defaults {
+            options.speed_scale = defaults.speed_scale.unwrap_or(options.speed_scale);
+            options.pitch_scale = defaults.pitch_scale.unwrap_or(options.pitch_scale);
+            options.intonation_scale = defaults
+                .intonation_scale
+                .unwrap_or(options.intonation_scale);
+            options.volume_scale = defaults.volume_scale.unwrap_or(options.volume_scale);
+        }
+        options
+    }
+
+    pub fn set_devoicing(&mut self, enable_devoicing: bool) {
+        self.enable_devoicing = enable_devoicing;
+    }
+
+    pub fn audio_query(&self, text: &str, speaker_id: u32) -> Result<AudioQueryModel> {
+        self.audio_query_with_options(text, speaker_id, &self.default_options(speaker_id))
+    }
+
+    pub fn audio_query_with_options(
+        &self,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Result<AudioQueryModel> {
+        let accent_phrases = self.create_accent_phrases(text)?;
+        let accent_phrases = self.replace_mora_data(accent_phrases, speaker_id)?;
+        Ok(self.audio_query_from_accent_phrases(accent_phrases, options))
+    }
+
+    // audio_query_with_optionsに加えて、途中で積んだ警告も返す
+    pub fn audio_query_with_warnings(
+        &self,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Result<(AudioQueryModel, Warnings)> {
+        let mut warnings = Warnings::new();
+        let accent_phrases = self.create_accent_phrases_with_warnings(text, &mut warnings)?;
+        let accent_phrases =
+            self.replace_mora_data_with_warnings(accent_phrases, speaker_id, &mut warnings)?;
+        Ok((
+            self.audio_query_from_accent_phrases(accent_phrases, options),
+            warnings,
+        ))
+    }
+
+    // replace_mora_dataなどで長さと音高を埋めたアクセント句から、AudioQueryを組み立てる
+    pub fn audio_query_from_accent_phrases(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        options: &SynthesisOptions,
+    ) -> AudioQueryModel {
+        AudioQueryModel {
+            kana: Some(kana_parser::create_kana(&accent_phrases)),
+            accent_phrases,
+            speed_scale: options.speed_scale,
+            pitch_scale: options.pitch_scale,
+            intonation_scale: options.intonation_scale,
+            volume_scale: options.volume_scale,
+            pre_phoneme_length: options.pre_phoneme_length,
+            post_phoneme_length: options.post_phoneme_length,
+            output_sampling_rate: SAMPLING_RATE,
+            output_stereo: false,
+        }
+    }
+
+    // アクセント句を一つだけ編集した場合に、その句だけ予測し直して差し替える
+    pub fn update_length_for_phrase(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        index: usize,
+        speaker_id: u32,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        update_phrase(accent_phrases, index, |accent_phrases| {
+            self.replace_phoneme_length(accent_phrases, speaker_id)
+        })
+    }
+
+    pub fn update_mora_pitch_for_phrase(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        index: usize,
+        speaker_id: u32,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        update_phrase(accent_phrases, index, |accent_phrases| {
+            self.replace_mora_pitch(accent_phrases, speaker_id)
+        })
+    }
+
+    // durationやf0を予測し直さず、AudioQueryの値をそのまま使って合成する
+    pub fn synthesis(&self, audio_query: &AudioQueryModel, speaker_id: u32) -> Result<AudioBuffer> {
+        self.synthesis_impl(audio_query, speaker_id, true)
+    }
+
+    // 疑問文の語尾上げも含めて一切手を加えず、AudioQueryの長さと音高だけを使ってdecodeする
+    pub fn synthesis_decode_only(
+        &self,
+        audio_query: &AudioQueryModel,
+        speaker_id: u32,
+    ) -> Result<AudioBuffer> {
+        audio_query.validate()?;
+        self.synthesis_impl(audio_query, speaker_id, false)
+    }
+
+    pub(crate) fn synthesis_impl(
+        &self,
+        audio_query: &AudioQueryModel,
+        speaker_id: u32,
+        enable_interrogative_upspeak: bool,
+    ) -> Result<AudioBuffer> {
+        self.check_speaker(speaker_id)?;
+        let wave = synthesis_engine::synthesis(
+            &self.decode.acquire(),
+            audio_query.accent_phrases.clone(),
+            audio_query.speed_scale,
+            audio_query.pitch_scale,
+            audio_query.intonation_scale,
+            audio_query.pre_phoneme_length,
+            audio_query.post_phoneme_length,
+            enable_interrogative_upspeak,
+            self.decode_padding,
+            speaker_id,
+        )?;
+
+        let wave = wave
+            .into_iter()
+            .map(|sample| sample * audio_query.volume_scale)
+            .collect();
+
+        // AudioQueryで指定された出力形式に合わせる
+        let audio =
+            AudioBuffer::mono(wave, SAMPLING_RATE).resample(audio_query.output_sampling_rate);
+        Ok(if audio_query.output_stereo {
+            audio.to_stereo()
+        } else {
+            audio
+        })
+    }
+
+    // フレームごとの音素IDとf0から直接decodeする
+    pub fn decode(&self, phoneme_ids: &[i64], f0: &[f32], speaker_id: u32) -> Result<AudioBuffer> {
+        self.check_speaker(speaker_id)?;
+        if phoneme_ids.len() != f0.len() {
+            bail!(
+                "length mismatch: {} phoneme ids, {} f0 frames",
+                phoneme_ids.len(),
+                f0.len()
+            );
+        }
+
+        let phoneme_size = OjtPhoneme::num_phoneme();
+        let mut phoneme = vec![0.; phoneme_ids.len() * phoneme_size];
+        for (i, phoneme_id) in phoneme_ids.iter().enumerate() {
+            if !(0..phoneme_size as i64).contains(phoneme_id) {
+                bail!("invalid phoneme id: {phoneme_id}");
+            }
+            phoneme[i * phoneme_size + *phoneme_id as usize] = 1.;
+        }
+
+        let wave = inference::decode(
+            &self.decode.acquire(),
+            f0.len(),
+            phoneme_size,
+            f0.to_vec(),
+            phoneme,
+            self.decode_padding,
+            speaker_id,
+        )?;
+        Ok(AudioBuffer::mono(wave, SAMPLING_RATE))
+    }
+
+    // 録音と、その音素の区切りを書いた.labから長さと音高を取り、別の話者で言い直す
+    // semitonesで録音した人の声域を話者の声域に合わせる
+    #[cfg(feature = "unstable")]
+    pub fn voice_change(
+        &self,
+        recording: &AudioBuffer,
+        lab: &str,
+        speaker_id: u32,
+        semitones: f32,
+    ) -> Result<AudioBuffer> {
+        let (phoneme_ids, f0) = voice_changer::frame_inputs(recording, lab, semitones)?;
+        self.decode(&phoneme_ids, &f0, speaker_id)
+    }
+
+    pub fn synthesis_from_query(
+        &self,
+        audio_query_json: &str,
+        speaker_id: u32,
+    ) -> Result<AudioBuffer> {
+        let audio_query: AudioQueryModel = serde_json::from_str(audio_query_json)?;
+        audio_query.validate()?;
+        self.synthesis(&audio_query, speaker_id)
+    }
+
+    pub fn tts(&self, text: &str, speaker_id: u32) -> Result<AudioBuffer> {
+        self.tts_with_options(text, speaker_id, &self.default_options(speaker_id))
+    }
+
+    pub fn tts_with_options(
+        &self,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Result<AudioBuffer> {
+        let accent_phrases = self.create_accent_phrases(text)?;
+        self.tts_from_accent_phrases(accent_phrases, speaker_id, options, &mut Warnings::new())
+    }
+
+    // tts_with_optionsに加えて、途中で積んだ警告と音割れの有無を返す
+    pub fn tts_with_warnings(
+        &self,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Result<(AudioBuffer, Warnings)> {
+        let mut warnings = Warnings::new();
+        let accent_phrases = self.create_accent_phrases_with_warnings(text, &mut warnings)?;
+        let wav =
+            self.tts_from_accent_phrases(accent_phrases, speaker_id, options, &mut warnings)?;
+        warnings.check_clipping(&wav);
+        Ok((wav, warnings))
+    }
+
+    pub fn tts_from_kana(&self, kana: &str, speaker_id: u32) -> Result<AudioBuffer> {
+        let accent_phrases = self.create_accent_phrases_from_kana(kana)?;
+        self.tts_from_accent_phrases(
+            accent_phrases,
+            speaker_id,
+            &self.default_options(speaker_id),
+            &mut Warnings::new(),
+        )
+    }
+
+    // tts_with_optionsと同じ手順で、decodeの手前までの値を求める
+    pub fn intermediates(
+        &self,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Result<Intermediates> {
+        // labelsはユーザー辞書を使わない、jpreprocessだけの結果
+        let labels = self.jpreprocess.extract_fullcontext(text)?;
+        let accent_phrases = self.analyze(text, &mut Warnings::new(), false)?;
+        let accent_phrases = self.replace_mora_data(accent_phrases, speaker_id)?;
+        let input = synthesis_engine::decoder_input(
+            accent_phrases,
+            options.speed_scale,
+            options.pitch_scale,
+            options.intonation_scale,
+            options.pre_phoneme_length,
+            options.post_phoneme_length,
+            options.enable_interrogative_upspeak,
+        );
+
+        Ok(Intermediates {
+            labels,
+            phonemes: input
+                .phonemes
+                .iter()
+                .map(|phoneme| phoneme.phoneme.clone())
+                .collect(),
+            phoneme_ids: input.phonemes.iter().map(OjtPhoneme::phoneme_id).collect(),
+            phoneme_lengths: input.phoneme_lengths,
+            f0: input.f0,
+        })
+    }
+
+    // テキスト解析のみ。長さと音高は0のまま
+    pub fn create_accent_phrases(&self, text: &str) -> Result<Vec<AccentPhraseModel>> {
+        self.analyze(text, &mut Warnings::new(), false)
+    }
+
+    // AquesTalk風の記法(AudioQueryのkana)からアクセント句を作る。長さと音高は0のまま
+    pub fn create_accent_phrases_from_kana(&self, kana: &str) -> Result<Vec<AccentPhraseModel>> {
+        kana_parser::parse_kana(kana)
+    }
+
+    // 辞書に無い語と、長すぎて切ったアクセント句をwarningsに積む
+    pub fn create_accent_phrases_with_warnings(
+        &self,
+        text: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        self.analyze(text, warnings, true)
+    }
+
+    // ユーザー辞書の語を切り出し、残りをjpreprocessで解析してつなげる
+    fn analyze(
+        &self,
+        text: &str,
+        warnings: &mut Warnings,
+        warn_unknown_words: bool,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        let user_dict = self.user_dict.read().unwrap_or_else(|err| err.into_inner());
+        let mut accent_phrases: Vec<AccentPhraseModel> = Vec::new();
+        for segment in user_dict.split(text) {
+            let text = match segment {
+                Segment::Word(word) => {
+                    accent_phrases.push(word.to_accent_phrase()?);
+                    continue;
+                }
+                Segment::Text(text) => text,
+            };
+            // 語の直後の読点と疑問符は、その語のアクセント句に付ける
+            if let Some(last) = accent_phrases.last_mut() {
+                let head = text.trim_start();
+                if head.starts_with(['、', '。', '，', '．', ',', '.', '！', '!']) {
+                    last.pause_mora.get_or_insert_with(kana_parser::pause_mora);
+                } else if head.starts_with(['？', '?']) {
+                    last.is_interrogative = true;
+                    last.pause_mora.get_or_insert_with(kana_parser::pause_mora);
+                }
+            }
+            // 語の後の記号だけの部分は、jpreprocessに渡しても読むものが無い
+            if !accent_phrases.is_empty() && !text.chars().any(char::is_alphanumeric) {
+                continue;
+            }
+            if warn_unknown_words {
+                let njd = self.jpreprocess.text_to_njd(text)?;
+                warnings.extend(
+                    njd.nodes
+                        .iter()
+                        .filter(|node| {
+                            node.get_read().is_none() && !node.get_string().trim().is_empty()
+                        })
+                        .map(|node| Warning::UnknownWord {
+                            surface: node.get_string().to_string(),
+                        }),
+                );
+            }
+            let labels = self.jpreprocess.extract_fullcontext(text)?;
+            accent_phrases.extend(synthesis_engine::create_accent_phrases(labels, warnings)?);
+        }
+        Ok(accent_phrases)
+    }
+
+    pub fn replace_phoneme_length(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        speaker_id: u32,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        self.check_speaker(speaker_id)?;
+        validation::check_phonemes(&accent_phrases)?;
+        let accent_phrases = if self.enable_devoicing {
+            devoicing::devoice(accent_phrases)
+        } else {
+            accent_phrases
+        };
+        match &self.predict_duration {
+            Some(predict_duration) => synthesis_engine::replace_phoneme_length(
+                predict_duration,
+                accent_phrases,
+                speaker_id,
+                &mut Warnings::new(),
+            ),
+            None => Ok(rule_based::replace_phoneme_length(accent_phrases)),
+        }
+    }
+
+    pub fn replace_mora_pitch(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        speaker_id: u32,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        self.check_speaker(speaker_id)?;
+        validation::check_phonemes(&accent_phrases)?;
+        match &self.predict_intonation {
+            Some(predict_intonation) => {
+                synthesis_engine::replace_mora_pitch(predict_intonation, accent_phrases, speaker_id)
+            }
+            None => Ok(rule_based::replace_mora_pitch(accent_phrases)),
+        }
+    }
+
+    // AudioQuery生成
+    pub fn replace_mora_data(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        speaker_id: u32,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        self.replace_mora_data_with_warnings(accent_phrases, speaker_id, &mut Warnings::new())
+    }
+
+    // 下限に引き上げた長さをwarningsに積む
+    pub fn replace_mora_data_with_warnings(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        speaker_id: u32,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<AccentPhraseModel>> {
+        self.check_speaker(speaker_id)?;
+        validation::check_phonemes(&accent_phrases)?;
+        let accent_phrases = if self.enable_devoicing {
+            devoicing::devoice(accent_phrases)
+        } else {
+            accent_phrases
+        };
+        synthesis_engine::replace_mora_data(
+            self.predict_duration.as_ref(),
+            self.predict_intonation.as_ref(),
+            accent_phrases,
+            speaker_id,
+            warnings,
+        )
+    }
+
+    fn tts_from_accent_phrases(
+        &self,
+        accent_phrases: Vec<AccentPhraseModel>,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+        warnings: &mut Warnings,
+    ) -> Result<AudioBuffer> {
+        let mut accent_phrases =
+            self.replace_mora_data_with_warnings(accent_phrases, speaker_id, warnings)?;
+
+        let particle_softening = self.particle_softening.get(&speaker_id);
+        let particle_frames = particle_softening.and_then(|particle_softening| {
+            particle_softening.apply(&mut accent_phrases, options.speed_scale)
+        });
+
+        // 合成
+        let mut wave = synthesis_engine::synthesis(
+            &self.decode.acquire(),
+            accent_phrases,
+            options.speed_scale,
+            options.pitch_scale,
+            options.intonation_scale,
+            options.pre_phoneme_length,
+            options.post_phoneme_length,
+            options.enable_interrogative_upspeak,
+            self.decode_padding,
+            speaker_id,
+        )?;
+
+        if let (Some(particle_softening), Some(particle_frames)) =
+            (particle_softening, particle_frames)
+        {
+            let post_frames =
+                (options.post_phoneme_length * FRAME_RATE / options.speed_scale).ceil() as usize;
+            particle_softening.apply_volume(&mut wave, particle_frames, post_frames);
+        }
+
+        let wave = wave
+            .into_iter()
+            .map(|sample| sample * options.volume_scale)
+            .collect();
+        Ok(AudioBuffer::mono(wave, SAMPLING_RATE))
+    }
+}
+
+fn update_phrase(
+    mut accent_phrases: Vec<AccentPhraseModel>,
+    index: usize,
+    predict: impl FnOnce(Vec<AccentPhraseModel>) -> Result<Vec<AccentPhraseModel>>,
+) -> Result<Vec<AccentPhraseModel>> {
+    let accent_phrase = accent_phrases
+        .get(index)
+        .cloned()
+        .ok_or(anyhow!("invalid accent phrase index: {index}"))?;
+    let mut predicted = predict(vec![accent_phrase])?;
+    accent_phrases[index] = predicted.remove(0);
+
+    Ok(accent_phrases)
+}
+
+fn build_optional_session(
+    model_dir: &Path,
+    file_name: &str,
+    options: &InitializeOptions,
+) -> Result<Option<Session>> {
+    let path = model_dir.join(file_name);
+    if !path.exists() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("{file_name} not found, falling back to rules");
+        return Ok(None);
+    }
+    Ok(Some(build_session(&path, options, Device::Cpu)?))
+}
+
+#[cfg(feature = "wasm")]
+fn build_session(path: &Path, _options: &InitializeOptions, device: Device) -> Result<Session> {
+    if device != Device::Cpu {
+        bail!("{device} is not available with the wasm feature");
+    }
+    Session::from_bytes(&fs::read(path)?)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn build_session(path: &Path, options: &InitializeOptions, device: Device) -> Result<Session> {
+    // deterministicでは最適化を切るので、書き出すものが無い
+    if options.cache_optimized_models && device == Device::Cpu && !options.deterministic {
+        return build_cached_session(path, options);
+    }
+    Ok(session_builder(options, device)?.with_model_from_file(path)?)
+}
+
+// 書き出したグラフがモデルより新しければ、それを読み込む。無いか古ければ、読み込みながら書き出す
+// ハードウェアに依るレイアウトの最適化(Level3)は書き出さずに読み込むたびに行うので、
+// 同じmodel_dirを別の機械と共有していても動く
+#[cfg(not(feature = "wasm"))]
+fn build_cached_session(path: &Path, options: &InitializeOptions) -> Result<Session> {
+    let optimized = optimized_model_path(path);
+    if is_newer(&optimized, path) {
+        match session_builder(options, Device::Cpu)?.with_model_from_file(&optimized) {
+            Ok(session) => return Ok(session),
+            Err(_err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("{}: {_err}, optimizing again", optimized.display());
+            }
+        }
+    }
+
+    // 書きかけのファイルを他のプロセスが読まないように、別名で書いてから置き換える
+    let temporary = optimized.with_extension(format!("{}.tmp", std::process::id()));
+    let session = session_builder(options, Device::Cpu)?
+        .with_optimization_level(GraphOptimizationLevel::Level2)?
+        .with_optimized_model_path(temporary.to_string_lossy())?
+        .with_model_from_file(path);
+    match session {
+        // 書き出した回だけはLevel2に留まるが、読み込み直すよりは速い
+        Ok(session) => {
+            if fs::rename(&temporary, &optimized).is_err() {
+                let _ = fs::remove_file(&temporary);
+            }
+            Ok(session)
+        }
+        // model_dirに書けなければ、書き出さずに読み込む
+        Err(_err) => {
+            let _ = fs::remove_file(&temporary);
+            #[cfg(feature = "tracing")]
+            tracing::warn!("could not write {}: {_err}", optimized.display());
+            Ok(session_builder(options, Device::Cpu)?.with_model_from_file(path)?)
+        }
+    }
+}
+
+// decode-0.onnxなら.decode-0.ort16.optimized.onnx。ORTの版が変われば作り直す
+// ドットで始めるので、モデルの一覧や指紋には含まれない
+#[cfg(not(feature = "wasm"))]
+fn optimized_model_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{stem}.ort{ONNXRUNTIME_API_VERSION}.optimized.onnx"
+    ))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn is_newer(path: &Path, than: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    matches!((modified(path), modified(than)), (Ok(a), Ok(b)) if a >= b)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn session_builder(options: &InitializeOptions, device: Device) -> Result<SessionBuilder> {
+    let builder = Session::builder()?.with_intra_threads(options.intra_threads() as _)?;
+    let builder = match device {
+        Device::Cpu => builder,
+        // 登録に失敗してもORTは黙ってCPUで動かすので、使えない構成は先に断る
+        Device::Cuda(_) if !cfg!(feature = "cuda") => {
+            bail!("{device} is not available: rebuild with --features cuda")
+        }
+        Device::Cuda(device_id) => {
+            builder.with_execution_providers([CUDAExecutionProvider::default()
+                .with_device_id(device_id)
+                .build()])?
+        }
+    };
+    let builder = if options.deterministic {
+        builder
+            .with_inter_threads(1)?
+            .with_parallel_execution(false)?
+            .with_optimization_level(GraphOptimizationLevel::Disable)?
+    } else {
+        builder
+    };
+    Ok(builder)
+}
@@ -0,0 +1,83 @@
+use crate::synthesis_engine::{AccentPhraseModel, UNVOICED_MORA_PHONEME_LIST};
+
+// 予測モデルが無い場合の規則による代用
+// 予測に比べて明らかに不自然なので、動作確認や制約の厳しい環境向け
+
+const LOW_PITCH: f32 = 5.5;
+const HIGH_PITCH: f32 = 5.8;
+
+// 音素ごとの平均的な長さ(秒)。話速はsynthesisで掛けられる
+const PLOSIVE_LENGTH: f32 = 0.07;
+const FRICATIVE_LENGTH: f32 = 0.09;
+const SONORANT_LENGTH: f32 = 0.05;
+const VOWEL_LENGTH: f32 = 0.1;
+const DEVOICED_VOWEL_LENGTH: f32 = 0.07;
+const MORAIC_NASAL_LENGTH: f32 = 0.09;
+const GEMINATE_LENGTH: f32 = 0.08;
+const PAUSE_LENGTH: f32 = 0.3;
+
+pub fn replace_phoneme_length(accent_phrases: Vec<AccentPhraseModel>) -> Vec<AccentPhraseModel> {
+    accent_phrases
+        .into_iter()
+        .map(|mut accent_phrase| {
+            for mora in accent_phrase
+                .moras
+                .iter_mut()
+                .chain(&mut accent_phrase.pause_mora)
+            {
+                mora.consonant_length = mora.consonant.as_deref().map(consonant_length);
+                mora.vowel_length = vowel_length(&mora.vowel);
+            }
+            accent_phrase
+        })
+        .collect()
+}
+
+fn consonant_length(consonant: &str) -> f32 {
+    match consonant {
+        "k" | "ky" | "g" | "gy" | "t" | "d" | "p" | "py" | "b" | "by" | "ts" | "ch" => {
+            PLOSIVE_LENGTH
+        }
+        "s" | "sh" | "z" | "j" | "h" | "hy" | "f" => FRICATIVE_LENGTH,
+        _ => SONORANT_LENGTH,
+    }
+}
+
+fn vowel_length(vowel: &str) -> f32 {
+    match vowel {
+        "A" | "I" | "U" | "E" | "O" => DEVOICED_VOWEL_LENGTH,
+        "N" => MORAIC_NASAL_LENGTH,
+        "cl" => GEMINATE_LENGTH,
+        "pau" => PAUSE_LENGTH,
+        _ => VOWEL_LENGTH,
+    }
+}
+
+// アクセント型から高低2段の階段状の音高を作る
+// 1型なら先頭のみ高く、それ以外は2モーラ目からアクセント核までを高くする
+pub fn replace_mora_pitch(accent_phrases: Vec<AccentPhraseModel>) -> Vec<AccentPhraseModel> {
+    accent_phrases
+        .into_iter()
+        .map(|mut accent_phrase| {
+            let accent = accent_phrase.accent;
+            for (i, mora) in accent_phrase.moras.iter_mut().enumerate() {
+                let is_high = if accent == 1 {
+                    i == 0
+                } else {
+                    i != 0 && i < accent
+                };
+                mora.pitch = if UNVOICED_MORA_PHONEME_LIST.contains(&mora.vowel.as_str()) {
+                    0.
+                } else if is_high {
+                    HIGH_PITCH
+                } else {
+                    LOW_PITCH
+                };
+            }
+            if let Some(pause_mora) = &mut accent_phrase.pause_mora {
+                pause_mora.pitch = 0.;
+            }
+            accent_phrase
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+// VOICEVOX ENGINEのspeaker_infoと同じ形式
+// <resource_dir>/<speaker_uuid>/ 以下に policy.md, portrait.png, icons/<id>.png,
+// portraits/<id>.png, voice_samples/<id>_<n>.wav を置く
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SpeakerInfo {
+    pub policy: String,
+    pub portrait: String,
+    pub style_infos: Vec<StyleInfo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StyleInfo {
+    pub id: u32,
+    pub icon: String,
+    pub portrait: Option<String>,
+    pub voice_samples: Vec<String>,
+}
+
+impl SpeakerInfo {
+    pub fn load(resource_dir: &Path, speaker_uuid: &str, style_ids: &[u32]) -> Result<Self> {
+        let speaker_dir = resource_dir.join(speaker_uuid);
+        let policy = fs::read_to_string(speaker_dir.join("policy.md"))
+            .with_context(|| format!("no speaker_info for {speaker_uuid}"))?;
+        let portrait = read_base64(&speaker_dir.join("portrait.png"))?;
+
+        let style_infos = style_ids
+            .iter()
+            .map(|id| {
+                let icon = read_base64(&speaker_dir.join("icons").join(format!("{id}.png")))?;
+                let portrait_path = speaker_dir.join("portraits").join(format!("{id}.png"));
+                let portrait = portrait_path
+                    .exists()
+                    .then(|| read_base64(&portrait_path))
+                    .transpose()?;
+                let voice_samples = (1..)
+                    .map(|n| {
+                        speaker_dir
+                            .join("voice_samples")
+                            .join(format!("{id}_{n:03}.wav"))
+                    })
+                    .take_while(|path| path.exists())
+                    .map(|path| read_base64(&path))
+                    .collect::<Result<_>>()?;
+
+                Ok(StyleInfo {
+                    id: *id,
+                    icon,
+                    portrait,
+                    voice_samples,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            policy,
+            portrait,
+            style_infos,
+        })
+    }
+}
+
+fn read_base64(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(STANDARD.encode(bytes))
+}
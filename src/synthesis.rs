@@ -0,0 +1,113 @@
+// 一度に少しずつ進める合成。GUIのメインループなどから呼べば、スレッドを立てずに応答を保てる
+// 一回のstepで進めるのは、テキスト解析・長さと音高の予測・息継ぎ一つ分のdecodeのいずれか一つだけ
+
+use crate::{
+    audio_buffer::AudioBuffer,
+    publish::{SynthesisOptions, VoicevoxCore},
+    synthesis_engine::{AccentPhraseModel, AudioQueryModel},
+    warnings::Warnings,
+};
+use anyhow::Result;
+use std::{mem, vec};
+
+pub struct Synthesis<'a> {
+    core: &'a VoicevoxCore,
+    speaker_id: u32,
+    options: SynthesisOptions,
+    state: State,
+    audio_query: Option<AudioQueryModel>,
+    warnings: Warnings,
+}
+
+enum State {
+    Analysis(String),
+    Prediction(Vec<AccentPhraseModel>),
+    Decode(vec::IntoIter<AudioQueryModel>),
+    Done,
+}
+
+pub enum Progress {
+    // テキスト解析か予測が済んだ。音声はまだ無い
+    Working,
+    // 息継ぎまでの音声。受け取った順に続けて鳴らせば一文になる
+    Audio(AudioBuffer),
+    // 全て終わった。これ以降のstepもDoneを返す
+    Done,
+}
+
+impl<'a> Synthesis<'a> {
+    // ここではまだ何もしない。tts_with_warningsと同じ手順を、stepのたびに一つずつ進める
+    // 話者ごとの助詞の柔らげ(unstable)は一文全体に掛けるものなので、ここでは使わない
+    pub fn new(
+        core: &'a VoicevoxCore,
+        text: &str,
+        speaker_id: u32,
+        options: &SynthesisOptions,
+    ) -> Self {
+        Self {
+            core,
+            speaker_id,
+            options: options.clone(),
+            state: State::Analysis(text.to_string()),
+            audio_query: None,
+            warnings: Warnings::new(),
+        }
+    }
+
+    // エラーになったら、それ以降はDoneを返す
+    pub fn step(&mut self) -> Result<Progress> {
+        match mem::replace(&mut self.state, State::Done) {
+            State::Analysis(text) => {
+                let accent_phrases = self
+                    .core
+                    .create_accent_phrases_with_warnings(&text, &mut self.warnings)?;
+                self.state = State::Prediction(accent_phrases);
+                Ok(Progress::Working)
+            }
+            State::Prediction(accent_phrases) => {
+                let accent_phrases = self.core.replace_mora_data_with_warnings(
+                    accent_phrases,
+                    self.speaker_id,
+                    &mut self.warnings,
+                )?;
+                let audio_query = self
+                    .core
+                    .audio_query_from_accent_phrases(accent_phrases, &self.options);
+                self.state = State::Decode(audio_query.split_breath_groups().into_iter());
+                self.audio_query = Some(audio_query);
+                Ok(Progress::Working)
+            }
+            State::Decode(mut groups) => {
+                let Some(group) = groups.next() else {
+                    return Ok(Progress::Done);
+                };
+                let audio = self.core.synthesis_impl(
+                    &group,
+                    self.speaker_id,
+                    self.options.enable_interrogative_upspeak,
+                )?;
+                self.warnings.check_clipping(&audio);
+                if !groups.as_slice().is_empty() {
+                    self.state = State::Decode(groups);
+                }
+                Ok(Progress::Audio(audio))
+            }
+            State::Done => Ok(Progress::Done),
+        }
+    }
+
+    // 最後の音声を返したか、エラーになったか
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    // 予測が済んでいれば、その結果。編集して保存し直すのに使える
+    pub fn audio_query(&self) -> Option<&AudioQueryModel> {
+        self.audio_query.as_ref()
+    }
+
+    // ここまでに積んだ警告
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+}
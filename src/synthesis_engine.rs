@@ -1,36 +1,73 @@
 use crate::{
     acoustic_feature_extractor::OjtPhoneme,
     full_context_label::{Phoneme, Utterance},
-    inference::{decode, predict_duration, predict_intonation},
+    inference::{decode, predict_duration, predict_intonation, Session, PHONEME_LENGTH_MINIMAL},
     mora_list::MORA_LIST_MINIMUM,
+    rule_based,
+    warnings::{Warning, Warnings},
 };
 use anyhow::Result;
-use ort::Session;
+use serde::{Deserialize, Serialize};
 
-const UNVOICED_MORA_PHONEME_LIST: &[&str] = &["A", "I", "U", "E", "O", "cl", "pau"];
-const MORA_PHONEME_LIST: &[&str] = &[
+pub(crate) const UNVOICED_MORA_PHONEME_LIST: &[&str] = &["A", "I", "U", "E", "O", "cl", "pau"];
+pub(crate) const MORA_PHONEME_LIST: &[&str] = &[
     "a", "i", "u", "e", "o", "N", "A", "I", "U", "E", "O", "cl", "pau",
 ];
 
-#[derive(Clone)]
-struct MoraModel {
-    text: String,
-    consonant: Option<String>,
-    consonant_length: Option<f32>,
-    vowel: String,
-    vowel_length: f32,
-    pitch: f32,
+pub const FRAME_RATE: f32 = 24000. / 256.;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MoraModel {
+    pub text: String,
+    pub consonant: Option<String>,
+    pub consonant_length: Option<f32>,
+    pub vowel: String,
+    pub vowel_length: f32,
+    pub pitch: f32,
+    // chibivox独自の拡張。decodeした後で、このモーラの音量に掛ける倍率。無ければ1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f32>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AccentPhraseModel {
-    moras: Vec<MoraModel>,
-    accent: usize,
-    pause_mora: Option<MoraModel>,
-    is_interrogative: bool,
+    pub moras: Vec<MoraModel>,
+    pub accent: usize,
+    pub pause_mora: Option<MoraModel>,
+    #[serde(default)]
+    pub is_interrogative: bool,
 }
 
-pub fn create_accent_phrases(labels: Vec<String>) -> Result<Vec<AccentPhraseModel>> {
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AudioQueryModel {
+    pub accent_phrases: Vec<AccentPhraseModel>,
+    #[serde(rename = "speedScale")]
+    pub speed_scale: f32,
+    #[serde(rename = "pitchScale")]
+    pub pitch_scale: f32,
+    #[serde(rename = "intonationScale")]
+    pub intonation_scale: f32,
+    #[serde(rename = "volumeScale")]
+    pub volume_scale: f32,
+    #[serde(rename = "prePhonemeLength")]
+    pub pre_phoneme_length: f32,
+    #[serde(rename = "postPhonemeLength")]
+    pub post_phoneme_length: f32,
+    #[serde(rename = "outputSamplingRate")]
+    pub output_sampling_rate: u32,
+    #[serde(rename = "outputStereo")]
+    pub output_stereo: bool,
+    pub kana: Option<String>,
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(labels = labels.len())))]
+pub fn create_accent_phrases(
+    labels: Vec<String>,
+    warnings: &mut Warnings,
+) -> Result<Vec<AccentPhraseModel>> {
     let utterance = Utterance::from_phonemes(
         labels
             .into_iter()
@@ -43,7 +80,7 @@ pub fn create_accent_phrases(labels: Vec<String>) -> Result<Vec<AccentPhraseMode
         |mut accum_vec, (i, breath_group)| {
             accum_vec.extend(breath_group.accent_phrases.iter().enumerate().map(
                 |(j, accent_phrase)| {
-                    let moras = accent_phrase
+                    let moras: Vec<MoraModel> = accent_phrase
                         .moras
                         .iter()
                         .map(|mora| {
@@ -68,9 +105,15 @@ pub fn create_accent_phrases(labels: Vec<String>) -> Result<Vec<AccentPhraseMode
                                 vowel: mora.vowel.phoneme().into(),
                                 vowel_length: 0.,
                                 pitch: 0.,
+                                gain: None,
                             }
                         })
                         .collect();
+                    if accent_phrase.truncated {
+                        warnings.push(Warning::TruncatedPhrase {
+                            text: moras.iter().map(|mora| mora.text.as_str()).collect(),
+                        });
+                    }
 
                     let pause_mora = if i != utterance.breath_groups.len() - 1
                         && j == breath_group.accent_phrases.len() - 1
@@ -82,6 +125,7 @@ pub fn create_accent_phrases(labels: Vec<String>) -> Result<Vec<AccentPhraseMode
                             vowel: "pau".into(),
                             vowel_length: 0.,
                             pitch: 0.,
+                            gain: None,
                         })
                     } else {
                         None
@@ -103,70 +147,136 @@ pub fn create_accent_phrases(labels: Vec<String>) -> Result<Vec<AccentPhraseMode
     Ok(accent_phrases)
 }
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(session, accent_phrases, warnings))
+)]
 pub fn replace_phoneme_length(
-    session: Session,
+    session: &Session,
     accent_phrases: Vec<AccentPhraseModel>,
     speaker_id: u32,
+    warnings: &mut Warnings,
 ) -> Result<Vec<AccentPhraseModel>> {
     let (_, phoneme_data_list) = initial_process(accent_phrases.clone());
     let (_, _, vowel_indexes_data) = split_mora(phoneme_data_list.clone());
 
-    let phoneme_list_s: Vec<i64> = phoneme_data_list
-        .iter()
-        .map(OjtPhoneme::phoneme_id)
-        .collect();
-    let phoneme_length = predict_duration(session, &phoneme_list_s, speaker_id)?;
+    let phoneme_length = predict_phoneme_length(session, &phoneme_data_list, speaker_id, warnings)?;
 
-    let mut index = 0;
-    let new_accent_phrases = accent_phrases
-        .into_iter()
-        .map(|accent_phrase| AccentPhraseModel {
-            moras: accent_phrase
-                .moras
-                .into_iter()
-                .map(|mora| {
-                    let new_mora = MoraModel {
-                        text: mora.text,
-                        consonant: mora.consonant.clone(),
-                        consonant_length: mora
-                            .consonant
-                            .map(|_| phoneme_length[vowel_indexes_data[index + 1] as usize - 1]),
-                        vowel: mora.vowel.clone(),
-                        vowel_length: phoneme_length[vowel_indexes_data[index + 1] as usize],
-                        pitch: mora.pitch,
-                    };
-                    index += 1;
-                    new_mora
-                })
-                .collect(),
-            accent: accent_phrase.accent,
-            pause_mora: accent_phrase.pause_mora.map(|pause_mora| {
-                let new_pause_mora = MoraModel {
-                    text: pause_mora.text,
-                    consonant: pause_mora.consonant,
-                    consonant_length: pause_mora.consonant_length,
-                    vowel: pause_mora.vowel,
-                    vowel_length: phoneme_length[vowel_indexes_data[index + 1] as usize],
-                    pitch: pause_mora.pitch,
-                };
-                index += 1;
-                new_pause_mora
-            }),
-            is_interrogative: accent_phrase.is_interrogative,
-        })
-        .collect();
-
-    Ok(new_accent_phrases)
+    Ok(apply_phoneme_length(
+        accent_phrases,
+        &phoneme_length,
+        &vowel_indexes_data,
+    ))
 }
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(session, accent_phrases))
+)]
 pub fn replace_mora_pitch(
-    session: Session,
+    session: &Session,
     accent_phrases: Vec<AccentPhraseModel>,
     speaker_id: u32,
 ) -> Result<Vec<AccentPhraseModel>> {
     let (_, phoneme_data_list) = initial_process(accent_phrases.clone());
     let (consonant_phoneme_data_list, vowel_phoneme_data_list, vowel_indexes) =
         split_mora(phoneme_data_list);
+
+    let f0_list = predict_mora_pitch(
+        session,
+        &accent_phrases,
+        &consonant_phoneme_data_list,
+        &vowel_phoneme_data_list,
+        &vowel_indexes,
+        speaker_id,
+    )?;
+
+    Ok(apply_mora_pitch(accent_phrases, &f0_list))
+}
+
+// replace_phoneme_lengthとreplace_mora_pitchを、音素列の前処理を共有して一度に行う
+// sessionが無ければ、長さや音高は規則で代用する
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(duration_session, intonation_session, accent_phrases, warnings))
+)]
+pub fn replace_mora_data(
+    duration_session: Option<&Session>,
+    intonation_session: Option<&Session>,
+    accent_phrases: Vec<AccentPhraseModel>,
+    speaker_id: u32,
+    warnings: &mut Warnings,
+) -> Result<Vec<AccentPhraseModel>> {
+    let (_, phoneme_data_list) = initial_process(accent_phrases.clone());
+    let (consonant_phoneme_data_list, vowel_phoneme_data_list, vowel_indexes) =
+        split_mora(phoneme_data_list.clone());
+
+    let phoneme_length = duration_session
+        .map(|duration_session| {
+            predict_phoneme_length(duration_session, &phoneme_data_list, speaker_id, warnings)
+        })
+        .transpose()?;
+    let f0_list = intonation_session
+        .map(|intonation_session| {
+            predict_mora_pitch(
+                intonation_session,
+                &accent_phrases,
+                &consonant_phoneme_data_list,
+                &vowel_phoneme_data_list,
+                &vowel_indexes,
+                speaker_id,
+            )
+        })
+        .transpose()?;
+
+    let accent_phrases = match phoneme_length {
+        Some(phoneme_length) => {
+            apply_phoneme_length(accent_phrases, &phoneme_length, &vowel_indexes)
+        }
+        None => rule_based::replace_phoneme_length(accent_phrases),
+    };
+    Ok(match f0_list {
+        Some(f0_list) => apply_mora_pitch(accent_phrases, &f0_list),
+        None => rule_based::replace_mora_pitch(accent_phrases),
+    })
+}
+
+fn predict_phoneme_length(
+    session: &Session,
+    phoneme_data_list: &[OjtPhoneme],
+    speaker_id: u32,
+    warnings: &mut Warnings,
+) -> Result<Vec<f32>> {
+    let phoneme_list_s: Vec<i64> = phoneme_data_list
+        .iter()
+        .map(OjtPhoneme::phoneme_id)
+        .collect();
+    let mut phoneme_length = predict_duration(session, &phoneme_list_s, speaker_id)?;
+
+    let last = phoneme_length.len().saturating_sub(1);
+    for (i, length) in phoneme_length.iter_mut().enumerate() {
+        if *length < PHONEME_LENGTH_MINIMAL {
+            // 前後のpauは後でpre/post_phoneme_lengthに置き換わるので知らせない
+            if i != 0 && i != last {
+                warnings.push(Warning::ClampedDuration {
+                    phoneme: phoneme_data_list[i].phoneme.clone(),
+                    predicted: *length,
+                });
+            }
+            *length = PHONEME_LENGTH_MINIMAL;
+        }
+    }
+    Ok(phoneme_length)
+}
+
+fn predict_mora_pitch(
+    session: &Session,
+    accent_phrases: &[AccentPhraseModel],
+    consonant_phoneme_data_list: &[OjtPhoneme],
+    vowel_phoneme_data_list: &[OjtPhoneme],
+    vowel_indexes: &[i64],
+    speaker_id: u32,
+) -> Result<Vec<f32>> {
     let consonant_phoneme_list: Vec<i64> = consonant_phoneme_data_list
         .iter()
         .map(OjtPhoneme::phoneme_id)
@@ -247,8 +357,62 @@ pub fn replace_mora_pitch(
     })
     .collect();
 
+    Ok(f0_list)
+}
+
+fn apply_phoneme_length(
+    accent_phrases: Vec<AccentPhraseModel>,
+    phoneme_length: &[f32],
+    vowel_indexes_data: &[i64],
+) -> Vec<AccentPhraseModel> {
     let mut index = 0;
-    let new_accent_phrases = accent_phrases
+    accent_phrases
+        .into_iter()
+        .map(|accent_phrase| AccentPhraseModel {
+            moras: accent_phrase
+                .moras
+                .into_iter()
+                .map(|mora| {
+                    let new_mora = MoraModel {
+                        text: mora.text,
+                        consonant: mora.consonant.clone(),
+                        consonant_length: mora
+                            .consonant
+                            .map(|_| phoneme_length[vowel_indexes_data[index + 1] as usize - 1]),
+                        vowel: mora.vowel.clone(),
+                        vowel_length: phoneme_length[vowel_indexes_data[index + 1] as usize],
+                        pitch: mora.pitch,
+                        gain: mora.gain,
+                    };
+                    index += 1;
+                    new_mora
+                })
+                .collect(),
+            accent: accent_phrase.accent,
+            pause_mora: accent_phrase.pause_mora.map(|pause_mora| {
+                let new_pause_mora = MoraModel {
+                    text: pause_mora.text,
+                    consonant: pause_mora.consonant,
+                    consonant_length: pause_mora.consonant_length,
+                    vowel: pause_mora.vowel,
+                    vowel_length: phoneme_length[vowel_indexes_data[index + 1] as usize],
+                    pitch: pause_mora.pitch,
+                    gain: pause_mora.gain,
+                };
+                index += 1;
+                new_pause_mora
+            }),
+            is_interrogative: accent_phrase.is_interrogative,
+        })
+        .collect()
+}
+
+fn apply_mora_pitch(
+    accent_phrases: Vec<AccentPhraseModel>,
+    f0_list: &[f32],
+) -> Vec<AccentPhraseModel> {
+    let mut index = 0;
+    accent_phrases
         .into_iter()
         .map(|accent_phrase| AccentPhraseModel {
             moras: accent_phrase
@@ -262,6 +426,7 @@ pub fn replace_mora_pitch(
                         vowel: mora.vowel,
                         vowel_length: mora.vowel_length,
                         pitch: f0_list[index + 1],
+                        gain: mora.gain,
                     };
                     index += 1;
                     new_mora
@@ -276,19 +441,22 @@ pub fn replace_mora_pitch(
                     vowel: pause_mora.vowel,
                     vowel_length: pause_mora.vowel_length,
                     pitch: f0_list[index + 1],
+                    gain: pause_mora.gain,
                 };
                 index += 1;
                 new_pause_mora
             }),
             is_interrogative: accent_phrase.is_interrogative,
         })
-        .collect();
-
-    Ok(new_accent_phrases)
+        .collect()
 }
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(session, accent_phrases))
+)]
 pub fn synthesis(
-    session: Session,
+    session: &Session,
     accent_phrases: Vec<AccentPhraseModel>,
     speed_scale: f32,
     pitch_scale: f32,
@@ -296,8 +464,67 @@ pub fn synthesis(
     pre_phoneme_length: f32,
     post_phoneme_length: f32,
     enable_interrogative_upspeak: bool,
+    padding: f32,
     speaker_id: u32,
 ) -> Result<Vec<f32>> {
+    let input = decoder_input(
+        accent_phrases,
+        speed_scale,
+        pitch_scale,
+        intonation_scale,
+        pre_phoneme_length,
+        post_phoneme_length,
+        enable_interrogative_upspeak,
+    );
+    let mut wave = decode(
+        session,
+        input.f0.len(),
+        OjtPhoneme::num_phoneme(),
+        input.f0,
+        input.phoneme,
+        padding,
+        speaker_id,
+    )?;
+    if let Some(gain) = &input.gain {
+        apply_gain(&mut wave, gain);
+    }
+    Ok(wave)
+}
+
+// フレームごとの倍率を、隣のフレームとの間で線形に補間して掛ける。倍率の変わり目で音がぷつっと鳴らないように
+fn apply_gain(wave: &mut [f32], gain: &[f32]) {
+    const SAMPLES_PER_FRAME: f32 = 256.;
+    for (i, sample) in wave.iter_mut().enumerate() {
+        let position = ((i as f32 + 0.5) / SAMPLES_PER_FRAME - 0.5).max(0.);
+        let frame = (position as usize).min(gain.len() - 1);
+        let next = (frame + 1).min(gain.len() - 1);
+        let t = position - frame as f32;
+        *sample *= gain[frame] * (1. - t) + gain[next] * t;
+    }
+}
+
+// decoderへの入力と、それを作る途中で求めた音素ごとの値
+pub(crate) struct DecoderInput {
+    pub phonemes: Vec<OjtPhoneme>,
+    // 音素ごとの長さ(秒)。speed_scaleを掛ける前
+    pub phoneme_lengths: Vec<f32>,
+    // フレームごとのf0
+    pub f0: Vec<f32>,
+    // フレームごとの音素のone-hotを平らにしたもの
+    pub phoneme: Vec<f32>,
+    // フレームごとの音量の倍率。どのモーラにもgainが無ければNone
+    pub gain: Option<Vec<f32>>,
+}
+
+pub(crate) fn decoder_input(
+    accent_phrases: Vec<AccentPhraseModel>,
+    speed_scale: f32,
+    pitch_scale: f32,
+    intonation_scale: f32,
+    pre_phoneme_length: f32,
+    post_phoneme_length: f32,
+    enable_interrogative_upspeak: bool,
+) -> DecoderInput {
     let accent_phrases = if enable_interrogative_upspeak {
         adjust_interrogative_accent_phrases(accent_phrases)
     } else {
@@ -306,24 +533,32 @@ pub fn synthesis(
 
     let (flatten_moras, phoneme_data_list) = initial_process(accent_phrases);
 
-    let mut phoneme_length_list = vec![pre_phoneme_length];
+    let phoneme_length_list =
+        create_phoneme_length_list(&flatten_moras, pre_phoneme_length, post_phoneme_length);
+    // phoneme_length_listと同じ並びの、音素ごとの倍率。子音も母音と同じ倍率にする
+    let phoneme_gain_list = flatten_moras
+        .iter()
+        .any(|mora| mora.gain.is_some())
+        .then(|| {
+            std::iter::once(1.)
+                .chain(flatten_moras.iter().flat_map(|mora| {
+                    let gain = mora.gain.unwrap_or(1.);
+                    mora.consonant_length
+                        .map(|_| gain)
+                        .into_iter()
+                        .chain(std::iter::once(gain))
+                }))
+                .chain(std::iter::once(1.))
+                .collect::<Vec<_>>()
+        });
     let mut f0_list = vec![0.];
     let mut voiced_list = vec![false];
     {
         let mut sum_of_f0_bigger_than_zero = 0.;
         let mut count_of_f0_bigger_than_zero = 0;
 
-        for mora in flatten_moras {
-            let consonant_length = mora.consonant_length;
-            let vowel_length = mora.vowel_length;
-            let pitch = mora.pitch;
-
-            if let Some(consonant_length) = consonant_length {
-                phoneme_length_list.push(consonant_length);
-            }
-            phoneme_length_list.push(vowel_length);
-
-            let f0_single = pitch * 2.0_f32.powf(pitch_scale);
+        for mora in &flatten_moras {
+            let f0_single = mora.pitch * 2.0_f32.powf(pitch_scale);
             f0_list.push(f0_single);
 
             let bigger_than_zero = f0_single > 0.;
@@ -334,7 +569,6 @@ pub fn synthesis(
                 count_of_f0_bigger_than_zero += 1;
             }
         }
-        phoneme_length_list.push(post_phoneme_length);
         f0_list.push(0.);
         voiced_list.push(false);
         let mean_f0 = sum_of_f0_bigger_than_zero / (count_of_f0_bigger_than_zero as f32);
@@ -352,15 +586,18 @@ pub fn synthesis(
 
     let mut phoneme: Vec<Vec<f32>> = Vec::new();
     let mut f0: Vec<f32> = Vec::new();
+    let mut gain = phoneme_gain_list.as_ref().map(|_| Vec::new());
     {
-        const RATE: f32 = 24000. / 256.;
         let mut sum_of_phoneme_length = 0;
         let mut count_of_f0 = 0;
         let mut vowel_indexes_index = 0;
 
         for (i, phoneme_length) in phoneme_length_list.iter().enumerate() {
-            let phoneme_length = (*phoneme_length * RATE / speed_scale).ceil() as usize;
+            let phoneme_length = to_frames(*phoneme_length, speed_scale);
             let phoneme_id = phoneme_data_list[i].phoneme_id();
+            if let (Some(gain), Some(phoneme_gain_list)) = (&mut gain, &phoneme_gain_list) {
+                gain.extend(std::iter::repeat(phoneme_gain_list[i]).take(phoneme_length));
+            }
 
             for _ in 0..phoneme_length {
                 let mut phonemes_vec = vec![0.; OjtPhoneme::num_phoneme()];
@@ -383,14 +620,95 @@ pub fn synthesis(
     // 2次元のvectorを1次元に変換し、アドレスを連続させる
     let flatten_phoneme = phoneme.into_iter().flatten().collect::<Vec<_>>();
 
-    decode(
-        session,
-        f0.len(),
-        OjtPhoneme::num_phoneme(),
+    DecoderInput {
+        phonemes: phoneme_data_list,
+        phoneme_lengths: phoneme_length_list,
         f0,
-        flatten_phoneme,
-        speaker_id,
+        phoneme: flatten_phoneme,
+        gain,
+    }
+}
+
+// synthesis()がdecoderに渡すフレーム数を、decodeせずに求める
+// 語尾上げはsynthesis()の既定どおり有効として数える。出力のサンプル数はこの256倍になる
+pub fn frame_count(audio_query: &AudioQueryModel, speed_scale: f32) -> usize {
+    let accent_phrases = adjust_interrogative_accent_phrases(audio_query.accent_phrases.clone());
+    let flatten_moras: Vec<MoraModel> = accent_phrases
+        .into_iter()
+        .flat_map(|accent_phrase| {
+            accent_phrase
+                .moras
+                .into_iter()
+                .chain(accent_phrase.pause_mora)
+        })
+        .collect();
+
+    create_phoneme_length_list(
+        &flatten_moras,
+        audio_query.pre_phoneme_length,
+        audio_query.post_phoneme_length,
     )
+    .into_iter()
+    .map(|phoneme_length| to_frames(phoneme_length, speed_scale))
+    .sum()
+}
+
+impl AudioQueryModel {
+    // 息継ぎ(pause_mora)ごとに分けたAudioQuery。順に合成してつなげれば、全体を一度に合成したものとほぼ同じ長さになる
+    // 息継ぎの無音は半分ずつ、前の後ろと次の前に置く。抑揚は分けた単位で平均を取るので、全体とは少し変わる
+    pub fn split_breath_groups(&self) -> Vec<AudioQueryModel> {
+        let group = |accent_phrases, pre_phoneme_length, post_phoneme_length| AudioQueryModel {
+            accent_phrases,
+            pre_phoneme_length,
+            post_phoneme_length,
+            kana: None,
+            ..*self
+        };
+        let mut groups = Vec::new();
+        let mut accent_phrases = Vec::new();
+        let mut pre_phoneme_length = self.pre_phoneme_length;
+        for accent_phrase in &self.accent_phrases {
+            let mut accent_phrase = accent_phrase.clone();
+            let pause_mora = accent_phrase.pause_mora.take();
+            accent_phrases.push(accent_phrase);
+            if let Some(pause_mora) = pause_mora {
+                let half = pause_mora.vowel_length / 2.;
+                groups.push(group(
+                    std::mem::take(&mut accent_phrases),
+                    pre_phoneme_length,
+                    half,
+                ));
+                pre_phoneme_length = half;
+            }
+        }
+        if !accent_phrases.is_empty() || groups.is_empty() {
+            groups.push(group(
+                accent_phrases,
+                pre_phoneme_length,
+                self.post_phoneme_length,
+            ));
+        }
+        groups
+    }
+}
+
+fn create_phoneme_length_list(
+    flatten_moras: &[MoraModel],
+    pre_phoneme_length: f32,
+    post_phoneme_length: f32,
+) -> Vec<f32> {
+    std::iter::once(pre_phoneme_length)
+        .chain(flatten_moras.iter().flat_map(|mora| {
+            mora.consonant_length
+                .into_iter()
+                .chain(std::iter::once(mora.vowel_length))
+        }))
+        .chain(std::iter::once(post_phoneme_length))
+        .collect()
+}
+
+fn to_frames(phoneme_length: f32, speed_scale: f32) -> usize {
+    (phoneme_length * FRAME_RATE / speed_scale).ceil() as usize
 }
 
 fn initial_process(accent_phrases: Vec<AccentPhraseModel>) -> (Vec<MoraModel>, Vec<OjtPhoneme>) {
@@ -550,5 +868,6 @@ fn make_interrogative_mora(last_mora: MoraModel) -> MoraModel {
         vowel: last_mora.vowel,
         vowel_length: FIX_VOWEL_LENGTH,
         pitch,
+        gain: last_mora.gain,
     }
 }
@@ -0,0 +1,210 @@
+// 結合テスト用の小さなダミーモデル
+// 本物と同じ入出力を持つONNXを書き出すので、大きなVOICEVOXのモデル無しでortを通した合成まで試せる
+// 出力は声にならない(音素長・音高は一定、波形はf0をなぞるだけ)
+
+use anyhow::Result;
+use std::{fs, path::Path};
+
+const IR_VERSION: u64 = 7;
+const OPSET_VERSION: u64 = 13;
+
+const FLOAT: u64 = 1;
+const INT64: u64 = 7;
+
+const PHONEME_LENGTH: f32 = 0.1;
+const PITCH: f32 = 5.5;
+const WAVE_GAIN: f32 = 0.01;
+
+const METAS: &str = r#"[{"name":"test","styles":[{"name":"normal","id":0}],"speaker_uuid":"00000000-0000-0000-0000-000000000000","version":"0.0.0"}]"#;
+
+// dir に predict_duration-0.onnx, predict_intonation-0.onnx, decode-0.onnx, metas.json を書き出す
+pub fn write(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("predict_duration-0.onnx"), predict_duration())?;
+    fs::write(dir.join("predict_intonation-0.onnx"), predict_intonation())?;
+    fs::write(dir.join("decode-0.onnx"), decode())?;
+    fs::write(dir.join("metas.json"), METAS)?;
+    Ok(())
+}
+
+// phoneme_length = phoneme_list * 0 + PHONEME_LENGTH
+fn predict_duration() -> Vec<u8> {
+    model(
+        Message::new()
+            .message(1, cast("phoneme_list", "x"))
+            .message(1, node("Mul", &["x", "zero"], "y"))
+            .message(
+                1,
+                node("Add", &["y", "phoneme_length_value"], "phoneme_length"),
+            )
+            .string(2, "predict_duration")
+            .message(5, float_scalar("zero", 0.))
+            .message(5, float_scalar("phoneme_length_value", PHONEME_LENGTH))
+            .message(
+                11,
+                value_info("phoneme_list", INT64, &[Dim::Param("length")]),
+            )
+            .message(11, value_info("speaker_id", INT64, &[Dim::Value(1)]))
+            .message(
+                12,
+                value_info("phoneme_length", FLOAT, &[Dim::Param("length")]),
+            ),
+    )
+}
+
+// f0_list = vowel_phoneme_list * 0 + PITCH
+fn predict_intonation() -> Vec<u8> {
+    let list = [Dim::Param("length")];
+    model(
+        Message::new()
+            .message(1, cast("vowel_phoneme_list", "x"))
+            .message(1, node("Mul", &["x", "zero"], "y"))
+            .message(1, node("Add", &["y", "pitch"], "f0_list"))
+            .string(2, "predict_intonation")
+            .message(5, float_scalar("zero", 0.))
+            .message(5, float_scalar("pitch", PITCH))
+            .message(11, value_info("length", INT64, &[]))
+            .message(11, value_info("vowel_phoneme_list", INT64, &list))
+            .message(11, value_info("consonant_phoneme_list", INT64, &list))
+            .message(11, value_info("start_accent_list", INT64, &list))
+            .message(11, value_info("end_accent_list", INT64, &list))
+            .message(11, value_info("start_accent_phrase_list", INT64, &list))
+            .message(11, value_info("end_accent_phrase_list", INT64, &list))
+            .message(11, value_info("speaker_id", INT64, &[Dim::Value(1)]))
+            .message(12, value_info("f0_list", FLOAT, &list)),
+    )
+}
+
+// wave = reshape(tile(f0, [1, 256]), [-1]) * WAVE_GAIN
+// 1フレームあたり256サンプルという長さの関係は本物と同じになる
+fn decode() -> Vec<u8> {
+    model(
+        Message::new()
+            .message(1, node("Tile", &["f0", "repeats"], "tiled"))
+            .message(1, node("Reshape", &["tiled", "shape"], "flatten"))
+            .message(1, node("Mul", &["flatten", "gain"], "wave"))
+            .string(2, "decode")
+            .message(5, int64_tensor("repeats", &[1, 256]))
+            .message(5, int64_tensor("shape", &[-1]))
+            .message(5, float_scalar("gain", WAVE_GAIN))
+            .message(
+                11,
+                value_info("f0", FLOAT, &[Dim::Param("length"), Dim::Value(1)]),
+            )
+            .message(
+                11,
+                value_info("phoneme", FLOAT, &[Dim::Param("length"), Dim::Value(45)]),
+            )
+            .message(11, value_info("speaker_id", INT64, &[Dim::Value(1)]))
+            .message(12, value_info("wave", FLOAT, &[Dim::Param("wave_length")])),
+    )
+}
+
+fn model(graph: Message) -> Vec<u8> {
+    Message::new()
+        .varint(1, IR_VERSION)
+        .string(2, "chibivox")
+        .message(7, graph)
+        .message(8, Message::new().string(1, "").varint(2, OPSET_VERSION))
+        .0
+}
+
+fn node(op_type: &str, inputs: &[&str], output: &str) -> Message {
+    inputs
+        .iter()
+        .fold(Message::new(), |node, input| node.string(1, input))
+        .string(2, output)
+        .string(3, output)
+        .string(4, op_type)
+}
+
+fn cast(input: &str, output: &str) -> Message {
+    // AttributeProto { name: "to", type: INT, i: FLOAT }
+    node("Cast", &[input], output).message(
+        5,
+        Message::new()
+            .string(1, "to")
+            .varint(20, 2)
+            .varint(3, FLOAT),
+    )
+}
+
+fn float_scalar(name: &str, value: f32) -> Message {
+    Message::new()
+        .varint(2, FLOAT)
+        .string(8, name)
+        .bytes(9, &value.to_le_bytes())
+}
+
+fn int64_tensor(name: &str, values: &[i64]) -> Message {
+    let raw_data: Vec<u8> = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    Message::new()
+        .varint(1, values.len() as u64)
+        .varint(2, INT64)
+        .string(8, name)
+        .bytes(9, &raw_data)
+}
+
+enum Dim {
+    Value(u64),
+    Param(&'static str),
+}
+
+fn value_info(name: &str, elem_type: u64, dims: &[Dim]) -> Message {
+    let shape = dims.iter().fold(Message::new(), |shape, dim| {
+        let dim = match dim {
+            Dim::Value(value) => Message::new().varint(1, *value),
+            Dim::Param(param) => Message::new().string(2, param),
+        };
+        shape.message(1, dim)
+    });
+    let tensor_type = Message::new().varint(1, elem_type).message(2, shape);
+    Message::new()
+        .string(1, name)
+        .message(2, Message::new().message(1, tensor_type))
+}
+
+// protobufの最低限のエンコーダ
+struct Message(Vec<u8>);
+
+impl Message {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn varint(mut self, field: u32, value: u64) -> Self {
+        self.key(field, 0);
+        self.write_varint(value);
+        self
+    }
+
+    fn bytes(mut self, field: u32, data: &[u8]) -> Self {
+        self.key(field, 2);
+        self.write_varint(data.len() as u64);
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    fn string(self, field: u32, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(self, field: u32, message: Message) -> Self {
+        self.bytes(field, &message.0)
+    }
+
+    fn key(&mut self, field: u32, wire_type: u64) {
+        self.write_varint((field as u64) << 3 | wire_type);
+    }
+
+    fn write_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+}
@@ -0,0 +1,14 @@
+use std::{num::NonZeroUsize, thread};
+
+// available_parallelismはLinuxではaffinityとcgroupのCPUクォータを考慮してくれるので、
+// コンテナに割り当てられた分だけを使うようになる
+pub fn available_cpus() -> usize {
+    thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+// ORTのintra-opスレッドと並行ジョブの積がCPU数を超えないようにする
+pub fn default_worker_count(intra_threads: usize) -> usize {
+    (available_cpus() / intra_threads.max(1)).max(1)
+}
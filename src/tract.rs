@@ -0,0 +1,61 @@
+// wasm featureでONNX Runtimeの代わりに使う推論。tractはRustだけで書かれているので、ブラウザでも動く
+// グラフの最適化は読み込むときに一度だけ行い、スレッドやデバイスの設定は持たない
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use tract_onnx::prelude::{
+    tvec, Framework, InferenceModelExt, IntoTValue, Tensor, TypedModel, TypedRunnableModel,
+};
+
+pub(crate) struct Session {
+    model: TypedRunnableModel<TypedModel>,
+    // tractの入出力は位置で決まるので、ORTと同じく名前で渡せるように覚えておく
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl Session {
+    pub fn from_bytes(model: &[u8]) -> Result<Self> {
+        let model = tract_onnx::onnx().model_for_read(&mut &*model)?;
+        let inputs = model
+            .input_outlets()?
+            .iter()
+            .map(|outlet| model.node(outlet.node).name.clone())
+            .collect();
+        let outputs = model
+            .output_outlets()?
+            .iter()
+            .map(|outlet| {
+                model
+                    .outlet_label(*outlet)
+                    .unwrap_or(&model.node(outlet.node).name)
+                    .to_string()
+            })
+            .collect();
+        let model = model.into_optimized()?.into_runnable()?;
+        Ok(Self {
+            model,
+            inputs,
+            outputs,
+        })
+    }
+
+    // 入力をすべて名前で渡し、outputの名前の出力をf32の列で返す
+    pub fn run(&self, inputs: Vec<(&str, Tensor)>, output: &str) -> Result<Vec<f32>> {
+        let mut inputs: HashMap<_, _> = inputs.into_iter().collect();
+        let mut values = tvec![];
+        for name in &self.inputs {
+            let tensor = inputs
+                .remove(name.as_str())
+                .ok_or(anyhow!("missing model input: {name}"))?;
+            values.push(tensor.into_tvalue());
+        }
+        let index = self
+            .outputs
+            .iter()
+            .position(|name| name == output)
+            .ok_or(anyhow!("missing model output: {output}"))?;
+        let outputs = self.model.run(values)?;
+        Ok(outputs[index].as_slice::<f32>()?.to_vec())
+    }
+}
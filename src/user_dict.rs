@@ -0,0 +1,237 @@
+// VOICEVOX ENGINEと同じ形のユーザー辞書。固有名詞などの読みとアクセントを登録する
+// 登録した語はテキスト解析の前に切り出し、jpreprocessを通さずに読みとアクセント型からアクセント句を作る
+// 保存するファイルもVOICEVOX ENGINEのuser_dict.jsonと同じく、uuidから語へのJSONのオブジェクト
+use crate::{kana_parser, synthesis_engine::AccentPhraseModel};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    ffi::OsString,
+    fmt, fs,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::Path,
+    time::SystemTime,
+};
+
+const MAX_PRIORITY: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserDictWord {
+    // テキスト中の表記
+    pub surface: String,
+    // カタカナの読み
+    pub pronunciation: String,
+    // アクセント核のモーラの位置。0なら平板
+    pub accent_type: usize,
+    // 0から10。同じ長さの表記が重なったときに、大きい方を使う
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+}
+
+fn default_priority() -> u32 {
+    5
+}
+
+impl UserDictWord {
+    pub fn new(surface: &str, pronunciation: &str, accent_type: usize) -> Self {
+        Self {
+            surface: surface.into(),
+            pronunciation: pronunciation.into(),
+            accent_type,
+            priority: default_priority(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), UserDictError> {
+        let invalid = |reason: String| Err(UserDictError::InvalidWord(reason));
+        if self.surface.trim().is_empty() {
+            return invalid("surface must not be empty".into());
+        }
+        if self.surface.chars().any(char::is_control) {
+            return invalid(format!(
+                "surface {:?} contains a control character",
+                self.surface
+            ));
+        }
+        // 記号を許すと、AquesTalk風記法のアクセントや区切りとして読まれてしまう
+        if !self
+            .pronunciation
+            .chars()
+            .all(|c| matches!(c, 'ァ'..='ヴ' | 'ー'))
+        {
+            return invalid(format!(
+                "pronunciation {:?} must be in katakana",
+                self.pronunciation
+            ));
+        }
+        let moras = match kana_parser::parse_word(&self.pronunciation, 0) {
+            Ok(accent_phrase) => accent_phrase.moras.len(),
+            Err(_) => {
+                return invalid(format!(
+                    "pronunciation {:?} cannot be read",
+                    self.pronunciation
+                ))
+            }
+        };
+        if self.accent_type > moras {
+            return invalid(format!(
+                "accent_type {} is beyond the {moras} moras of {:?}",
+                self.accent_type, self.pronunciation
+            ));
+        }
+        if self.priority > MAX_PRIORITY {
+            return invalid(format!("priority must be at most {MAX_PRIORITY}"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_accent_phrase(&self) -> Result<AccentPhraseModel> {
+        kana_parser::parse_word(&self.pronunciation, self.accent_type)
+    }
+}
+
+#[derive(Debug)]
+pub enum UserDictError {
+    WordNotFound(String),
+    InvalidWord(String),
+}
+
+impl fmt::Display for UserDictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WordNotFound(uuid) => write!(f, "no user dictionary word {uuid}"),
+            Self::InvalidWord(reason) => write!(f, "invalid user dictionary word: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for UserDictError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserDict {
+    words: BTreeMap<String, UserDictWord>,
+}
+
+// テキストを、ユーザー辞書の語とそれ以外に分けたもの
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Word(&'a UserDictWord),
+}
+
+impl UserDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // ファイルが無ければ空
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let user_dict: Self = serde_json::from_str(&json)?;
+        for word in user_dict.words.values() {
+            word.validate()?;
+        }
+        Ok(user_dict)
+    }
+
+    // 書いている途中で落ちても前のものが残るように、別名で書いてから置き換える
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = OsString::from(path);
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn words(&self) -> &BTreeMap<String, UserDictWord> {
+        &self.words
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // 振ったuuidを返す
+    pub fn add_word(&mut self, word: UserDictWord) -> Result<String, UserDictError> {
+        word.validate()?;
+        let uuid = new_uuid();
+        self.words.insert(uuid.clone(), word);
+        Ok(uuid)
+    }
+
+    pub fn update_word(&mut self, uuid: &str, word: UserDictWord) -> Result<(), UserDictError> {
+        word.validate()?;
+        let Some(slot) = self.words.get_mut(uuid) else {
+            return Err(UserDictError::WordNotFound(uuid.into()));
+        };
+        *slot = word;
+        Ok(())
+    }
+
+    pub fn remove_word(&mut self, uuid: &str) -> Result<UserDictWord, UserDictError> {
+        self.words
+            .remove(uuid)
+            .ok_or_else(|| UserDictError::WordNotFound(uuid.into()))
+    }
+
+    // 先頭から、その位置で始まる最も長い語を切り出す。同じ長さならpriorityの大きい方
+    pub(crate) fn split<'a>(&'a self, text: &'a str) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let (mut start, mut i) = (0, 0);
+        while let Some(c) = text[i..].chars().next() {
+            let word = self
+                .words
+                .values()
+                .filter(|word| text[i..].starts_with(&word.surface))
+                .max_by_key(|word| (word.surface.len(), word.priority));
+            let Some(word) = word else {
+                i += c.len_utf8();
+                continue;
+            };
+            if start < i {
+                segments.push(Segment::Text(&text[start..i]));
+            }
+            segments.push(Segment::Word(word));
+            i += word.surface.len();
+            start = i;
+        }
+        if start < text.len() {
+            segments.push(Segment::Text(&text[start..]));
+        }
+        segments
+    }
+}
+
+// uuidのクレートに頼らず、OSの乱数で初期化されるRandomStateから作るversion 4のUUID
+fn new_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let mut bytes = [0; 16];
+    for chunk in bytes.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
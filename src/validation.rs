@@ -0,0 +1,265 @@
+use crate::{
+    acoustic_feature_extractor::OjtPhoneme,
+    inference::PHONEME_LENGTH_MINIMAL,
+    publish::SAMPLING_RATE,
+    synthesis_engine::{AccentPhraseModel, AudioQueryModel, MORA_PHONEME_LIST},
+};
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+// 値の範囲。倍率はCLIのProsodyArgsと同じにする
+// 外から受け取った値で、フレーム数や再サンプリング先の大きさが際限なく増えないように、長さとサンプリングレートにも上限を設ける
+const SPEED_SCALE_RANGE: (f32, f32) = (0.5, 2.);
+const PITCH_SCALE_RANGE: (f32, f32) = (-0.15, 0.15);
+const INTONATION_SCALE_RANGE: (f32, f32) = (0., 2.);
+const VOLUME_SCALE_RANGE: (f32, f32) = (0., 2.);
+// 秒
+const MAX_PHONEME_LENGTH: f32 = 10.;
+const MAX_OUTPUT_SAMPLING_RATE: u32 = 192000;
+
+// 外部から受け取ったAudioQueryの扱い方
+// Strictは不正な値があれば拒否し、Lenientは直せるものは直して警告を返す
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+impl AudioQueryModel {
+    // 手で編集されたAudioQueryが、予測し直さずにそのまま合成できるかを確かめる
+    pub fn validate(&self) -> Result<()> {
+        self.clone()
+            .validate_with_mode(ValidationMode::Strict)
+            .map(|_| ())
+    }
+
+    pub fn validate_with_mode(&mut self, mode: ValidationMode) -> Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        let mut report = |message: String| match mode {
+            ValidationMode::Strict => Err(anyhow!(message)),
+            ValidationMode::Lenient => {
+                warnings.push(message);
+                Ok(())
+            }
+        };
+
+        check_range(
+            &mut report,
+            "speedScale",
+            &mut self.speed_scale,
+            SPEED_SCALE_RANGE,
+            1.,
+        )?;
+        check_range(
+            &mut report,
+            "pitchScale",
+            &mut self.pitch_scale,
+            PITCH_SCALE_RANGE,
+            0.,
+        )?;
+        check_range(
+            &mut report,
+            "intonationScale",
+            &mut self.intonation_scale,
+            INTONATION_SCALE_RANGE,
+            1.,
+        )?;
+        check_range(
+            &mut report,
+            "volumeScale",
+            &mut self.volume_scale,
+            VOLUME_SCALE_RANGE,
+            1.,
+        )?;
+        check_range(
+            &mut report,
+            "prePhonemeLength",
+            &mut self.pre_phoneme_length,
+            (0., MAX_PHONEME_LENGTH),
+            0.,
+        )?;
+        check_range(
+            &mut report,
+            "postPhonemeLength",
+            &mut self.post_phoneme_length,
+            (0., MAX_PHONEME_LENGTH),
+            0.,
+        )?;
+        if self.output_sampling_rate == 0 {
+            report("outputSamplingRate must be positive".into())?;
+            self.output_sampling_rate = SAMPLING_RATE;
+        } else if self.output_sampling_rate > MAX_OUTPUT_SAMPLING_RATE {
+            report(format!(
+                "outputSamplingRate must be at most {MAX_OUTPUT_SAMPLING_RATE}: {}",
+                self.output_sampling_rate
+            ))?;
+            self.output_sampling_rate = MAX_OUTPUT_SAMPLING_RATE;
+        }
+
+        // 音素が不明なものは直しようがないので、モードによらず拒否する
+        check_phonemes(&self.accent_phrases)?;
+        for (i, accent_phrase) in self.accent_phrases.iter_mut().enumerate() {
+            let mora_count = accent_phrase.moras.len();
+            if !(1..=mora_count).contains(&accent_phrase.accent) {
+                report(format!(
+                    "accent_phrases[{i}]: accent {} is out of range 1..={mora_count}",
+                    accent_phrase.accent
+                ))?;
+                accent_phrase.accent = accent_phrase.accent.clamp(1, mora_count);
+            }
+
+            let moras = accent_phrase
+                .moras
+                .iter_mut()
+                .enumerate()
+                .map(|(j, mora)| (format!("accent_phrases[{i}].moras[{j}]"), mora))
+                .chain(
+                    accent_phrase
+                        .pause_mora
+                        .iter_mut()
+                        .map(|mora| (format!("accent_phrases[{i}].pause_mora"), mora)),
+                );
+            for (location, mora) in moras {
+                if !is_positive(mora.vowel_length) {
+                    report(format!("{location}: vowel_length must be positive"))?;
+                    mora.vowel_length = PHONEME_LENGTH_MINIMAL;
+                } else if mora.vowel_length > MAX_PHONEME_LENGTH {
+                    report(format!(
+                        "{location}: vowel_length must be at most {MAX_PHONEME_LENGTH}"
+                    ))?;
+                    mora.vowel_length = MAX_PHONEME_LENGTH;
+                }
+                match (&mora.consonant, mora.consonant_length) {
+                    (Some(_), Some(consonant_length))
+                        if is_positive(consonant_length)
+                            && consonant_length <= MAX_PHONEME_LENGTH => {}
+                    (Some(_), Some(consonant_length)) if is_positive(consonant_length) => {
+                        report(format!(
+                            "{location}: consonant_length must be at most {MAX_PHONEME_LENGTH}"
+                        ))?;
+                        mora.consonant_length = Some(MAX_PHONEME_LENGTH);
+                    }
+                    (Some(_), _) => {
+                        report(format!("{location}: consonant_length must be positive"))?;
+                        mora.consonant_length = Some(PHONEME_LENGTH_MINIMAL);
+                    }
+                    (None, Some(_)) => {
+                        report(format!("{location}: consonant_length without consonant"))?;
+                        mora.consonant_length = None;
+                    }
+                    (None, None) => {}
+                }
+                if !is_non_negative(mora.pitch) {
+                    report(format!(
+                        "{location}: pitch must be a finite non-negative value"
+                    ))?;
+                    mora.pitch = 0.;
+                }
+                if mora.gain.is_some_and(|gain| !is_non_negative(gain)) {
+                    report(format!(
+                        "{location}: gain must be a finite non-negative value"
+                    ))?;
+                    mora.gain = None;
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+// アクセント句が、予測や合成に渡せる音素でできていない
+#[derive(Debug)]
+pub struct InvalidAccentPhraseError {
+    // accent_phrases[0].moras[1] の形式
+    pub location: String,
+    pub reason: InvalidAccentPhraseReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidAccentPhraseReason {
+    NoMoras,
+    UnknownVowel(String),
+    UnknownConsonant(String),
+}
+
+impl fmt::Display for InvalidAccentPhraseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.location)?;
+        match &self.reason {
+            InvalidAccentPhraseReason::NoMoras => write!(f, "no moras"),
+            InvalidAccentPhraseReason::UnknownVowel(vowel) => write!(f, "unknown vowel {vowel:?}"),
+            InvalidAccentPhraseReason::UnknownConsonant(consonant) => {
+                write!(f, "unknown consonant {consonant:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidAccentPhraseError {}
+
+pub(crate) fn check_phonemes(
+    accent_phrases: &[AccentPhraseModel],
+) -> Result<(), InvalidAccentPhraseError> {
+    for (i, accent_phrase) in accent_phrases.iter().enumerate() {
+        if accent_phrase.moras.is_empty() {
+            return Err(InvalidAccentPhraseError {
+                location: format!("accent_phrases[{i}]"),
+                reason: InvalidAccentPhraseReason::NoMoras,
+            });
+        }
+        let moras = accent_phrase
+            .moras
+            .iter()
+            .enumerate()
+            .map(|(j, mora)| (format!("accent_phrases[{i}].moras[{j}]"), mora))
+            .chain(
+                accent_phrase
+                    .pause_mora
+                    .iter()
+                    .map(|mora| (format!("accent_phrases[{i}].pause_mora"), mora)),
+            );
+        for (location, mora) in moras {
+            let reason = if !MORA_PHONEME_LIST.contains(&mora.vowel.as_str()) {
+                InvalidAccentPhraseReason::UnknownVowel(mora.vowel.clone())
+            } else if let Some(consonant) = mora
+                .consonant
+                .as_ref()
+                .filter(|consonant| !OjtPhoneme::contains(consonant))
+            {
+                InvalidAccentPhraseReason::UnknownConsonant(consonant.clone())
+            } else {
+                continue;
+            };
+            return Err(InvalidAccentPhraseError { location, reason });
+        }
+    }
+    Ok(())
+}
+
+// 有限でなければdefaultに、範囲外なら端に寄せる
+fn check_range(
+    report: &mut impl FnMut(String) -> Result<()>,
+    name: &str,
+    value: &mut f32,
+    (min, max): (f32, f32),
+    default: f32,
+) -> Result<()> {
+    if !value.is_finite() {
+        report(format!("{name} must be finite: {value}"))?;
+        *value = default;
+    } else if !(min..=max).contains(value) {
+        report(format!("{name} must be between {min} and {max}: {value}"))?;
+        *value = value.clamp(min, max);
+    }
+    Ok(())
+}
+
+fn is_positive(value: f32) -> bool {
+    value.is_finite() && value > 0.
+}
+
+fn is_non_negative(value: f32) -> bool {
+    value.is_finite() && value >= 0.
+}
@@ -0,0 +1,148 @@
+// 録音の長さと抑揚をそのまま使い、別の話者で言い直す(実験的)
+// 音素の区切りは外部のアライナ(juliusのsegmentation-kitなど)が書き出した.labで受け取り、
+// f0は録音から自己相関で求めて、decoderにフレーム単位で渡す
+
+use crate::{
+    acoustic_feature_extractor::OjtPhoneme, pitch_shift, synthesis_engine::FRAME_RATE, AudioBuffer,
+    SAMPLING_RATE,
+};
+use anyhow::{anyhow, bail, Result};
+use std::f32::consts::LN_2;
+
+// .labの一行。時刻は秒
+struct Segment {
+    start: f32,
+    end: f32,
+    phoneme: String,
+}
+
+// decoderに渡すフレームごとの音素IDと対数f0
+// semitonesだけ音高をずらして、録音した人の声域を話者の声域に合わせる
+pub(crate) fn frame_inputs(
+    recording: &AudioBuffer,
+    lab: &str,
+    semitones: f32,
+) -> Result<(Vec<i64>, Vec<f32>)> {
+    let segments = parse_lab(lab)?;
+    let Some(last) = segments.last() else {
+        bail!("lab contains no phonemes");
+    };
+    let frames = to_frame(last.end);
+
+    let space = OjtPhoneme {
+        phoneme: OjtPhoneme::space_phoneme(),
+    };
+    let mut phoneme_ids = vec![space.phoneme_id(); frames];
+    let mut voiced = vec![false; frames];
+    for segment in &segments {
+        let range = to_frame(segment.start).min(frames)..to_frame(segment.end).min(frames);
+        let phoneme = OjtPhoneme {
+            phoneme: segment.phoneme.clone(),
+        };
+        phoneme_ids[range.clone()].fill(phoneme.phoneme_id());
+        voiced[range].fill(is_voiced(&segment.phoneme));
+    }
+
+    let wave = recording.to_mono().resample(SAMPLING_RATE).samples;
+    let periods = pitch_shift::periods(&wave, SAMPLING_RATE);
+    let log_f0: Vec<Option<f32>> = (0..frames)
+        .map(|i| {
+            let period = periods.get(i).copied().flatten()?;
+            Some((SAMPLING_RATE as f32 / period as f32).ln())
+        })
+        .collect();
+    let log_f0 =
+        interpolate(&log_f0).ok_or_else(|| anyhow!("no pitch was detected in the recording"))?;
+
+    // 無音や促音、無声化した母音では声帯が鳴っていないので0にする
+    let shift = semitones / 12. * LN_2;
+    let f0 = log_f0
+        .into_iter()
+        .zip(&voiced)
+        .map(|(log_f0, &voiced)| if voiced { log_f0 + shift } else { 0. })
+        .collect();
+    Ok((phoneme_ids, f0))
+}
+
+// 時刻はsegmentation-kitのような秒単位と、HTKの100ナノ秒単位のどちらも受け付ける
+fn parse_lab(lab: &str) -> Result<Vec<Segment>> {
+    lab.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let line_number = i + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [start, end, phoneme] = fields[..] else {
+                bail!("lab line {line_number}: expected `start end phoneme`");
+            };
+            let time = |s: &str| -> Result<f32> {
+                let time: f64 = s
+                    .parse()
+                    .map_err(|_| anyhow!("lab line {line_number}: invalid time {s:?}"))?;
+                let seconds = if s.contains('.') { time } else { time / 1e7 };
+                Ok(seconds as f32)
+            };
+            let segment = Segment {
+                start: time(start)?,
+                end: time(end)?,
+                phoneme: normalize_phoneme(phoneme).ok_or_else(|| {
+                    anyhow!("lab line {line_number}: unknown phoneme {phoneme:?}")
+                })?,
+            };
+            if segment.end < segment.start {
+                bail!("lab line {line_number}: end is before start");
+            }
+            Ok(segment)
+        })
+        .collect()
+}
+
+// アライナの音素名をOjtPhonemeの名前にそろえる
+fn normalize_phoneme(phoneme: &str) -> Option<String> {
+    let phoneme = match phoneme {
+        "silB" | "silE" | "sil" | "sp" => OjtPhoneme::space_phoneme(),
+        "q" => "cl".into(),
+        _ => phoneme.into(),
+    };
+    OjtPhoneme::contains(&phoneme).then_some(phoneme)
+}
+
+fn is_voiced(phoneme: &str) -> bool {
+    !matches!(phoneme, "pau" | "cl" | "A" | "I" | "U" | "E" | "O")
+}
+
+fn to_frame(seconds: f32) -> usize {
+    (seconds * FRAME_RATE).round() as usize
+}
+
+// 検出できなかったフレームを前後の値から線形補間で埋める。一つも無ければNone
+fn interpolate(values: &[Option<f32>]) -> Option<Vec<f32>> {
+    let known: Vec<(usize, f32)> = values
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| Some((i, (*value)?)))
+        .collect();
+    let (&(first_index, first), &(last_index, last)) = (known.first()?, known.last()?);
+
+    let mut next = 0;
+    Some(
+        (0..values.len())
+            .map(|i| {
+                if i <= first_index {
+                    return first;
+                }
+                if i >= last_index {
+                    return last;
+                }
+                while known[next].0 < i {
+                    next += 1;
+                }
+                let (after_index, after) = known[next];
+                let (before_index, before) = known[next - 1];
+                before
+                    + (after - before) * (i - before_index) as f32
+                        / (after_index - before_index) as f32
+            })
+            .collect(),
+    )
+}
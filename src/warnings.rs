@@ -0,0 +1,96 @@
+use crate::audio_buffer::AudioBuffer;
+use serde::Serialize;
+use std::fmt;
+
+// 合成は続けられたが、結果が意図どおりでないかもしれないこと
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Warning {
+    // 辞書に無く、読みが分からなかった語
+    UnknownWord { surface: String },
+    // ラベルが扱えるモーラ数を超えたため、途中で切ったアクセント句。textは残した部分
+    TruncatedPhrase { text: String },
+    // 予測された長さが短すぎたため、下限に引き上げた音素
+    ClampedDuration { phoneme: String, predicted: f32 },
+    // [-1, 1]を超え、書き出すときに切り詰められるサンプル
+    ClippedSamples { count: usize, peak: f32 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownWord { surface } => write!(f, "unknown word: {surface:?}"),
+            Self::TruncatedPhrase { text } => {
+                write!(f, "accent phrase is too long and was cut after {text:?}")
+            }
+            Self::ClampedDuration { phoneme, predicted } => write!(
+                f,
+                "predicted length of {phoneme:?} ({predicted}s) was raised to the minimum"
+            ),
+            Self::ClippedSamples { count, peak } => {
+                write!(f, "{count} samples clipped (peak {peak:.2})")
+            }
+        }
+    }
+}
+
+// 解析から合成までの各段階が、処理を止めずに積んでいく警告
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Warnings(Vec<Warning>);
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        self.0.push(warning);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+        self.0.iter()
+    }
+
+    // 音量や音高を変えた後の最終的な波形について調べる
+    pub fn check_clipping(&mut self, audio: &AudioBuffer) {
+        let clipped = audio.samples.iter().filter(|sample| sample.abs() > 1.);
+        let count = clipped.clone().count();
+        if count > 0 {
+            let peak = clipped.fold(0., |peak: f32, sample| peak.max(sample.abs()));
+            self.push(Warning::ClippedSamples { count, peak });
+        }
+    }
+}
+
+impl Extend<Warning> for Warnings {
+    fn extend<T: IntoIterator<Item = Warning>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+    type Item = &'a Warning;
+    type IntoIter = std::slice::Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
@@ -0,0 +1,66 @@
+// ブラウザ向けのJavaScript API。モデルとmetas.jsonはfetchなどで読み込んだものを渡す
+// wasm-pack build --target web -- --no-default-features --features wasm
+
+use crate::{wav::SampleFormat, AudioQueryModel, InitializeOptions, VoicevoxCore};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct Chibivox(VoicevoxCore);
+
+#[wasm_bindgen]
+impl Chibivox {
+    // 予測モデルは無ければ規則で代用する
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        decode: &[u8],
+        predict_duration: Option<Vec<u8>>,
+        predict_intonation: Option<Vec<u8>>,
+        metas_json: &str,
+        acknowledge_terms: bool,
+    ) -> Result<Chibivox, JsError> {
+        let options = InitializeOptions {
+            acknowledge_terms,
+            ..Default::default()
+        };
+        let core = VoicevoxCore::from_bytes(
+            decode,
+            predict_duration.as_deref(),
+            predict_intonation.as_deref(),
+            metas_json,
+            &options,
+        )
+        .map_err(js_error)?;
+        Ok(Self(core))
+    }
+
+    // metas.jsonと同じ形のJSON
+    pub fn metas(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(self.0.metas())?)
+    }
+
+    // VOICEVOX ENGINEと同じ形のAudioQueryのJSON
+    pub fn audio_query(&self, text: &str, speaker_id: u32) -> Result<String, JsError> {
+        let audio_query = self.0.audio_query(text, speaker_id).map_err(js_error)?;
+        Ok(serde_json::to_string(&audio_query)?)
+    }
+
+    // 16bitのWAV。new Blob([wav], {type: "audio/wav"})でそのまま鳴らせる
+    pub fn synthesis(&self, audio_query_json: &str, speaker_id: u32) -> Result<Vec<u8>, JsError> {
+        let audio_query: AudioQueryModel = serde_json::from_str(audio_query_json)?;
+        let wav = self
+            .0
+            .synthesis(&audio_query, speaker_id)
+            .map_err(js_error)?;
+        Ok(wav.to_wav(SampleFormat::I16))
+    }
+
+    pub fn tts(&self, text: &str, speaker_id: u32) -> Result<Vec<u8>, JsError> {
+        let wav = self.0.tts(text, speaker_id).map_err(js_error)?;
+        Ok(wav.to_wav(SampleFormat::I16))
+    }
+}
+
+// anyhow::Errorはstd::error::Errorを実装しないので、原因まで含めた文字列にする
+fn js_error(err: anyhow::Error) -> JsError {
+    JsError::new(&format!("{err:#}"))
+}
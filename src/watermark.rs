@@ -0,0 +1,107 @@
+// 合成した音声であることを後から確かめるための透かし
+// 鍵から作った±1の系列を、その場の音量に比例した小さな振幅で重ねる(スペクトル拡散)
+// 無音の部分には足さないので、ノイズとして聞こえにくい
+// 先頭からの位置で照合するため、切り取りやリサンプリングをした音声からは検出できない
+
+use crate::AudioBuffer;
+
+// 音量を測る区間。decoderの1フレームと同じ
+const FRAME_SIZE: usize = 256;
+// その場の音量に対する透かしの大きさ(-40dB)
+const STRENGTH: f32 = 0.01;
+// detectの値がこれを超えれば透かしがあるとみなす。透かしが無ければ概ね±2に収まる
+pub const DETECTION_THRESHOLD: f32 = 5.;
+
+pub fn embed(audio: &AudioBuffer, key: &str) -> AudioBuffer {
+    Embedder::new(key).embed(audio)
+}
+
+// 少しずつ送り出す音声に、つなげたものに一度にembedしたのと同じ透かしを入れる
+// 区切りが256サンプルの倍数でなければ、区切りの前後で音量の測り方だけが変わる
+pub struct Embedder(Chips);
+
+impl Embedder {
+    pub fn new(key: &str) -> Self {
+        Self(Chips::new(key))
+    }
+
+    // 前回までに渡した音声の続きとして透かしを入れる
+    pub fn embed(&mut self, audio: &AudioBuffer) -> AudioBuffer {
+        let channels = audio.channels.max(1) as usize;
+        let mark = mark_with(&audio.to_mono().samples, &mut self.0);
+        let samples = audio
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| sample + STRENGTH * mark[i / channels])
+            .collect();
+        AudioBuffer {
+            samples,
+            ..audio.clone()
+        }
+    }
+}
+
+// 透かしとの相関を、透かしが無い場合の標準偏差を単位にして返す
+pub fn detect(audio: &AudioBuffer, key: &str) -> f32 {
+    let samples = audio.to_mono().samples;
+    if samples.len() < 2 {
+        return 0.;
+    }
+    // 音声の大部分は低い帯域にあるので、差分を取って透かしが目立つ高い帯域で比べる
+    let mark = mark(&samples, key);
+    let (mut dot, mut signal_power, mut mark_power) = (0f64, 0f64, 0f64);
+    for n in 1..samples.len() {
+        let signal = (samples[n] - samples[n - 1]) as f64;
+        let mark = (mark[n] - mark[n - 1]) as f64;
+        dot += signal * mark;
+        signal_power += signal * signal;
+        mark_power += mark * mark;
+    }
+    if signal_power == 0. || mark_power == 0. {
+        return 0.;
+    }
+    (dot / (signal_power * mark_power).sqrt() * ((samples.len() - 1) as f64).sqrt()) as f32
+}
+
+// 音量で重み付けした±1の系列。透かしを足しても音量はほとんど変わらないので、検出側でも同じものが作れる
+fn mark(samples: &[f32], key: &str) -> Vec<f32> {
+    mark_with(samples, &mut Chips::new(key))
+}
+
+fn mark_with(samples: &[f32], chips: &mut Chips) -> Vec<f32> {
+    samples
+        .chunks(FRAME_SIZE)
+        .flat_map(|frame| {
+            let rms = (frame.iter().map(|sample| sample * sample).sum::<f32>()
+                / frame.len() as f32)
+                .sqrt();
+            frame.iter().map(|_| rms).collect::<Vec<_>>()
+        })
+        .map(|rms| rms * chips.next())
+        .collect()
+}
+
+// 鍵をFNV-1aで種にしたxorshift
+struct Chips(u64);
+
+impl Chips {
+    fn new(key: &str) -> Self {
+        let seed = key.bytes().fold(0xcbf29ce484222325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        // 0だとずっと0のままになる
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        if self.0 & 1 == 0 {
+            1.
+        } else {
+            -1.
+        }
+    }
+}
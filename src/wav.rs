@@ -0,0 +1,257 @@
+// WAVとraw PCMの読み書き
+// ホストのエンディアンによらず、常にリトルエンディアンで書き出す
+
+use crate::AudioBuffer;
+use anyhow::{bail, Result};
+use std::io::{self, Seek, SeekFrom, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    I16,
+    I24,
+    F32,
+}
+
+impl SampleFormat {
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            Self::I16 => 16,
+            Self::I24 => 24,
+            Self::F32 => 32,
+        }
+    }
+
+    fn audio_format(self) -> u16 {
+        match self {
+            Self::I16 | Self::I24 => 1,
+            Self::F32 => 3,
+        }
+    }
+}
+
+pub const WAV_HEADER_SIZE: usize = 44;
+// 長さが分からないまま送り出す場合のdataチャンクの大きさ
+pub const STREAMING_DATA_SIZE: u32 = u32::MAX - 36;
+
+pub fn wav_header(
+    sample_rate: u32,
+    channels: u16,
+    format: SampleFormat,
+    data_size: u32,
+) -> [u8; WAV_HEADER_SIZE] {
+    let block_align = channels * format.bits_per_sample() / 8;
+    let byte_rate = sample_rate * block_align as u32;
+
+    let mut header = [0; WAV_HEADER_SIZE];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data_size).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&format.audio_format().to_le_bytes());
+    header[22..24].copy_from_slice(&channels.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&byte_rate.to_le_bytes());
+    header[32..34].copy_from_slice(&block_align.to_le_bytes());
+    header[34..36].copy_from_slice(&format.bits_per_sample().to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+// raw PCMとしての書き出しにもそのまま使える
+pub fn encode_samples(samples: &[f32], format: SampleFormat) -> Vec<u8> {
+    match format {
+        SampleFormat::I16 => samples
+            .iter()
+            .flat_map(|sample| {
+                let sample = (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i16;
+                sample.to_le_bytes()
+            })
+            .collect(),
+        SampleFormat::I24 => samples
+            .iter()
+            .flat_map(|sample| {
+                let sample = (sample.clamp(-1., 1.) * 8388607.).round() as i32;
+                let [b0, b1, b2, _] = sample.to_le_bytes();
+                [b0, b1, b2]
+            })
+            .collect(),
+        SampleFormat::F32 => samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect(),
+    }
+}
+
+pub fn encode_wav(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    format: SampleFormat,
+) -> Vec<u8> {
+    let data = encode_samples(samples, format);
+    let mut wav = Vec::with_capacity(WAV_HEADER_SIZE + data.len());
+    wav.extend_from_slice(&wav_header(
+        sample_rate,
+        channels,
+        format,
+        data.len() as u32,
+    ));
+    wav.extend_from_slice(&data);
+    wav
+}
+
+// クレジット表記などを入れるLIST/INFOチャンク。ICMT(コメント)として書く
+pub fn info_chunk(comment: &str) -> Vec<u8> {
+    // 文字列はNUL終端で、チャンクの大きさは偶数に揃える
+    let mut text = comment.as_bytes().to_vec();
+    text.push(0);
+    if text.len() % 2 == 1 {
+        text.push(0);
+    }
+    let mut chunk = Vec::with_capacity(20 + text.len());
+    chunk.extend_from_slice(b"LIST");
+    chunk.extend_from_slice(&(12 + text.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(b"INFO");
+    chunk.extend_from_slice(b"ICMT");
+    chunk.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&text);
+    chunk
+}
+
+// encode_wavの結果のdataの後ろにチャンクを足し、RIFFの大きさを直す
+pub fn append_chunk(wav: &mut Vec<u8>, chunk: &[u8]) {
+    wav.extend_from_slice(chunk);
+    let riff_size = wav.len() as u32 - 8;
+    wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+}
+
+// サンプルを受け取ったそばから書き出すWAVライタ
+// Seekできる書き出し先ならfinalizeでヘッダの長さを直し、そうでなければ長さ不明のまま流す
+pub struct WavWriter<W: Write> {
+    writer: W,
+    sample_rate: u32,
+    channels: u16,
+    format: SampleFormat,
+    data_size: u32,
+}
+
+impl<W: Write> WavWriter<W> {
+    pub fn new(
+        mut writer: W,
+        sample_rate: u32,
+        channels: u16,
+        format: SampleFormat,
+    ) -> io::Result<Self> {
+        writer.write_all(&wav_header(
+            sample_rate,
+            channels,
+            format,
+            STREAMING_DATA_SIZE,
+        ))?;
+        Ok(Self {
+            writer,
+            sample_rate,
+            channels,
+            format,
+            data_size: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let data = encode_samples(samples, self.format);
+        self.writer.write_all(&data)?;
+        self.data_size += data.len() as u32;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn finalize(self) -> io::Result<W> {
+        self.finalize_with_chunk(&[])
+    }
+
+    // dataの後ろにinfo_chunkなどを足して閉じる
+    // 長さ不明のまま流す場合はdataの続きとして読まれてしまうので、Seekできる場合に限る
+    pub fn finalize_with_chunk(mut self, chunk: &[u8]) -> io::Result<W> {
+        self.writer.write_all(chunk)?;
+        let mut header = wav_header(self.sample_rate, self.channels, self.format, self.data_size);
+        header[4..8].copy_from_slice(&(36 + self.data_size + chunk.len() as u32).to_le_bytes());
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&header)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.into_inner()
+    }
+}
+
+// 16/24/32bit整数とfloat32のPCMだけを読む。fmtとdata以外のチャンクは読み飛ばす
+pub fn decode_wav(bytes: &[u8]) -> Result<AudioBuffer> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        bail!("not a WAV file");
+    }
+
+    let mut fmt = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let size = u32::from_le_bytes(bytes[position + 4..position + 8].try_into()?) as usize;
+        // 長さ不明のまま書き出されたdataチャンクは、ファイルの終わりまでとみなす
+        let body = &bytes[position + 8..(position + 8).saturating_add(size).min(bytes.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+                let audio_format = u16_at(0);
+                let channels = u16_at(2);
+                let sample_rate = u32::from_le_bytes(body[4..8].try_into()?);
+                let bits_per_sample = u16_at(14);
+                // WAVE_FORMAT_EXTENSIBLEは中身の形式を見る
+                let audio_format = if audio_format == 0xfffe && body.len() >= 26 {
+                    u16_at(24)
+                } else {
+                    audio_format
+                };
+                if !matches!((audio_format, bits_per_sample), (1, 16 | 24 | 32) | (3, 32)) {
+                    bail!("unsupported WAV format: format {audio_format}, {bits_per_sample} bits");
+                }
+                if channels == 0 {
+                    bail!("WAV file has no channels");
+                }
+                fmt = Some((audio_format == 3, bits_per_sample, channels, sample_rate));
+            }
+            b"data" => {
+                let Some((float, bits_per_sample, channels, sample_rate)) = fmt else {
+                    bail!("WAV data chunk appears before fmt chunk");
+                };
+                return Ok(AudioBuffer {
+                    samples: decode_samples(body, float, bits_per_sample),
+                    sample_rate,
+                    channels,
+                });
+            }
+            _ => {}
+        }
+        // チャンクは偶数バイト境界に揃えられている
+        position += 8 + size + size % 2;
+    }
+    bail!("WAV file has no data chunk")
+}
+
+fn decode_samples(data: &[u8], float: bool, bits_per_sample: u16) -> Vec<f32> {
+    let bytes_per_sample = bits_per_sample as usize / 8;
+    data.chunks_exact(bytes_per_sample)
+        .map(|sample| match (float, sample) {
+            (true, &[b0, b1, b2, b3]) => f32::from_le_bytes([b0, b1, b2, b3]),
+            (false, &[b0, b1]) => i16::from_le_bytes([b0, b1]) as f32 / 32768.,
+            // 上位バイトに詰めてから符号を保ったまま右に戻す
+            (false, &[b0, b1, b2]) => (i32::from_le_bytes([0, b0, b1, b2]) >> 8) as f32 / 8388608.,
+            (false, &[b0, b1, b2, b3]) => i32::from_le_bytes([b0, b1, b2, b3]) as f32 / 2147483648.,
+            _ => unreachable!(),
+        })
+        .collect()
+}
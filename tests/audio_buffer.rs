@@ -0,0 +1,58 @@
+use chibivox::AudioBuffer;
+
+#[test]
+fn i16_conversion_clips() {
+    let audio = AudioBuffer::mono(vec![0., 0.5, 2., -2.], 24000);
+    assert_eq!(audio.to_i16(), [0, 16384, i16::MAX, -i16::MAX]);
+}
+
+#[test]
+fn stereo_round_trip() {
+    let audio = AudioBuffer::mono(vec![0.25, -0.5], 24000);
+    let stereo = audio.to_stereo();
+
+    assert_eq!(stereo.channels, 2);
+    assert_eq!(stereo.samples, [0.25, 0.25, -0.5, -0.5]);
+    assert_eq!(stereo.frames(), 2);
+    assert_eq!(stereo.to_mono(), audio);
+}
+
+#[test]
+fn mono_averages_channels() {
+    let audio = AudioBuffer {
+        samples: vec![1., 0., -0.5, 0.5],
+        sample_rate: 24000,
+        channels: 2,
+    };
+    assert_eq!(audio.to_mono().samples, [0.5, 0.]);
+}
+
+#[test]
+fn resample_keeps_duration() {
+    let audio = AudioBuffer::mono(vec![0.; 24000], 24000);
+    let resampled = audio.resample(48000);
+
+    assert_eq!(resampled.sample_rate, 48000);
+    assert_eq!(resampled.samples.len(), 48000);
+    assert_eq!(resampled.duration(), audio.duration());
+}
+
+#[test]
+fn resample_interpolates_linearly() {
+    let audio = AudioBuffer::mono(vec![0., 1.], 1);
+    assert_eq!(audio.resample(2).samples, [0., 0.5, 1., 1.]);
+}
+
+#[test]
+fn pitch_shift_keeps_length_and_channels() {
+    // 120Hzのパルス列
+    let samples = (0..24000)
+        .map(|i| if i % 200 == 0 { 0.5 } else { 0. })
+        .collect();
+    let audio = AudioBuffer::mono(samples, 24000).to_stereo();
+    let shifted = audio.pitch_shift(5.);
+
+    assert_eq!(shifted.samples.len(), audio.samples.len());
+    assert_eq!(shifted.channels, 2);
+    assert_eq!(audio.pitch_shift(0.), audio);
+}
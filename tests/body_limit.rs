@@ -0,0 +1,48 @@
+// serveが大きすぎる本文を読まずに413で断り、その後も答え続けること
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, spawn_server};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+};
+
+// 本文を送らずに、Content-Lengthだけ大きなものを名乗り、ステータス行を返す
+// tiny_httpは応答した後に残りの本文を読み捨てようとするので、最後まで読まずに切る
+fn announce(port: u16, path: &str, length: usize) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status).unwrap();
+    status
+}
+
+#[test]
+fn rejects_large_bodies() {
+    let model_dir = common::test_model_dir();
+    let (mut child, port) = spawn_server(&model_dir, &[]);
+
+    let response = announce(port, "/tts?speaker=0", 2 << 20);
+    assert!(response.contains(" 413 "), "{response}");
+    // JSONを受け取るものも同じ。読めない本文の422とは分ける
+    for path in [
+        "/synthesis?speaker=0",
+        "/mora_data?speaker=0",
+        "/add_preset",
+    ] {
+        let response = announce(port, path, 32 << 20);
+        assert!(response.contains(" 413 "), "{path}: {response}");
+    }
+    // Content-Lengthが上限以内なら、これまでどおり答える
+    let response = request(port, "POST", "/tts?speaker=0", "こんにちは").unwrap();
+    assert!(response.contains(" 200 "), "{response}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
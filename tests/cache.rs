@@ -0,0 +1,77 @@
+// serve --cacheで、同じ/ttsを二度目は合成し直さずに返すこと
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, request_bytes, spawn_server};
+use std::fs;
+
+// ステータス行とヘッダーを含む応答の先頭と、本文
+fn tts(port: u16, text: &str) -> Option<(String, Vec<u8>)> {
+    let response = request_bytes(port, "POST", "/tts?speaker=0", "", text)?;
+    let end = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&response[..end]).into_owned();
+    Some((head, response[end + 4..].to_vec()))
+}
+
+#[test]
+fn second_request_is_a_hit() {
+    let model_dir = common::test_model_dir();
+    let cache_dir = model_dir.join("cache");
+    let (mut child, port) = spawn_server(&model_dir, &["--cache", cache_dir.to_str().unwrap()]);
+
+    let (head, miss) = tts(port, "こんにちは").unwrap();
+    assert!(head.contains("X-Cache: miss"), "{head}");
+
+    let (head, hit) = tts(port, "こんにちは").unwrap();
+    assert!(head.contains("X-Cache: hit"), "{head}");
+    assert_eq!(hit, miss);
+    let (head, _) = tts(port, "さようなら").unwrap();
+    assert!(head.contains("X-Cache: miss"), "{head}");
+    let entries = fs::read_dir(&cache_dir)
+        .unwrap()
+        .filter(|entry| {
+            !entry
+                .as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with('.')
+        })
+        .count();
+    assert_eq!(entries, 2);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+// 大きさの変わらないモデルに置き換えて読み込み直しても、前のモデルの結果を返さないこと
+#[test]
+fn reloaded_model_is_a_miss() {
+    let model_dir = common::test_model_dir();
+    let cache_dir = model_dir.join("cache");
+    let (mut child, port) = spawn_server(&model_dir, &["--cache", cache_dir.to_str().unwrap()]);
+
+    let (head, _) = tts(port, "こんにちは").unwrap();
+    assert!(head.contains("X-Cache: miss"), "{head}");
+    let (head, _) = tts(port, "こんにちは").unwrap();
+    assert!(head.contains("X-Cache: hit"), "{head}");
+
+    // 学習し直したモデルの代わりに、producer_nameの一文字だけを変える
+    let decode = model_dir.join("decode-0.onnx");
+    let mut model = fs::read(&decode).unwrap();
+    let producer = model
+        .windows(8)
+        .position(|window| window == b"chibivox")
+        .unwrap();
+    model[producer + 7] = b'y';
+    fs::write(&decode, &model).unwrap();
+    let reloaded = request(port, "POST", "/reload", "").unwrap();
+    assert!(reloaded.contains(" 200 "), "{reloaded}");
+
+    let (head, _) = tts(port, "こんにちは").unwrap();
+    assert!(head.contains("X-Cache: miss"), "{head}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
@@ -0,0 +1,110 @@
+// serveがリクエストのidをX-Request-Idで返し、POST /cancelで待っているリクエストを取り消し、知らないidを断ること
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, request_with_headers, spawn_server};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn request_ids_and_cancel() {
+    let model_dir = common::test_model_dir();
+    let (mut child, port) = spawn_server(&model_dir, &[]);
+
+    // クライアントが付けたidはそのまま返し、無ければ振る
+    let response = request_with_headers(
+        port,
+        "POST",
+        "/tts?speaker=0",
+        "X-Request-Id: page-1\r\n",
+        "こんにちは",
+    )
+    .unwrap();
+    assert!(response.contains(" 200 "), "{response}");
+    assert!(response.contains("X-Request-Id: page-1\r\n"), "{response}");
+    let response = request(port, "GET", "/speakers", "").unwrap();
+    assert!(response.contains("X-Request-Id: "), "{response}");
+
+    // 終わったリクエストはもう取り消せない
+    let cancelled = request(port, "POST", "/cancel?id=page-1", "").unwrap();
+    assert!(cancelled.contains(" 404 "), "{cancelled}");
+    let cancelled = request(port, "POST", "/cancel", "").unwrap();
+    assert!(cancelled.contains(" 422 "), "{cancelled}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+// 一つしかないworkerを塞いでいる間に届いたリクエストを取り消すと、499を返してworkerを次に回すこと
+#[test]
+fn cancels_a_queued_request() {
+    let model_dir = common::test_model_dir();
+    let (mut child, port) = spawn_server(&model_dir, &["--workers", "1"]);
+
+    // tiny_httpは1024バイト以下の本文を先に読んでしまうので、それより長い本文の途中で止めてworkerを塞ぐ
+    let text = "あ".repeat(400);
+    let mut blocker = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        blocker,
+        "POST /tts?speaker=0 HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        text.len(),
+        &text[..600]
+    )
+    .unwrap();
+    // 後のリクエストより先にworkerに渡るように
+    thread::sleep(Duration::from_millis(500));
+
+    let queued = thread::spawn(move || {
+        request_with_headers(
+            port,
+            "POST",
+            "/tts?speaker=0",
+            "X-Request-Id: queued\r\n",
+            "こんにちは",
+        )
+        .unwrap()
+    });
+    // 受け付けられるまでは知らないidとして断られる
+    let started = Instant::now();
+    loop {
+        let cancelled = request(port, "POST", "/cancel?id=queued", "").unwrap();
+        if cancelled.contains(" 204 ") {
+            break;
+        }
+        assert!(cancelled.contains(" 404 "), "{cancelled}");
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "request was not queued"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    blocker.write_all(text[600..].as_bytes()).unwrap();
+    let mut response = Vec::new();
+    blocker.read_to_end(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.contains(" 200 "), "{response}");
+    let cancelled = queued.join().unwrap();
+    assert!(cancelled.contains(" 499 "), "{cancelled}");
+    assert!(
+        cancelled.contains("X-Request-Id: queued\r\n"),
+        "{cancelled}"
+    );
+    // 振ったidは推測できない長さで、取り消した後もworkerは次のリクエストを処理する
+    let response = request(port, "POST", "/tts?speaker=0", "こんにちは").unwrap();
+    assert!(response.contains(" 200 "), "{response}");
+    let id = response
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Request-Id: "))
+        .unwrap();
+    assert_eq!(id.len(), 32, "{id}");
+    assert!(id.bytes().all(|byte| byte.is_ascii_hexdigit()), "{id}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
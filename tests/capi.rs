@@ -0,0 +1,96 @@
+// VOICEVOX CORE 0.14と同じ手順で、C APIから合成できること
+#![cfg(feature = "capi")]
+
+mod common;
+
+use chibivox::capi::*;
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+};
+
+#[test]
+fn synthesizes_through_c_api() {
+    std::env::set_var("CHIBIVOX_MODEL_DIR", common::test_model_dir());
+    unsafe {
+        assert_eq!(
+            voicevox_load_model(0),
+            VoicevoxResultCode::VOICEVOX_RESULT_UNINITIALIZED_STATUS_ERROR
+        );
+        assert_eq!(
+            voicevox_initialize(voicevox_make_default_initialize_options()),
+            VoicevoxResultCode::VOICEVOX_RESULT_OK
+        );
+        assert_eq!(
+            voicevox_load_model(0),
+            VoicevoxResultCode::VOICEVOX_RESULT_OK
+        );
+        assert!(voicevox_is_model_loaded(0));
+        assert!(!voicevox_is_model_loaded(1));
+        let metas = CStr::from_ptr(voicevox_get_metas_json()).to_str().unwrap();
+        assert!(metas.contains("\"id\":0"));
+
+        let text = CString::new("こんにちは").unwrap();
+        let mut audio_query = ptr::null_mut();
+        assert_eq!(
+            voicevox_audio_query(
+                text.as_ptr(),
+                0,
+                voicevox_make_default_audio_query_options(),
+                &mut audio_query,
+            ),
+            VoicevoxResultCode::VOICEVOX_RESULT_OK
+        );
+
+        let (mut length, mut wav) = (0, ptr::null_mut());
+        assert_eq!(
+            voicevox_synthesis(
+                audio_query,
+                0,
+                voicevox_make_default_synthesis_options(),
+                &mut length,
+                &mut wav,
+            ),
+            VoicevoxResultCode::VOICEVOX_RESULT_OK
+        );
+        assert_eq!(std::slice::from_raw_parts(wav, 4), b"RIFF");
+        voicevox_wav_free(wav);
+        voicevox_audio_query_json_free(audio_query);
+
+        assert_eq!(
+            voicevox_tts(
+                text.as_ptr(),
+                1,
+                voicevox_make_default_tts_options(),
+                &mut length,
+                &mut wav,
+            ),
+            VoicevoxResultCode::VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR
+        );
+        let mut unknown = ptr::null_mut();
+        assert_eq!(
+            voicevox_audio_query(
+                text.as_ptr(),
+                1,
+                voicevox_make_default_audio_query_options(),
+                &mut unknown,
+            ),
+            VoicevoxResultCode::VOICEVOX_RESULT_INVALID_SPEAKER_ID_ERROR
+        );
+        assert!(unknown.is_null());
+        let kana = CString::new("コンニチワ'").unwrap();
+        let options = VoicevoxTtsOptions {
+            kana: true,
+            ..voicevox_make_default_tts_options()
+        };
+        assert_eq!(
+            voicevox_tts(kana.as_ptr(), 0, options, &mut length, &mut wav),
+            VoicevoxResultCode::VOICEVOX_RESULT_OK
+        );
+        assert!(length > 44);
+        voicevox_wav_free(wav);
+
+        voicevox_finalize();
+        assert!(voicevox_get_metas_json().is_null());
+    }
+}
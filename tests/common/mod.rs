@@ -0,0 +1,19 @@
+#[cfg(feature = "server")]
+pub mod server;
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// テストごとに別のディレクトリへダミーモデルを書き出す
+pub fn test_model_dir() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "chibivox-test-model-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    chibivox::test_model::write(&dir).unwrap();
+    dir
+}
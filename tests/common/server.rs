@@ -0,0 +1,75 @@
+// serveを立ち上げ、生のHTTPでリクエストを送るテスト用の道具
+// テストごとに使うものが違うので、使わないものがあっても警告しない
+#![allow(dead_code)]
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+// 空いているポートでserveを起動し、モデルを読み込んで/readyが200を返すまで待つ
+pub fn spawn_server(model_dir: &Path, args: &[&str]) -> (Child, u16) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(model_dir)
+        .args(["serve", "--port", &port.to_string()])
+        .args(args)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while !request(port, "GET", "/ready", "").is_some_and(|response| response.contains(" 200 ")) {
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "server did not start"
+        );
+        thread::sleep(Duration::from_millis(100));
+    }
+    (child, port)
+}
+
+// ステータス行とヘッダーを含む応答全体
+pub fn request(port: u16, method: &str, path: &str, body: &str) -> Option<String> {
+    request_with_headers(port, method, path, "", body)
+}
+
+// headersは"Name: value\r\n"を並べたもの
+pub fn request_with_headers(
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> Option<String> {
+    let response = request_bytes(port, method, path, headers, body)?;
+    Some(String::from_utf8_lossy(&response).into_owned())
+}
+
+// 本文がバイナリの場合に
+pub fn request_bytes(
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> Option<Vec<u8>> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).ok()?;
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    Some(response)
+}
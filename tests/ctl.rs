@@ -0,0 +1,67 @@
+// 動かしたままのサーバーの既定値を、chibivox ctlから変えられること
+#![cfg(all(unix, feature = "server"))]
+
+mod common;
+
+use common::server::spawn_server;
+use serde_json::Value;
+use std::{
+    path::Path,
+    process::{Command, Output},
+    thread,
+    time::{Duration, Instant},
+};
+
+fn ctl(socket: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--json")
+        .arg("ctl")
+        .arg("--socket")
+        .arg(socket)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn defaults(output: &Output) -> Value {
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn set_changes_the_running_server() {
+    let model_dir = common::test_model_dir();
+    let socket = model_dir.join("control.sock");
+    let (mut child, _) = spawn_server(
+        &model_dir,
+        &["--control", "--control-socket", socket.to_str().unwrap()],
+    );
+    // 制御用のソケットは/readyが200を返した後に開くので、開くまで待つ
+    let started = Instant::now();
+    let initial = loop {
+        let output = ctl(&socket, &["get"]);
+        if output.status.success() {
+            break defaults(&output);
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "server did not start"
+        );
+        thread::sleep(Duration::from_millis(100));
+    };
+    assert!(initial["speed"].is_null());
+
+    let changed = defaults(&ctl(&socket, &["set", "speed", "1.5"]));
+    assert_eq!(changed["speed"], 1.5);
+    defaults(&ctl(&socket, &["set", "speaker", "0"]));
+    // 範囲外の値は断り、それまでの値を残す
+    assert!(!ctl(&socket, &["set", "speed", "9"]).status.success());
+    let current = defaults(&ctl(&socket, &["get"]));
+    assert_eq!(current["speed"], 1.5);
+    assert_eq!(current["speaker"], 0);
+    let reset = defaults(&ctl(&socket, &["set", "speed", "default"]));
+    assert!(reset["speed"].is_null());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
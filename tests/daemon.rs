@@ -0,0 +1,67 @@
+// chibivox daemonに、一つの接続で続けてテキストを送ってPCMを受け取れること
+#![cfg(all(unix, feature = "cli"))]
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+fn request(stream: &mut UnixStream, speaker_id: u32, text: &str) -> (u8, Vec<u8>) {
+    stream.write_all(&speaker_id.to_le_bytes()).unwrap();
+    stream
+        .write_all(&(text.len() as u32).to_le_bytes())
+        .unwrap();
+    stream.write_all(text.as_bytes()).unwrap();
+    let mut header = [0; 5];
+    stream.read_exact(&mut header).unwrap();
+    let mut body = vec![0; u32::from_le_bytes(header[1..].try_into().unwrap()) as usize];
+    stream.read_exact(&mut body).unwrap();
+    (header[0], body)
+}
+
+#[test]
+fn answers_text_with_pcm() {
+    let model_dir = common::test_model_dir();
+    let socket = model_dir.join("daemon.sock");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(&model_dir)
+        .arg("daemon")
+        .arg("--socket")
+        .arg(&socket)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // 起動してモデルを読み込むまで待つ
+    let started = Instant::now();
+    let mut stream = loop {
+        if let Ok(stream) = UnixStream::connect(&socket) {
+            break stream;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "daemon did not start"
+        );
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let (status, pcm) = request(&mut stream, 0, "こんにちは");
+    assert_eq!(status, 0);
+    assert!(!pcm.is_empty());
+    assert_eq!(pcm.len() % 2, 0);
+    // 失敗しても接続は続く
+    let (status, message) = request(&mut stream, 999, "こんにちは");
+    assert_ne!(status, 0);
+    assert!(!message.is_empty());
+    let (status, again) = request(&mut stream, 0, "こんにちは");
+    assert_eq!(status, 0);
+    assert_eq!(again, pcm);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use chibivox::{InitializeOptions, VoicevoxCore};
+
+fn core(decode_padding: f32) -> VoicevoxCore {
+    let options = InitializeOptions {
+        decode_padding,
+        ..Default::default()
+    };
+    VoicevoxCore::new_with_options(common::test_model_dir(), &options).unwrap()
+}
+
+#[test]
+fn output_length_is_frames_times_256() {
+    const FRAMES: usize = 50;
+    let phoneme_ids = [0; FRAMES];
+    let f0 = [0.; FRAMES];
+
+    for decode_padding in [0., 0.1, 0.4, 1.] {
+        let audio = core(decode_padding).decode(&phoneme_ids, &f0, 0).unwrap();
+        assert_eq!(
+            audio.samples.len(),
+            FRAMES * 256,
+            "padding {decode_padding}"
+        );
+    }
+}
+
+#[test]
+fn rejects_negative_padding() {
+    // モデルを読み込む前に弾かれる
+    let options = InitializeOptions {
+        decode_padding: -0.1,
+        ..Default::default()
+    };
+    assert!(VoicevoxCore::new_with_options("model", &options).is_err());
+}
@@ -0,0 +1,77 @@
+// 失敗の種類ごとに--helpのEXIT_CODESのとおりの終了コードを返し、--error-format jsonでは種類と段階と入力を書くこと
+#![cfg(feature = "cli")]
+
+mod common;
+
+use serde_json::Value;
+use std::{fs, path::Path, process::Command};
+
+// 終了コードと、標準エラー出力のJSONのerror
+fn chibivox(model_dir: &Path, args: &[&str]) -> (i32, Value) {
+    let result = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .env("CHIBIVOX_CONFIG", model_dir.join("config.toml"))
+        .arg("--model-dir")
+        .arg(model_dir)
+        .args(["--error-format", "json"])
+        .args(args)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    let error = stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find_map(|line| line.get("error").cloned())
+        .unwrap_or_else(|| panic!("no JSON error in {stderr}"));
+    (result.status.code().unwrap(), error)
+}
+
+#[test]
+fn unknown_speaker_is_an_argument_error() {
+    let model_dir = common::test_model_dir();
+    let output = model_dir.join("out.wav").display().to_string();
+    let (code, error) = chibivox(
+        &model_dir,
+        &["synth", "こんにちは", "--speaker", "9", "-o", &output],
+    );
+    assert_eq!(code, 2, "{error}");
+    assert_eq!(error["kind"], "argument");
+    assert!(error["stage"].is_null(), "{error}");
+    assert!(error["input"].is_null(), "{error}");
+}
+
+#[test]
+fn bad_flag_is_an_argument_error() {
+    let model_dir = common::test_model_dir();
+    let (code, error) = chibivox(&model_dir, &["synth", "--no-such-flag", "こんにちは"]);
+    assert_eq!(code, 2, "{error}");
+    assert_eq!(error["kind"], "argument");
+    assert_eq!(error["input"], "--no-such-flag");
+}
+
+#[test]
+fn broken_model_is_a_model_error() {
+    let model_dir = common::test_model_dir();
+    fs::write(model_dir.join("decode-0.onnx"), b"not an onnx model").unwrap();
+    let output = model_dir.join("out.wav").display().to_string();
+    let (code, error) = chibivox(&model_dir, &["synth", "こんにちは", "-o", &output]);
+    assert_eq!(code, 4, "{error}");
+    assert_eq!(error["kind"], "model");
+    assert_eq!(error["stage"], "load");
+    assert_eq!(error["input"], model_dir.display().to_string());
+}
+
+#[test]
+fn partial_batch_failure() {
+    let model_dir = common::test_model_dir();
+    let output = model_dir.join("out");
+    // 二つ目の書き出し先をディレクトリで塞いでおく
+    fs::create_dir_all(output.join("002.wav")).unwrap();
+    let output = format!("{}/", output.display());
+    let (code, error) = chibivox(
+        &model_dir,
+        &["synth", "こんにちは", "さようなら", "-o", &output],
+    );
+    assert_eq!(code, 8, "{error}");
+    assert_eq!(error["kind"], "partial");
+    assert!(model_dir.join("out/001.wav").is_file());
+}
@@ -0,0 +1,24 @@
+use chibivox::AudioBuffer;
+
+#[test]
+fn stream_info() {
+    let audio = AudioBuffer::mono(vec![0.; 5000], 24000).to_stereo();
+    let flac = audio.to_flac();
+
+    assert_eq!(&flac[0..4], b"fLaC");
+    // 最後のメタデータブロックであるSTREAMINFO、34バイト
+    assert_eq!(flac[4..8], [0x80, 0, 0, 34]);
+    // サンプリングレート(20bit)、チャンネル数-1(3bit)、ビット深度-1(5bit)、総サンプル数(36bit)
+    let fields = u64::from_be_bytes(flac[18..26].try_into().unwrap());
+    assert_eq!(fields >> 44, 24000);
+    assert_eq!((fields >> 41) & 0b111, 1);
+    assert_eq!((fields >> 36) & 0b11111, 15);
+    assert_eq!(fields & 0xf_ffff_ffff, 5000);
+}
+
+#[test]
+fn frames_start_with_sync_code() {
+    let flac = AudioBuffer::mono(vec![0.25; 100], 24000).to_flac();
+    // STREAMINFOの直後に最初のフレームが来る
+    assert_eq!(flac[42..44], [0xff, 0xf8]);
+}
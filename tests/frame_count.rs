@@ -0,0 +1,87 @@
+use chibivox::{frame_count, AccentPhraseModel, AudioQueryModel, MoraModel};
+
+fn mora(vowel_length: f32) -> MoraModel {
+    MoraModel {
+        text: "カ".into(),
+        consonant: Some("k".into()),
+        consonant_length: Some(0.05),
+        vowel: "a".into(),
+        vowel_length,
+        pitch: 5.,
+        gain: None,
+    }
+}
+
+fn query(is_interrogative: bool) -> AudioQueryModel {
+    AudioQueryModel {
+        accent_phrases: vec![AccentPhraseModel {
+            moras: vec![mora(0.1)],
+            accent: 1,
+            pause_mora: None,
+            is_interrogative,
+        }],
+        speed_scale: 1.,
+        pitch_scale: 0.,
+        intonation_scale: 1.,
+        volume_scale: 1.,
+        pre_phoneme_length: 0.1,
+        post_phoneme_length: 0.1,
+        output_sampling_rate: 24000,
+        output_stereo: false,
+        kana: None,
+    }
+}
+
+#[test]
+fn rounds_each_phoneme_up() {
+    // pre 10 + k 5 + a 10 + post 10
+    assert_eq!(frame_count(&query(false), 1.), 35);
+    // pre 5 + k 3 + a 5 + post 5
+    assert_eq!(frame_count(&query(false), 2.), 18);
+}
+
+#[test]
+fn counts_interrogative_mora() {
+    // 語尾上げで0.15秒(15フレーム)の母音が足される
+    assert_eq!(frame_count(&query(true), 1.), 50);
+}
+
+#[test]
+fn breath_groups_keep_the_length() {
+    // カ、カ、カ の3つの息継ぎ
+    let mut audio_query = query(false);
+    let phrase = audio_query.accent_phrases[0].clone();
+    audio_query.accent_phrases = (0..3)
+        .map(|i| AccentPhraseModel {
+            pause_mora: (i < 2).then(|| MoraModel {
+                text: "、".into(),
+                consonant: None,
+                consonant_length: None,
+                vowel: "pau".into(),
+                vowel_length: 0.2,
+                pitch: 0.,
+                gain: None,
+            }),
+            ..phrase.clone()
+        })
+        .collect();
+
+    let groups = audio_query.split_breath_groups();
+    assert_eq!(groups.len(), 3);
+    assert!(groups.iter().all(
+        |group| group.accent_phrases.len() == 1 && group.accent_phrases[0].pause_mora.is_none()
+    ));
+    assert_eq!(groups[0].pre_phoneme_length, 0.1);
+    assert_eq!(groups[0].post_phoneme_length, 0.1);
+    assert_eq!(groups[1].pre_phoneme_length, 0.1);
+    assert_eq!(groups[2].post_phoneme_length, 0.1);
+    let total: usize = groups.iter().map(|group| frame_count(group, 1.)).sum();
+    assert_eq!(total, frame_count(&audio_query, 1.));
+}
+
+#[test]
+fn no_pause_is_one_group() {
+    let groups = query(false).split_breath_groups();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(frame_count(&groups[0], 1.), 35);
+}
@@ -0,0 +1,145 @@
+// --jobsを変えても、書き出すファイルと警告の順序が変わらないこと
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TEXTS: &[&str] = &[
+    "こんにちは。",
+    "今日はいい天気ですね。",
+    "ABCDEFG",
+    "東京特許許可局。",
+    "ずんだもち？",
+    "音声合成のテストです。",
+    "あ",
+    "アイウエオカキクケコ。",
+];
+
+// outputに書き出し、警告の行を返す
+fn synth(model_dir: &Path, output: &str, args: &[&str]) -> Vec<String> {
+    let result = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        // 手元の設定ファイルに左右されないように
+        .env("CHIBIVOX_CONFIG", model_dir.join("config.toml"))
+        .arg("--model-dir")
+        .arg(model_dir)
+        .arg("synth")
+        .args(TEXTS)
+        .arg("--deterministic")
+        .args(["-o", output])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{result:?}");
+    String::from_utf8(result.stderr)
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("warning: "))
+        .map(str::to_string)
+        .collect()
+}
+
+fn read_dir(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(&path).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn output_does_not_depend_on_jobs() {
+    let model_dir = common::test_model_dir();
+    let serial = model_dir.join("serial");
+    let output = |dir: &Path| format!("{}/", dir.display());
+    let warnings = synth(
+        &model_dir,
+        &output(&serial),
+        &["--jobs", "1", "--save-query"],
+    );
+    let expected = read_dir(&serial);
+    assert_eq!(expected.len(), TEXTS.len() * 2);
+
+    for jobs in ["2", "4", "0"] {
+        let parallel = model_dir.join(format!("jobs{jobs}"));
+        assert_eq!(
+            synth(
+                &model_dir,
+                &output(&parallel),
+                &["--jobs", jobs, "--save-query"]
+            ),
+            warnings,
+            "--jobs {jobs}"
+        );
+        assert!(read_dir(&parallel) == expected, "--jobs {jobs}");
+    }
+}
+
+#[test]
+fn concat_does_not_depend_on_jobs() {
+    let model_dir = common::test_model_dir();
+    let concat = |jobs: &str| {
+        let output = model_dir.join(format!("concat{jobs}.wav"));
+        let warnings = synth(
+            &model_dir,
+            &output.display().to_string(),
+            &["--jobs", jobs, "--concat"],
+        );
+        (warnings, fs::read(&output).unwrap())
+    };
+    let expected = concat("1");
+    for jobs in ["2", "4"] {
+        assert!(concat(jobs) == expected, "--jobs {jobs}");
+    }
+}
+
+// --concatでも--jobsの数だけ並行に合成すること
+// 一つ目の入力の途中の値の書き出しを名前付きパイプで止めておき、その間に二つ目の入力が書き出されるかを見る
+#[cfg(unix)]
+#[test]
+fn concat_runs_in_parallel() {
+    let model_dir = common::test_model_dir();
+    let blocked = model_dir.join("parallel_001.intermediates.json");
+    let status = Command::new("mkfifo").arg(&blocked).status().unwrap();
+    assert!(status.success());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .env("CHIBIVOX_CONFIG", model_dir.join("config.toml"))
+        .arg("--model-dir")
+        .arg(&model_dir)
+        .arg("synth")
+        .args(&TEXTS[..2])
+        .args([
+            "--jobs",
+            "2",
+            "--concat",
+            "--save-query",
+            "--dump-intermediates",
+        ])
+        .arg("-o")
+        .arg(model_dir.join("parallel.wav"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let second = model_dir.join("parallel_002.json");
+    let started = Instant::now();
+    while !second.exists() && started.elapsed() < Duration::from_secs(30) {
+        thread::sleep(Duration::from_millis(50));
+    }
+    let parallel = second.exists();
+    // 読み出して、止めておいた一つ目を進める
+    fs::read(&blocked).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(parallel, "the second input waited for the first");
+}
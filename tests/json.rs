@@ -0,0 +1,76 @@
+// --jsonでは、書き出したファイルごとに一行のJSONを標準出力に書くこと
+#![cfg(feature = "cli")]
+
+mod common;
+
+use serde_json::Value;
+use std::{path::Path, process::Command};
+
+fn chibivox(model_dir: &Path, args: &[&str]) -> Vec<Value> {
+    let result = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .env("CHIBIVOX_CONFIG", model_dir.join("config.toml"))
+        .arg("--model-dir")
+        .arg(model_dir)
+        .arg("--json")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{result:?}");
+    String::from_utf8(result.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn synth_prints_one_record_per_file() {
+    let model_dir = common::test_model_dir();
+    let output = format!("{}/", model_dir.join("out").display());
+    let records = chibivox(
+        &model_dir,
+        &["synth", "こんにちは。", "ABCDEFG", "-o", &output],
+    );
+    assert_eq!(records.len(), 2);
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(record["input"], format!("input {}", i + 1));
+        assert!(Path::new(record["path"].as_str().unwrap()).is_file());
+        assert!(record["duration"].as_f64().unwrap() > 0.);
+        assert!(record["warnings"].is_array());
+        assert!(record["timings"]["decode"].is_f64());
+    }
+}
+
+#[test]
+fn concat_records_offsets() {
+    let model_dir = common::test_model_dir();
+    let output = model_dir.join("concat.wav").display().to_string();
+    let records = chibivox(
+        &model_dir,
+        &[
+            "synth",
+            "こんにちは。",
+            "さようなら。",
+            "--concat",
+            "-o",
+            &output,
+        ],
+    );
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["offset"], 0.);
+    assert_eq!(
+        records[1]["offset"].as_f64().unwrap(),
+        records[0]["duration"].as_f64().unwrap()
+    );
+    assert!(records
+        .iter()
+        .all(|record| record["path"] == output.as_str()));
+}
+
+#[test]
+fn list_speakers_prints_metas() {
+    let model_dir = common::test_model_dir();
+    let records = chibivox(&model_dir, &["list-speakers"]);
+    assert_eq!(records.len(), 1);
+    assert!(records[0].as_array().is_some_and(|metas| !metas.is_empty()));
+}
@@ -0,0 +1,67 @@
+// GET /metricsで、答えたリクエストと合成した音声の長さが見えること。/health、/ready、/version、/openapi.json、/engine_manifestも答え、知らない話者には422を返すこと
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, spawn_server};
+
+#[test]
+fn counts_requests_and_audio() {
+    let model_dir = common::test_model_dir();
+    let (mut child, port) = spawn_server(&model_dir, &[]);
+
+    let response = request(port, "POST", "/tts?speaker=0", "こんにちは").unwrap();
+    assert!(response.contains(" 200 "), "{response}");
+    let metrics = request(port, "GET", "/metrics", "").unwrap();
+    assert!(
+        metrics.contains("chibivox_requests_total{route=\"/tts\",status=\"200\"} 1"),
+        "{metrics}"
+    );
+    assert!(metrics.contains("chibivox_stage_duration_seconds_count{stage=\"decode\"} 1"));
+    assert!(!metrics.contains("chibivox_audio_seconds_total 0\n"));
+    assert!(metrics.contains("chibivox_queue_depth{queue=\"requests\"} 0"));
+
+    let health = request(port, "GET", "/health", "").unwrap();
+    assert!(health.contains(" 200 "), "{health}");
+    // 確認用に付けたクエリは無視する
+    let ready = request(port, "GET", "/ready?probe=1", "").unwrap();
+    assert!(ready.contains(" 200 "), "{ready}");
+    let version = request(port, "GET", "/version", "").unwrap();
+    assert!(version.contains(env!("CARGO_PKG_VERSION")), "{version}");
+    assert!(version.contains("\"decode-0.onnx\""), "{version}");
+    assert!(
+        version.contains("\"execution_providers\":[\"cpu\"]"),
+        "{version}"
+    );
+    let openapi = request(port, "GET", "/openapi.json", "").unwrap();
+    assert!(openapi.contains("\"openapi\":\"3."), "{openapi}");
+    assert!(openapi.contains("\"/audio_query\""), "{openapi}");
+    assert!(openapi.contains("\"AudioQueryModel\""), "{openapi}");
+    let manifest = request(port, "GET", "/engine_manifest", "").unwrap();
+    assert!(manifest.contains("\"name\":\"chibivox\""), "{manifest}");
+    assert!(
+        manifest.contains("\"default_sampling_rate\":24000"),
+        "{manifest}"
+    );
+    assert!(
+        manifest.contains("\"interrogative_upspeak\":true"),
+        "{manifest}"
+    );
+    assert!(manifest.contains("\"frame_rate\":93.75"), "{manifest}");
+    assert!(manifest.contains("\"icon\":\"\""), "{manifest}");
+    assert!(manifest.contains("\"terms_of_service\":\"\""), "{manifest}");
+    assert!(manifest.contains("\"update_infos\":[]"), "{manifest}");
+    assert!(
+        manifest.contains("\"dependency_licenses\":[]"),
+        "{manifest}"
+    );
+
+    // 知らない話者は、リクエストの誤りとして返す
+    let unknown = request(port, "POST", "/tts?speaker=9", "こんにちは").unwrap();
+    assert!(unknown.contains(" 422 "), "{unknown}");
+    let unknown = request(port, "POST", "/audio_query?text=a&speaker=9", "").unwrap();
+    assert!(unknown.contains(" 422 "), "{unknown}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
@@ -0,0 +1,79 @@
+// serveの/add_presetなどで保存したプリセットを、/audio_query_from_presetとquery --presetで使えること
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, spawn_server};
+use std::{fs, process::Command};
+
+const PRESET: &str = r#"{"id":0,"name":"ゆっくり","speaker_uuid":"00000000-0000-0000-0000-000000000000","style_id":0,"speedScale":0.75,"pitchScale":0,"intonationScale":1,"volumeScale":1,"prePhonemeLength":0.1,"postPhonemeLength":0.1}"#;
+
+#[test]
+fn presets_round_trip() {
+    let model_dir = common::test_model_dir();
+    let presets_path = model_dir.join("presets.toml");
+    let (mut child, port) =
+        spawn_server(&model_dir, &["--presets", presets_path.to_str().unwrap()]);
+
+    let added = request(port, "POST", "/add_preset", PRESET).unwrap();
+    assert!(
+        added.contains(" 200 ") && added.ends_with("\r\n\r\n0"),
+        "{added}"
+    );
+    // 同じidなら次のidを振り、同じ名前なら断る
+    let duplicate = request(port, "POST", "/add_preset", PRESET).unwrap();
+    assert!(duplicate.contains(" 422 "), "{duplicate}");
+    let other = PRESET.replace("ゆっくり", "はやい").replace("0.75", "1.5");
+    let added = request(port, "POST", "/add_preset", &other).unwrap();
+    assert!(added.ends_with("\r\n\r\n1"), "{added}");
+    let deleted = request(port, "POST", "/delete_preset?id=1", "").unwrap();
+    assert!(deleted.contains(" 204 "), "{deleted}");
+    let presets = request(port, "GET", "/presets", "").unwrap();
+    assert!(presets.contains("\"name\":\"ゆっくり\""), "{presets}");
+    assert!(!presets.contains("はやい"), "{presets}");
+
+    let audio_query = request(
+        port,
+        "POST",
+        "/audio_query_from_preset?text=%E3%81%82&preset_id=0",
+        "",
+    )
+    .unwrap();
+    assert!(audio_query.contains("\"speedScale\":0.75"), "{audio_query}");
+    let unknown = request(
+        port,
+        "POST",
+        "/audio_query_from_preset?text=a&preset_id=9",
+        "",
+    )
+    .unwrap();
+    assert!(unknown.contains(" 422 "), "{unknown}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let saved = fs::read_to_string(&presets_path).unwrap();
+    assert!(saved.contains("[\"ゆっくり\"]"), "{saved}");
+    let output = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(&model_dir)
+        .args(["query", "--preset", "ゆっくり", "あ"])
+        .env("CHIBIVOX_PRESETS", &presets_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let audio_query = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        audio_query.contains("\"speedScale\": 0.75"),
+        "{audio_query}"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(&model_dir)
+        .args(["query", "--preset", "はやい", "あ"])
+        .env("CHIBIVOX_PRESETS", &presets_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,331 @@
+// 公開APIのスナップショット
+// ここがコンパイルできなくなる変更は破壊的変更なので、semverに従ってバージョンを上げる
+// unstableの下にあるものは対象外
+
+use anyhow::Result;
+use chibivox::{
+    prelude::*, wav::SampleFormat, AudioFeatures, Intermediates, InvalidAccentPhraseError,
+    InvalidAccentPhraseReason, PaddingTrimError, Progress, ProsodySmoother, SpeakerInfo,
+    StyleDefaults, StyleInfo, Synthesis, TermsNotAcknowledgedError, UnsupportedStyleError,
+    UsageTerms, UserDict, UserDictError, UserDictWord, ValidationMode, Warning, Warnings,
+};
+use std::{collections::BTreeMap, path::Path};
+
+#[test]
+fn constants() {
+    assert_eq!(SAMPLING_RATE, 24000);
+    // wasmではONNX Runtimeを使わない
+    #[cfg(not(feature = "wasm"))]
+    assert!(chibivox::ONNXRUNTIME_API_VERSION >= 16);
+}
+
+#[test]
+fn options() {
+    let _ = SynthesisOptions {
+        speed_scale: 1.,
+        pitch_scale: 0.,
+        intonation_scale: 1.,
+        volume_scale: 1.,
+        pre_phoneme_length: 0.1,
+        post_phoneme_length: 0.1,
+        enable_interrogative_upspeak: true,
+    };
+    let _ = InitializeOptions {
+        deterministic: false,
+        cpu_num_threads: 0,
+        ort_library_path: None,
+        decode_padding: 0.4,
+        acknowledge_terms: false,
+        cache_optimized_models: false,
+    };
+    let _: fn(&InitializeOptions) -> usize = InitializeOptions::intra_threads;
+}
+
+#[test]
+fn voicevox_core() {
+    let _: fn(&Path) -> Result<VoicevoxCore> = |model_dir| VoicevoxCore::new(model_dir);
+    let _: fn(&Path, &InitializeOptions) -> Result<VoicevoxCore> =
+        |model_dir, options| VoicevoxCore::new_with_options(model_dir, options);
+    #[cfg(feature = "wasm")]
+    let _: fn(
+        &[u8],
+        Option<&[u8]>,
+        Option<&[u8]>,
+        &str,
+        &InitializeOptions,
+    ) -> Result<VoicevoxCore> = VoicevoxCore::from_bytes;
+    let _: fn(&VoicevoxCore) -> &[SpeakerMeta] = VoicevoxCore::metas;
+    let _: fn(&VoicevoxCore, u32) -> Option<&UsageTerms> = VoicevoxCore::usage_terms;
+    let _: fn(&VoicevoxCore) -> bool = VoicevoxCore::is_rule_based_duration;
+    let _: fn(&VoicevoxCore) -> bool = VoicevoxCore::is_rule_based_intonation;
+    let _: fn(&VoicevoxCore, u32) -> SynthesisOptions = VoicevoxCore::default_options;
+    let _: fn(&mut VoicevoxCore, bool) = VoicevoxCore::set_devoicing;
+
+    let _: fn(&VoicevoxCore, &str, u32) -> Result<AudioQueryModel> = VoicevoxCore::audio_query;
+    let _: fn(&VoicevoxCore, &str, u32, &SynthesisOptions) -> Result<AudioQueryModel> =
+        VoicevoxCore::audio_query_with_options;
+    let _: fn(&VoicevoxCore, &str, u32, &SynthesisOptions) -> Result<(AudioQueryModel, Warnings)> =
+        VoicevoxCore::audio_query_with_warnings;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, &SynthesisOptions) -> AudioQueryModel =
+        VoicevoxCore::audio_query_from_accent_phrases;
+    let _: fn(&VoicevoxCore, &str) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::create_accent_phrases;
+    let _: fn(&VoicevoxCore, &str, &mut Warnings) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::create_accent_phrases_with_warnings;
+    let _: fn(&VoicevoxCore, &str) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::create_accent_phrases_from_kana;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, u32) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::replace_phoneme_length;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, u32) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::replace_mora_pitch;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, u32) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::replace_mora_data;
+    let _: fn(
+        &VoicevoxCore,
+        Vec<AccentPhraseModel>,
+        u32,
+        &mut Warnings,
+    ) -> Result<Vec<AccentPhraseModel>> = VoicevoxCore::replace_mora_data_with_warnings;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, usize, u32) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::update_length_for_phrase;
+    let _: fn(&VoicevoxCore, Vec<AccentPhraseModel>, usize, u32) -> Result<Vec<AccentPhraseModel>> =
+        VoicevoxCore::update_mora_pitch_for_phrase;
+
+    let _: fn(&VoicevoxCore, &AudioQueryModel, u32) -> Result<AudioBuffer> =
+        VoicevoxCore::synthesis;
+    let _: fn(&VoicevoxCore, &AudioQueryModel, u32) -> Result<AudioBuffer> =
+        VoicevoxCore::synthesis_decode_only;
+    let _: fn(&VoicevoxCore, &str, u32) -> Result<AudioBuffer> = VoicevoxCore::synthesis_from_query;
+    let _: fn(&VoicevoxCore, &[i64], &[f32], u32) -> Result<AudioBuffer> = VoicevoxCore::decode;
+    let _: fn(&VoicevoxCore, &str, u32) -> Result<AudioBuffer> = VoicevoxCore::tts;
+    let _: fn(&VoicevoxCore, &str, u32, &SynthesisOptions) -> Result<AudioBuffer> =
+        VoicevoxCore::tts_with_options;
+    let _: fn(&VoicevoxCore, &str, u32, &SynthesisOptions) -> Result<(AudioBuffer, Warnings)> =
+        VoicevoxCore::tts_with_warnings;
+    let _: fn(&VoicevoxCore, &str, u32) -> Result<AudioBuffer> = VoicevoxCore::tts_from_kana;
+    let _: fn(&VoicevoxCore, &str, u32, &SynthesisOptions) -> Result<Intermediates> =
+        VoicevoxCore::intermediates;
+}
+
+#[test]
+fn audio_query() {
+    let mora = MoraModel {
+        text: "ア".into(),
+        consonant: None,
+        consonant_length: None,
+        vowel: "a".into(),
+        vowel_length: 0.1,
+        pitch: 5.5,
+        gain: None,
+    };
+    let accent_phrase = AccentPhraseModel {
+        moras: vec![mora],
+        accent: 1,
+        pause_mora: None,
+        is_interrogative: false,
+    };
+    let mut audio_query = AudioQueryModel {
+        accent_phrases: vec![accent_phrase],
+        speed_scale: 1.,
+        pitch_scale: 0.,
+        intonation_scale: 1.,
+        volume_scale: 1.,
+        pre_phoneme_length: 0.1,
+        post_phoneme_length: 0.1,
+        output_sampling_rate: SAMPLING_RATE,
+        output_stereo: false,
+        kana: None,
+    };
+
+    let _: fn(&AudioQueryModel) -> Result<()> = AudioQueryModel::validate;
+    let _: Result<Vec<String>> = audio_query.validate_with_mode(ValidationMode::Lenient);
+    let _: usize = chibivox::frame_count(&audio_query, 1.);
+    let _: Vec<AudioQueryModel> = audio_query.split_breath_groups();
+    match ValidationMode::default() {
+        ValidationMode::Strict | ValidationMode::Lenient => {}
+    }
+}
+
+#[test]
+fn prosody_smoother() {
+    let _: fn(f32) -> ProsodySmoother = ProsodySmoother::new;
+    let _: fn(&mut ProsodySmoother, &mut AudioQueryModel) = ProsodySmoother::apply;
+}
+
+#[test]
+fn user_dict() {
+    let _: fn(&VoicevoxCore, UserDict) = VoicevoxCore::set_user_dict;
+    let _: fn() -> UserDict = UserDict::new;
+    let _: fn(&Path) -> Result<UserDict> = |path| UserDict::load(path);
+    let _: fn(&UserDict, &Path) -> Result<()> = |user_dict, path| user_dict.save(path);
+    let _: fn(&UserDict) -> &BTreeMap<String, UserDictWord> = UserDict::words;
+    let _: fn(&mut UserDict, UserDictWord) -> Result<String, UserDictError> = UserDict::add_word;
+    let _: fn(&mut UserDict, &str, UserDictWord) -> Result<(), UserDictError> =
+        UserDict::update_word;
+    let _: fn(&mut UserDict, &str) -> Result<UserDictWord, UserDictError> = UserDict::remove_word;
+    let _: fn(&str, &str, usize) -> UserDictWord = UserDictWord::new;
+    let _ = |word: UserDictWord| -> (String, String, usize, u32) {
+        (
+            word.surface,
+            word.pronunciation,
+            word.accent_type,
+            word.priority,
+        )
+    };
+    let _ = |err: UserDictError| match err {
+        UserDictError::WordNotFound(uuid) => uuid,
+        UserDictError::InvalidWord(reason) => reason,
+    };
+}
+
+#[test]
+fn synthesis() {
+    // 寿命の引数があるので、関数そのものではなくクロージャで確かめる
+    let _: for<'a> fn(&'a VoicevoxCore, &str, u32, &SynthesisOptions) -> Synthesis<'a> =
+        |core, text, speaker_id, options| Synthesis::new(core, text, speaker_id, options);
+    let _: fn(&mut Synthesis) -> Result<Progress> = |synthesis| synthesis.step();
+    let _: fn(&Synthesis) -> bool = |synthesis| synthesis.is_done();
+    let _: for<'a> fn(&'a Synthesis<'_>) -> Option<&'a AudioQueryModel> =
+        |synthesis| synthesis.audio_query();
+    let _: for<'a> fn(&'a Synthesis<'_>) -> &'a Warnings = |synthesis| synthesis.warnings();
+    let _ = |progress: Progress| match progress {
+        Progress::Working | Progress::Done => {}
+        Progress::Audio(audio) => {
+            let _: AudioBuffer = audio;
+        }
+    };
+}
+
+#[test]
+fn intermediates() {
+    let _ = Intermediates {
+        labels: Vec::new(),
+        phonemes: Vec::new(),
+        phoneme_ids: Vec::new(),
+        phoneme_lengths: Vec::new(),
+        f0: Vec::new(),
+    };
+}
+
+#[test]
+fn warnings() {
+    let mut warnings = Warnings::new();
+    warnings.push(Warning::UnknownWord {
+        surface: String::new(),
+    });
+    warnings.extend([
+        Warning::TruncatedPhrase {
+            text: String::new(),
+        },
+        Warning::ClampedDuration {
+            phoneme: String::new(),
+            predicted: 0.,
+        },
+    ]);
+    warnings.check_clipping(&AudioBuffer::mono(vec![0.], SAMPLING_RATE));
+    let _: bool = warnings.is_empty();
+    let _: usize = warnings.len();
+    for warning in &warnings {
+        match warning {
+            Warning::UnknownWord { .. }
+            | Warning::TruncatedPhrase { .. }
+            | Warning::ClampedDuration { .. }
+            | Warning::ClippedSamples { .. } => {}
+        }
+    }
+}
+
+#[test]
+fn audio_buffer() {
+    let audio = AudioBuffer {
+        samples: vec![0.],
+        sample_rate: SAMPLING_RATE,
+        channels: 1,
+    };
+    let _: AudioBuffer = AudioBuffer::mono(vec![0.], SAMPLING_RATE);
+    let _: fn(&[u8]) -> Result<AudioBuffer> = AudioBuffer::from_wav;
+    let _: usize = audio.frames();
+    let _: f32 = audio.duration();
+    let _: Vec<i16> = audio.to_i16();
+    let _: Vec<u8> = audio.to_wav(SampleFormat::F32);
+    let _: Vec<u8> = audio.to_flac();
+    let _: AudioBuffer = audio.to_mono().to_stereo().resample(48000);
+    let _: AudioBuffer = audio.pitch_shift(2.);
+    match SampleFormat::I16 {
+        SampleFormat::I16 | SampleFormat::I24 | SampleFormat::F32 => {}
+    }
+}
+
+#[test]
+fn metas() {
+    let style = StyleMeta {
+        name: "ノーマル".into(),
+        id: 0,
+        style_type: StyleType::Talk,
+        defaults: Some(StyleDefaults {
+            speed_scale: Some(1.1),
+            pitch_scale: None,
+            intonation_scale: None,
+            volume_scale: None,
+        }),
+    };
+    let _ = SpeakerMeta {
+        name: "test".into(),
+        styles: vec![style],
+        speaker_uuid: String::new(),
+        version: String::new(),
+        terms: Some(UsageTerms {
+            credit: Some("VOICEVOX:test".into()),
+            commercial: false,
+        }),
+    };
+    let _: fn(&Path) -> Result<Vec<SpeakerMeta>> = chibivox::load_metas;
+    match StyleType::default() {
+        StyleType::Talk | StyleType::SingingTeacher | StyleType::FrameDecode | StyleType::Sing => {}
+    }
+    let _ = SpeakerInfo {
+        policy: String::new(),
+        portrait: String::new(),
+        style_infos: vec![StyleInfo {
+            id: 0,
+            icon: String::new(),
+            portrait: None,
+            voice_samples: Vec::new(),
+        }],
+    };
+    let _: fn(&Path, &str, &[u32]) -> Result<SpeakerInfo> = SpeakerInfo::load;
+}
+
+#[test]
+fn errors() {
+    let _ = UnsupportedStyleError {
+        speaker_id: 0,
+        style_type: StyleType::Sing,
+        supported_speaker_ids: Vec::new(),
+    };
+    let _ = TermsNotAcknowledgedError {
+        speaker_id: 0,
+        speaker_name: String::new(),
+    };
+    let _ = PaddingTrimError {
+        output_size: 0,
+        padding_size: 0,
+    };
+    let _ = InvalidAccentPhraseError {
+        location: String::new(),
+        reason: InvalidAccentPhraseReason::NoMoras,
+    };
+    match InvalidAccentPhraseReason::NoMoras {
+        InvalidAccentPhraseReason::NoMoras
+        | InvalidAccentPhraseReason::UnknownVowel(_)
+        | InvalidAccentPhraseReason::UnknownConsonant(_) => {}
+    }
+}
+
+#[test]
+fn utilities() {
+    let _: fn() -> usize = chibivox::available_cpus;
+    let _: fn(usize) -> usize = chibivox::default_worker_count;
+    let _: fn(&[f32], u32) -> AudioFeatures = AudioFeatures::extract;
+    let _: fn(&Path) -> Result<()> = chibivox::test_model::write;
+}
@@ -0,0 +1,36 @@
+// serveがPOST /reloadとSIGHUPで、待ち受けを止めずにモデルを読み込み直すこと
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, spawn_server};
+use std::{process::Command, thread, time::Duration};
+
+#[test]
+fn reloads_models() {
+    let model_dir = common::test_model_dir();
+    let (mut child, port) = spawn_server(&model_dir, &[]);
+
+    let reloaded = request(port, "POST", "/reload", "").unwrap();
+    assert!(reloaded.contains(" 200 "), "{reloaded}");
+    assert!(reloaded.contains("\"models\":"), "{reloaded}");
+    let response = request(port, "POST", "/tts?speaker=0", "こんにちは").unwrap();
+    assert!(response.contains(" 200 "), "{response}");
+
+    // SIGHUPでは終了せずに読み込み直す
+    #[cfg(unix)]
+    {
+        let status = Command::new("kill")
+            .args(["-HUP", &child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        thread::sleep(Duration::from_millis(500));
+        assert!(child.try_wait().unwrap().is_none());
+        let response = request(port, "POST", "/tts?speaker=0", "こんにちは").unwrap();
+        assert!(response.contains(" 200 "), "{response}");
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
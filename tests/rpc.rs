@@ -0,0 +1,84 @@
+// chibivox rpc --stdioに、LSPと同じ形で要求を送って応答を受け取れること
+#![cfg(feature = "cli")]
+
+mod common;
+
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Command, Stdio},
+};
+
+fn write_message(writer: &mut impl Write, message: &Value) {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+}
+
+fn read_message(reader: &mut impl BufRead) -> Value {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        assert_eq!(name, "Content-Length");
+        length = value.trim().parse().unwrap();
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[test]
+fn answers_json_rpc_on_stdio() {
+    let model_dir = common::test_model_dir();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(&model_dir)
+        .args(["rpc", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "audio_query", "params": {"text": "こんにちは"}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "synthesize", "params": {"text": "こんにちは", "speaker": 0}}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "speak", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "cancel", "params": {"id": 99}}),
+        // 通知には応答しない
+        json!({"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 1}}),
+    ];
+    for request in &requests {
+        write_message(&mut stdin, request);
+    }
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let responses: HashMap<i64, Value> = (0..4)
+        .map(|_| {
+            let response = read_message(&mut stdout);
+            (response["id"].as_i64().unwrap(), response)
+        })
+        .collect();
+    // 標準入力を閉じれば、処理中の要求に応答してから終わる
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+
+    // 取り消しが間に合うかは処理の速さに依る
+    let audio_query = &responses[&1];
+    assert!(
+        audio_query["result"]["accent_phrases"].is_array()
+            || audio_query["error"]["code"] == -32800,
+        "{audio_query}"
+    );
+    let synthesis = &responses[&2]["result"];
+    assert!(synthesis["wav"].as_str().unwrap().len() > 44);
+    assert!(synthesis["duration"].as_f64().unwrap() > 0.);
+    assert_eq!(responses[&3]["error"]["code"], -32601);
+    assert_eq!(responses[&4]["result"], Value::Null);
+}
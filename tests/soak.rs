@@ -0,0 +1,300 @@
+// 長時間動かし続けてもメモリやファイルディスクリプタが増え続けないかを見る
+// 時間がかかるので普段は走らせない。CHIBIVOX_SOAK_SECS(既定60秒)の間、ランダムな入力で合成し続ける
+//   CHIBIVOX_SOAK_SECS=14400 cargo test --release --test soak -- --ignored --nocapture
+// 再現したいときはCHIBIVOX_SOAK_SEEDに、開始時に表示した値を渡す
+
+mod common;
+
+use chibivox::{SynthesisOptions, VoicevoxCore};
+use std::{
+    env, fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const WORDS: &[&str] = &[
+    "こんにちは",
+    "今日は",
+    "いい天気ですね",
+    "音声合成",
+    "テスト",
+    "東京特許許可局",
+    "123",
+    "ABC",
+    "ずんだもち",
+    "ですか",
+    "、",
+    "。",
+    "？",
+    "！",
+    "ー",
+];
+
+const KANA: &[&str] = &[
+    "コンニチワ'",
+    "ア'/イ'ウ",
+    "テ'_スト、ソーク'",
+    "ヨロシクオネガイシマ'_ス？",
+];
+
+#[test]
+#[ignore]
+fn library() {
+    let core = VoicevoxCore::new(common::test_model_dir()).unwrap();
+    let count = AtomicUsize::new(0);
+    let seed = seed();
+    let mut monitor = Monitor::new(std::process::id());
+    thread::scope(|scope| {
+        for worker in 0..4 {
+            let (core, count) = (&core, &count);
+            scope.spawn(move || {
+                let mut rng = Rng::new(seed ^ (worker + 1));
+                let until = Instant::now() + duration();
+                while Instant::now() < until {
+                    synthesize(core, &mut rng);
+                    count.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+        monitor.watch(&count);
+    });
+    monitor.check();
+}
+
+// 実際のバイナリでサーバーを立て、モデルの読み込み直しを含めて繰り返し問い合わせる
+#[cfg(feature = "server")]
+#[test]
+#[ignore]
+fn server() {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        process::{Command, Stdio},
+    };
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chibivox"))
+        .arg("--model-dir")
+        .arg(common::test_model_dir())
+        .args(["serve", "--port", &port.to_string(), "--idle-unload", "2s"])
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let request = |method: &str, path: &str, body: &str| -> Option<u16> {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).ok()?;
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .ok()?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).ok()?;
+        String::from_utf8_lossy(&response)
+            .split(' ')
+            .nth(1)?
+            .parse()
+            .ok()
+    };
+    // 起動してモデルを読み込むまで待つ
+    let started = Instant::now();
+    while request("GET", "/ready", "") != Some(200) {
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "server did not start"
+        );
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let count = AtomicUsize::new(0);
+    let seed = seed();
+    let mut monitor = Monitor::new(child.id());
+    thread::scope(|scope| {
+        for worker in 0..4 {
+            let (request, count) = (&request, &count);
+            scope.spawn(move || {
+                let mut rng = Rng::new(seed ^ (worker + 1));
+                let until = Instant::now() + duration();
+                while Instant::now() < until {
+                    let status = match rng.below(4) {
+                        0 => request("POST", "/tts?speaker=0", &text(&mut rng)),
+                        1 => {
+                            let text = percent_encode(&text(&mut rng));
+                            request("POST", &format!("/audio_query?speaker=0&text={text}"), "")
+                        }
+                        2 => request("GET", "/speakers", ""),
+                        // 壊れたリクエストでも漏れないこと
+                        _ => request("POST", "/synthesis?speaker=0", "{"),
+                    };
+                    assert!(status.is_some(), "server stopped responding");
+                    count.fetch_add(1, Ordering::Relaxed);
+                    // 時々手を止めて、モデルを手放させる
+                    if rng.below(500) == 0 {
+                        thread::sleep(Duration::from_secs(3));
+                    }
+                }
+            });
+        }
+        monitor.watch(&count);
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    monitor.check();
+}
+
+fn synthesize(core: &VoicevoxCore, rng: &mut Rng) {
+    let options = SynthesisOptions {
+        speed_scale: 0.5 + rng.unit() * 1.5,
+        pitch_scale: rng.unit() * 0.3 - 0.15,
+        intonation_scale: rng.unit() * 2.,
+        volume_scale: rng.unit() * 2.,
+        enable_interrogative_upspeak: rng.below(2) == 0,
+        ..Default::default()
+    };
+    // 空文字列や記号だけの入力などで失敗するのは構わない。落ちたり漏れたりしないことを見る
+    let _ = match rng.below(3) {
+        0 => core.tts_with_options(&text(rng), 0, &options).map(drop),
+        1 => core
+            .audio_query_with_options(&text(rng), 0, &options)
+            .and_then(|audio_query| core.synthesis_decode_only(&audio_query, 0))
+            .map(drop),
+        _ => core.tts_from_kana(KANA[rng.below(KANA.len())], 0).map(drop),
+    };
+}
+
+fn text(rng: &mut Rng) -> String {
+    (0..1 + rng.below(20))
+        .map(|_| WORDS[rng.below(WORDS.len())])
+        .collect()
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes().map(|byte| format!("%{byte:02X}")).collect()
+}
+
+fn duration() -> Duration {
+    let secs = env::var("CHIBIVOX_SOAK_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(60);
+    Duration::from_secs(secs)
+}
+
+fn seed() -> u64 {
+    let seed = env::var("CHIBIVOX_SOAK_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64
+        });
+    eprintln!("CHIBIVOX_SOAK_SEED={seed}");
+    seed
+}
+
+// テストのためだけに乱数のクレートを足さないよう、xorshiftで済ませる
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // 0だとずっと0のままになる
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn unit(&mut self) -> f32 {
+        (self.next() % 1_000_000) as f32 / 1_000_000.
+    }
+}
+
+// 対象のプロセスのRSSとファイルディスクリプタの数を、一定間隔で記録する
+// /procを読むので、Linux以外では記録せずに合成だけを続ける
+struct Monitor {
+    pid: u32,
+    started: Instant,
+    samples: Vec<(Duration, u64, usize)>,
+}
+
+impl Monitor {
+    fn new(pid: u32) -> Self {
+        Self {
+            pid,
+            started: Instant::now(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn watch(&mut self, count: &AtomicUsize) {
+        let until = self.started + duration();
+        while Instant::now() < until {
+            thread::sleep(
+                until
+                    .saturating_duration_since(Instant::now())
+                    .min(Duration::from_secs(10)),
+            );
+            let elapsed = self.started.elapsed();
+            let count = count.load(Ordering::Relaxed);
+            match (self.rss_kib(), self.fds()) {
+                (Some(rss), Some(fds)) => {
+                    eprintln!(
+                        "{:>6}s {count:>8} syntheses  rss {rss} KiB  fds {fds}",
+                        elapsed.as_secs()
+                    );
+                    self.samples.push((elapsed, rss, fds));
+                }
+                _ => eprintln!("{:>6}s {count:>8} syntheses", elapsed.as_secs()),
+            }
+        }
+    }
+
+    // 読み込みやキャッシュで増える最初の1割を除き、その後の増え方を見る
+    fn check(&self) {
+        let warmed_up = duration() / 10;
+        let Some(&(_, base_rss, base_fds)) = self
+            .samples
+            .iter()
+            .find(|(elapsed, _, _)| *elapsed >= warmed_up)
+        else {
+            return;
+        };
+        let &(_, rss, fds) = self.samples.last().unwrap();
+        assert!(
+            rss <= base_rss + base_rss / 2 + 64 * 1024,
+            "rss grew from {base_rss} KiB to {rss} KiB"
+        );
+        assert!(fds <= base_fds + 16, "fds grew from {base_fds} to {fds}");
+    }
+
+    fn rss_kib(&self) -> Option<u64> {
+        let status = fs::read_to_string(format!("/proc/{}/status", self.pid)).ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    fn fds(&self) -> Option<usize> {
+        Some(fs::read_dir(format!("/proc/{}/fd", self.pid)).ok()?.count())
+    }
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use chibivox::{InitializeOptions, TermsNotAcknowledgedError, VoicevoxCore};
+use std::fs;
+
+// ダミーモデルのmetas.jsonに、商用利用を認めない規約を書き足す
+fn core(acknowledge_terms: bool) -> VoicevoxCore {
+    let dir = common::test_model_dir();
+    fs::write(
+        dir.join("metas.json"),
+        r#"[{"name":"test","styles":[{"name":"normal","id":0}],"speaker_uuid":"00000000-0000-0000-0000-000000000000","version":"0.0.0","terms":{"credit":"VOICEVOX:test","commercial":false}}]"#,
+    )
+    .unwrap();
+    let options = InitializeOptions {
+        acknowledge_terms,
+        ..Default::default()
+    };
+    VoicevoxCore::new_with_options(dir, &options).unwrap()
+}
+
+#[test]
+fn refuses_until_acknowledged() {
+    let err = core(false).tts("こんにちは", 0).unwrap_err();
+    assert!(err.downcast_ref::<TermsNotAcknowledgedError>().is_some());
+
+    assert!(core(true).tts("こんにちは", 0).is_ok());
+}
+
+#[test]
+fn exposes_credit() {
+    let core = core(false);
+    let terms = core.usage_terms(0).unwrap();
+    assert_eq!(terms.credit.as_deref(), Some("VOICEVOX:test"));
+    assert!(!terms.commercial);
+    assert!(core.usage_terms(1).is_none());
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use chibivox::{
+    frame_count, InitializeOptions, Progress, ProsodySmoother, Synthesis, SynthesisOptions,
+    UserDict, UserDictWord, VoicevoxCore, SAMPLING_RATE,
+};
+
+fn core() -> VoicevoxCore {
+    VoicevoxCore::new_with_options(common::test_model_dir(), &Default::default()).unwrap()
+}
+
+#[test]
+fn loads_models_and_metas() {
+    let core = core();
+    assert!(!core.is_rule_based_duration());
+    assert!(!core.is_rule_based_intonation());
+    assert_eq!(core.metas().len(), 1);
+}
+
+#[test]
+fn predicts_through_onnx() {
+    // 無声化されるモーラを含まない文
+    let query = core().audio_query("ありがとう", 0).unwrap();
+    let moras = query.accent_phrases.iter().flat_map(|phrase| &phrase.moras);
+    for mora in moras {
+        // ダミーモデルは音素長0.1、音高5.5を返す
+        assert_eq!(mora.vowel_length, 0.1, "{}", mora.text);
+        assert_eq!(mora.pitch, 5.5, "{}", mora.text);
+    }
+}
+
+#[test]
+fn synthesizes_end_to_end() {
+    let core = core();
+    let query = core.audio_query("こんにちは", 0).unwrap();
+    let audio = core.synthesis(&query, 0).unwrap();
+
+    assert_eq!(audio.sample_rate, SAMPLING_RATE);
+    assert_eq!(
+        audio.samples.len(),
+        frame_count(&query, query.speed_scale) * 256
+    );
+}
+
+#[test]
+fn style_defaults_yield_to_user_options() {
+    let model_dir = common::test_model_dir();
+    std::fs::write(
+        model_dir.join("metas.json"),
+        r#"[{"name":"test","styles":[{"name":"slow","id":0,"defaults":{"speed_scale":1.2}}],"speaker_uuid":"","version":""}]"#,
+    )
+    .unwrap();
+    let core = VoicevoxCore::new(&model_dir).unwrap();
+
+    assert_eq!(core.default_options(0).speed_scale, 1.2);
+    assert_eq!(core.audio_query("ありがとう", 0).unwrap().speed_scale, 1.2);
+    let options = SynthesisOptions::default();
+    let audio_query = core
+        .audio_query_with_options("ありがとう", 0, &options)
+        .unwrap();
+    assert_eq!(audio_query.speed_scale, 1.);
+}
+
+#[test]
+fn intermediates_match_decoder_input() {
+    let core = core();
+    let options = SynthesisOptions::default();
+    let intermediates = core.intermediates("ありがとう", 0, &options).unwrap();
+    let query = core
+        .audio_query_with_options("ありがとう", 0, &options)
+        .unwrap();
+
+    assert!(!intermediates.labels.is_empty());
+    assert_eq!(intermediates.phonemes.first().unwrap(), "pau");
+    assert_eq!(intermediates.phonemes.last().unwrap(), "pau");
+    assert_eq!(
+        intermediates.phoneme_ids.len(),
+        intermediates.phonemes.len()
+    );
+    assert_eq!(
+        intermediates.phoneme_lengths.len(),
+        intermediates.phonemes.len()
+    );
+    assert_eq!(
+        intermediates.f0.len(),
+        frame_count(&query, query.speed_scale)
+    );
+}
+
+#[cfg(feature = "unstable")]
+#[test]
+fn voice_change_follows_lab_timing() {
+    use chibivox::AudioBuffer;
+    use std::f32::consts::PI;
+
+    // 200Hzの正弦波を「あ」と読んだ録音とみなす
+    let recording = AudioBuffer::mono(
+        (0..SAMPLING_RATE / 2)
+            .map(|i| (2. * PI * 200. * i as f32 / SAMPLING_RATE as f32).sin() * 0.5)
+            .collect(),
+        SAMPLING_RATE,
+    );
+    let lab = "0 1000000 silB\n1000000 4000000 a\n4000000 5000000 silE\n";
+    let audio = core().voice_change(&recording, lab, 0, 0.).unwrap();
+
+    // 0.5秒分のフレーム
+    assert_eq!(audio.samples.len(), 47 * 256);
+    assert!(core()
+        .voice_change(&recording, "0 1000000 xyz\n", 0, 0.)
+        .is_err());
+}
+
+// tractは書き出さない
+#[cfg(not(feature = "wasm"))]
+#[test]
+fn caches_optimized_models() {
+    let model_dir = common::test_model_dir();
+    let options = InitializeOptions {
+        cache_optimized_models: true,
+        ..Default::default()
+    };
+    VoicevoxCore::new_with_options(&model_dir, &options).unwrap();
+    let optimized = std::fs::read_dir(&model_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(".decode-0.") && name.ends_with(".optimized.onnx"))
+        .count();
+    assert_eq!(optimized, 1);
+
+    // 二度目は書き出したものを読み込む
+    let core = VoicevoxCore::new_with_options(&model_dir, &options).unwrap();
+    let query = core.audio_query("こんにちは", 0).unwrap();
+    let audio = core.synthesis(&query, 0).unwrap();
+    assert_eq!(
+        audio.samples.len(),
+        frame_count(&query, query.speed_scale) * 256
+    );
+}
+
+#[test]
+fn steps_through_synthesis() {
+    let core = core();
+    let options = SynthesisOptions::default();
+    let mut synthesis = Synthesis::new(&core, "こんにちは", 0, &options);
+    assert!(matches!(synthesis.step().unwrap(), Progress::Working));
+    assert!(synthesis.audio_query().is_none());
+    assert!(matches!(synthesis.step().unwrap(), Progress::Working));
+    let query = synthesis.audio_query().unwrap().clone();
+
+    // 息継ぎが無いので、一度のdecodeで終わる
+    let Progress::Audio(audio) = synthesis.step().unwrap() else {
+        panic!("expected audio");
+    };
+    assert!(synthesis.is_done());
+    assert!(matches!(synthesis.step().unwrap(), Progress::Done));
+    assert_eq!(audio.samples, core.synthesis(&query, 0).unwrap().samples);
+}
+
+#[test]
+fn mora_gain_scales_after_decode() {
+    let core = core();
+    let mut query = core.audio_query("こんにちは", 0).unwrap();
+    let original = core.synthesis(&query, 0).unwrap();
+    for mora in query
+        .accent_phrases
+        .iter_mut()
+        .flat_map(|phrase| &mut phrase.moras)
+    {
+        mora.gain = Some(0.5);
+    }
+    query.validate().unwrap();
+    let quieter = core.synthesis(&query, 0).unwrap();
+
+    // 前後の無音はダミーモデルでは0なので、全体がちょうど半分になる
+    assert_eq!(quieter.samples.len(), original.samples.len());
+    for (quieter, original) in quieter.samples.iter().zip(&original.samples) {
+        assert!((quieter - original * 0.5).abs() < 1e-6);
+    }
+    // gainは書き出したAudioQueryにも残る
+    assert!(serde_json::to_string(&query)
+        .unwrap()
+        .contains("\"gain\":0.5"));
+}
+
+#[test]
+fn smooths_prosody_across_sentences() {
+    let core = core();
+    let mut smoother = ProsodySmoother::new(0.5);
+    let mut first = core.audio_query("ありがとう", 0).unwrap();
+    smoother.apply(&mut first);
+    // 最初の文は寄せる先が無いので変えない
+    for mora in first.accent_phrases.iter().flat_map(|phrase| &phrase.moras) {
+        assert_eq!((mora.vowel_length, mora.pitch), (0.1, 5.5));
+    }
+
+    // 高く遅く予測された次の文を、前の文との間まで寄せる
+    let mut second = core.audio_query("ありがとう", 0).unwrap();
+    for mora in second
+        .accent_phrases
+        .iter_mut()
+        .flat_map(|phrase| &mut phrase.moras)
+    {
+        if let Some(consonant_length) = &mut mora.consonant_length {
+            *consonant_length = 0.4;
+        }
+        mora.vowel_length = 0.4;
+        mora.pitch = 6.5;
+    }
+    smoother.apply(&mut second);
+    for mora in second
+        .accent_phrases
+        .iter()
+        .flat_map(|phrase| &phrase.moras)
+    {
+        assert!(
+            (mora.vowel_length - 0.2).abs() < 1e-6,
+            "{}",
+            mora.vowel_length
+        );
+        assert!((mora.pitch - 6.).abs() < 1e-6, "{}", mora.pitch);
+    }
+}
+
+#[test]
+fn reads_user_dict_words() {
+    let core = core();
+    let mut user_dict = UserDict::new();
+    user_dict
+        .add_word(UserDictWord::new("鳴花ヒメ", "ナキカヒメ", 3))
+        .unwrap();
+    core.set_user_dict(user_dict);
+
+    let accent_phrases = core.create_accent_phrases("鳴花ヒメ、です").unwrap();
+    let kana: String = accent_phrases[0]
+        .moras
+        .iter()
+        .map(|mora| mora.text.as_str())
+        .collect();
+    assert_eq!(kana, "ナキカヒメ");
+    assert_eq!(accent_phrases[0].accent, 3);
+    assert!(accent_phrases[0].pause_mora.is_some());
+    assert!(accent_phrases.len() > 1);
+
+    // カタカナ以外の読みと、モーラ数を超えるアクセント型は断る
+    let mut user_dict = UserDict::new();
+    assert!(user_dict
+        .add_word(UserDictWord::new("a", "えー", 0))
+        .is_err());
+    assert!(user_dict
+        .add_word(UserDictWord::new("a", "エー", 3))
+        .is_err());
+}
@@ -0,0 +1,100 @@
+// serveの/user_dict_wordで登録した語を、再起動せずにテキスト解析で使い、ファイルに保存すること
+#![cfg(feature = "server")]
+
+mod common;
+
+use common::server::{request, spawn_server};
+use std::fs;
+
+#[test]
+fn registers_words_without_restart() {
+    let model_dir = common::test_model_dir();
+    let user_dict_path = model_dir.join("user_dict.json");
+    let (mut child, port) = spawn_server(
+        &model_dir,
+        &["--user-dict", user_dict_path.to_str().unwrap()],
+    );
+
+    // 鳴花ヒメ
+    let surface = "%E9%B3%B4%E8%8A%B1%E3%83%92%E3%83%A1";
+    // ナキカヒメ
+    let pronunciation = "%E3%83%8A%E3%82%AD%E3%82%AB%E3%83%92%E3%83%A1";
+    let added = request(
+        port,
+        "POST",
+        &format!("/user_dict_word?surface={surface}&pronunciation={pronunciation}&accent_type=3"),
+        "",
+    )
+    .unwrap();
+    assert!(added.contains(" 200 "), "{added}");
+    let uuid = added.rsplit("\r\n\r\n").next().unwrap().trim_matches('"');
+    let accent_phrases = request(
+        port,
+        "POST",
+        &format!("/accent_phrases?text={surface}&speaker=0"),
+        "",
+    )
+    .unwrap();
+    assert!(accent_phrases.contains("\"accent\":3,"), "{accent_phrases}");
+    let saved = fs::read_to_string(&user_dict_path).unwrap();
+    assert!(
+        saved.contains(uuid) && saved.contains("ナキカヒメ"),
+        "{saved}"
+    );
+
+    // ひらがなの読みは断る
+    let invalid = request(
+        port,
+        "POST",
+        &format!("/user_dict_word?surface={surface}&pronunciation=%E3%81%82&accent_type=0"),
+        "",
+    )
+    .unwrap();
+    assert!(invalid.contains(" 422 "), "{invalid}");
+
+    let deleted = request(port, "DELETE", &format!("/user_dict_word/{uuid}"), "").unwrap();
+    assert!(deleted.contains(" 204 "), "{deleted}");
+    let user_dict = request(port, "GET", "/user_dict", "").unwrap();
+    assert!(user_dict.ends_with("{}"), "{user_dict}");
+    let missing = request(port, "DELETE", &format!("/user_dict_word/{uuid}"), "").unwrap();
+    assert!(missing.contains(" 422 "), "{missing}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+// --cacheでも、語を登録した後は前の読みで合成したものを返さないこと
+#[test]
+fn cache_follows_user_dict() {
+    let model_dir = common::test_model_dir();
+    let user_dict_path = model_dir.join("user_dict.json");
+    let cache_dir = model_dir.join("cache");
+    let (mut child, port) = spawn_server(
+        &model_dir,
+        &[
+            "--user-dict",
+            user_dict_path.to_str().unwrap(),
+            "--cache",
+            cache_dir.to_str().unwrap(),
+        ],
+    );
+
+    let tts = || request(port, "POST", "/tts?speaker=0", "鳴花ヒメ").unwrap();
+    let before = tts();
+    assert!(before.contains("X-Cache: miss"), "{before}");
+    assert!(tts().contains("X-Cache: hit"));
+    let added = request(
+        port,
+        "POST",
+        "/user_dict_word?surface=%E9%B3%B4%E8%8A%B1%E3%83%92%E3%83%A1&pronunciation=%E3%83%8A%E3%82%AD%E3%82%AB%E3%83%92%E3%83%A1&accent_type=3",
+        "",
+    )
+    .unwrap();
+    assert!(added.contains(" 200 "), "{added}");
+    let after = tts();
+    assert!(after.contains("X-Cache: miss"), "{after}");
+    assert!(tts().contains("X-Cache: hit"));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
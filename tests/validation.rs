@@ -0,0 +1,90 @@
+// 外から受け取ったAudioQueryの値の範囲を、Strictでは断り、Lenientでは範囲の端に寄せること
+use chibivox::{AccentPhraseModel, AudioQueryModel, MoraModel, ValidationMode, SAMPLING_RATE};
+
+fn query() -> AudioQueryModel {
+    AudioQueryModel {
+        accent_phrases: vec![AccentPhraseModel {
+            moras: vec![MoraModel {
+                text: "カ".into(),
+                consonant: Some("k".into()),
+                consonant_length: Some(0.05),
+                vowel: "a".into(),
+                vowel_length: 0.1,
+                pitch: 5.,
+                gain: None,
+            }],
+            accent: 1,
+            pause_mora: None,
+            is_interrogative: false,
+        }],
+        speed_scale: 1.,
+        pitch_scale: 0.,
+        intonation_scale: 1.,
+        volume_scale: 1.,
+        pre_phoneme_length: 0.1,
+        post_phoneme_length: 0.1,
+        output_sampling_rate: SAMPLING_RATE,
+        output_stereo: false,
+        kana: None,
+    }
+}
+
+// 範囲外の値を一つずつ入れたもの
+fn out_of_range() -> Vec<AudioQueryModel> {
+    let edits: &[fn(&mut AudioQueryModel)] = &[
+        |query| query.speed_scale = 1e-30,
+        |query| query.speed_scale = f32::INFINITY,
+        |query| query.pitch_scale = 1.,
+        |query| query.intonation_scale = f32::NAN,
+        |query| query.volume_scale = 3.,
+        |query| query.pre_phoneme_length = 1e9,
+        |query| query.output_sampling_rate = u32::MAX,
+        |query| query.accent_phrases[0].moras[0].vowel_length = 1e9,
+        |query| query.accent_phrases[0].moras[0].consonant_length = Some(1e9),
+    ];
+    edits
+        .iter()
+        .map(|edit| {
+            let mut query = query();
+            edit(&mut query);
+            query
+        })
+        .collect()
+}
+
+#[test]
+fn strict_rejects_out_of_range_values() {
+    assert!(query().validate().is_ok());
+    for query in out_of_range() {
+        assert!(
+            query.validate().is_err(),
+            "{}",
+            serde_json::to_string(&query).unwrap()
+        );
+    }
+}
+
+#[test]
+fn lenient_clamps_out_of_range_values() {
+    for mut query in out_of_range() {
+        let warnings = query.validate_with_mode(ValidationMode::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        query.validate().unwrap();
+    }
+
+    let mut clamped = query();
+    clamped.speed_scale = 1e-30;
+    clamped.pitch_scale = -1.;
+    clamped.volume_scale = 3.;
+    clamped.accent_phrases[0].moras[0].vowel_length = 1e9;
+    clamped.validate_with_mode(ValidationMode::Lenient).unwrap();
+    assert_eq!(clamped.speed_scale, 0.5);
+    assert_eq!(clamped.pitch_scale, -0.15);
+    assert_eq!(clamped.volume_scale, 2.);
+    assert_eq!(clamped.accent_phrases[0].moras[0].vowel_length, 10.);
+    // 有限でないものは既定の値に戻す
+    let mut reset = query();
+    reset.intonation_scale = f32::NAN;
+    reset.validate_with_mode(ValidationMode::Lenient).unwrap();
+    assert_eq!(reset.intonation_scale, 1.);
+}
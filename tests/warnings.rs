@@ -0,0 +1,26 @@
+use chibivox::{AudioBuffer, Warning, Warnings, SAMPLING_RATE};
+
+#[test]
+fn check_clipping_counts_samples_beyond_full_scale() {
+    let mut warnings = Warnings::new();
+    warnings.check_clipping(&AudioBuffer::mono(vec![0.5, -1., 1.], SAMPLING_RATE));
+    assert!(warnings.is_empty());
+
+    warnings.check_clipping(&AudioBuffer::mono(vec![1.5, 0., -2.], SAMPLING_RATE));
+    assert_eq!(
+        warnings.into_iter().collect::<Vec<_>>(),
+        [Warning::ClippedSamples { count: 2, peak: 2. }]
+    );
+}
+
+#[test]
+fn serializes_with_kind_tag() {
+    let mut warnings = Warnings::new();
+    warnings.push(Warning::UnknownWord {
+        surface: "ほげ".into(),
+    });
+    assert_eq!(
+        serde_json::to_string(&warnings).unwrap(),
+        r#"[{"kind":"unknown_word","surface":"ほげ"}]"#
+    );
+}
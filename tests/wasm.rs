@@ -0,0 +1,39 @@
+// ファイルを読まずに、バイト列からtractで合成できること
+#![cfg(feature = "wasm")]
+
+mod common;
+
+use chibivox::{InitializeOptions, VoicevoxCore, SAMPLING_RATE};
+use std::fs;
+
+#[test]
+fn synthesizes_from_bytes() {
+    let model_dir = common::test_model_dir();
+    let read = |name: &str| fs::read(model_dir.join(name)).unwrap();
+    let core = VoicevoxCore::from_bytes(
+        &read("decode-0.onnx"),
+        Some(&read("predict_duration-0.onnx")),
+        Some(&read("predict_intonation-0.onnx")),
+        &fs::read_to_string(model_dir.join("metas.json")).unwrap(),
+        &InitializeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(core.metas().len(), 1);
+
+    let query = core.audio_query("こんにちは", 0).unwrap();
+    let audio = core.synthesis(&query, 0).unwrap();
+    assert_eq!(audio.sample_rate, SAMPLING_RATE);
+    assert!(!audio.samples.is_empty());
+
+    // 予測モデルが無ければ規則で代用する
+    let core = VoicevoxCore::from_bytes(
+        &read("decode-0.onnx"),
+        None,
+        None,
+        "[]",
+        &InitializeOptions::default(),
+    )
+    .unwrap();
+    assert!(core.is_rule_based_duration());
+    core.tts("こんにちは", 0).unwrap();
+}
@@ -0,0 +1,83 @@
+use chibivox::{watermark, AudioBuffer, SAMPLING_RATE};
+use std::f32::consts::TAU;
+
+// 音量の揺れる倍音の多い信号を、声の代わりにする
+fn voice_like(seconds: f32) -> AudioBuffer {
+    let sample_rate = SAMPLING_RATE as f32;
+    let samples = (0..(sample_rate * seconds) as usize)
+        .map(|i| {
+            let t = i as f32 / sample_rate;
+            let f0 = 150. + 30. * (t * 3.).sin();
+            let envelope = (t * 4.).sin().abs();
+            (1..30)
+                .map(|harmonic| (TAU * f0 * harmonic as f32 * t).sin() * 0.3 / harmonic as f32)
+                .sum::<f32>()
+                * envelope
+        })
+        .collect();
+    AudioBuffer::mono(samples, SAMPLING_RATE)
+}
+
+// 16bitで書き出して読み直した後でも検出できる
+fn round_trip(audio: &AudioBuffer) -> AudioBuffer {
+    AudioBuffer::mono(
+        audio
+            .to_i16()
+            .into_iter()
+            .map(|sample| sample as f32 / i16::MAX as f32)
+            .collect(),
+        audio.sample_rate,
+    )
+}
+
+#[test]
+fn detects_only_with_the_same_key() {
+    let audio = voice_like(1.);
+    let marked = round_trip(&watermark::embed(&audio, "secret"));
+
+    assert!(watermark::detect(&marked, "secret") > watermark::DETECTION_THRESHOLD);
+    assert!(watermark::detect(&marked, "other").abs() < watermark::DETECTION_THRESHOLD);
+    assert!(
+        watermark::detect(&round_trip(&audio), "secret").abs() < watermark::DETECTION_THRESHOLD
+    );
+}
+
+#[test]
+fn is_quiet_relative_to_the_signal() {
+    let audio = voice_like(1.);
+    let marked = watermark::embed(&audio, "secret");
+    let signal: f32 = audio.samples.iter().map(|s| s * s).sum();
+    let mark: f32 = marked
+        .samples
+        .iter()
+        .zip(&audio.samples)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum();
+    assert!(mark < signal * 1e-3);
+}
+
+#[test]
+fn stereo_is_marked_on_both_channels() {
+    let audio = voice_like(1.).to_stereo();
+    let marked = watermark::embed(&audio, "secret");
+    assert_eq!(marked.channels, 2);
+    assert!(watermark::detect(&marked, "secret") > watermark::DETECTION_THRESHOLD);
+}
+
+#[test]
+fn embedder_continues_across_chunks() {
+    let audio = voice_like(1.);
+    let whole = watermark::embed(&audio, "secret");
+    // decoderの出力と同じく、256サンプルの倍数で区切る
+    let mut embedder = watermark::Embedder::new("secret");
+    let chunked: Vec<f32> = audio
+        .samples
+        .chunks(256 * 30)
+        .flat_map(|chunk| {
+            embedder
+                .embed(&AudioBuffer::mono(chunk.to_vec(), SAMPLING_RATE))
+                .samples
+        })
+        .collect();
+    assert_eq!(chunked, whole.samples);
+}
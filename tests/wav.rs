@@ -0,0 +1,155 @@
+use chibivox::wav::{
+    append_chunk, decode_wav, encode_samples, encode_wav, info_chunk, wav_header, SampleFormat,
+    WavWriter, STREAMING_DATA_SIZE,
+};
+use std::io::Cursor;
+
+#[test]
+fn i16_header_is_byte_exact() {
+    let header = wav_header(24000, 1, SampleFormat::I16, 4);
+    #[rustfmt::skip]
+    let expected: [u8; 44] = [
+        b'R', b'I', b'F', b'F', 40, 0, 0, 0,
+        b'W', b'A', b'V', b'E',
+        b'f', b'm', b't', b' ', 16, 0, 0, 0,
+        1, 0, // PCM
+        1, 0, // mono
+        0xc0, 0x5d, 0, 0, // 24000
+        0x80, 0xbb, 0, 0, // 48000
+        2, 0,
+        16, 0,
+        b'd', b'a', b't', b'a', 4, 0, 0, 0,
+    ];
+    assert_eq!(header, expected);
+}
+
+#[test]
+fn f32_header_is_byte_exact() {
+    let header = wav_header(24000, 2, SampleFormat::F32, 16);
+    #[rustfmt::skip]
+    let expected: [u8; 44] = [
+        b'R', b'I', b'F', b'F', 52, 0, 0, 0,
+        b'W', b'A', b'V', b'E',
+        b'f', b'm', b't', b' ', 16, 0, 0, 0,
+        3, 0, // IEEE float
+        2, 0, // stereo
+        0xc0, 0x5d, 0, 0, // 24000
+        0x00, 0xee, 0x02, 0, // 192000
+        8, 0,
+        32, 0,
+        b'd', b'a', b't', b'a', 16, 0, 0, 0,
+    ];
+    assert_eq!(header, expected);
+}
+
+#[test]
+fn samples_are_little_endian() {
+    assert_eq!(
+        encode_samples(&[0.5, -1.], SampleFormat::I16),
+        [0x00, 0x40, 0x01, 0x80]
+    );
+    assert_eq!(
+        encode_samples(&[1.], SampleFormat::F32),
+        [0x00, 0x00, 0x80, 0x3f]
+    );
+}
+
+#[test]
+fn i16_samples_are_clipped() {
+    assert_eq!(
+        encode_samples(&[2., -2.], SampleFormat::I16),
+        encode_samples(&[1., -1.], SampleFormat::I16)
+    );
+}
+
+#[test]
+fn wav_is_header_followed_by_data() {
+    let wav = encode_wav(&[0.25, -0.25], 24000, 1, SampleFormat::F32);
+    assert_eq!(wav.len(), 44 + 8);
+    assert_eq!(&wav[..44], wav_header(24000, 1, SampleFormat::F32, 8));
+    assert_eq!(
+        &wav[44..],
+        encode_samples(&[0.25, -0.25], SampleFormat::F32)
+    );
+}
+
+#[test]
+fn i24_samples_are_little_endian() {
+    assert_eq!(
+        encode_samples(&[0.5, -1.], SampleFormat::I24),
+        [0x00, 0x00, 0x40, 0x01, 0x00, 0x80]
+    );
+}
+
+#[test]
+fn writer_fixes_header_on_finalize() {
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), 24000, 1, SampleFormat::I16).unwrap();
+    writer.write_samples(&[0.5]).unwrap();
+    writer.write_samples(&[-1.]).unwrap();
+    let wav = writer.finalize().unwrap().into_inner();
+
+    assert_eq!(wav, encode_wav(&[0.5, -1.], 24000, 1, SampleFormat::I16));
+}
+
+#[test]
+fn info_chunk_follows_data() {
+    let chunk = info_chunk("VOICEVOX:test");
+    // "VOICEVOX:test\0" は14バイトなので詰め物は要らない
+    assert_eq!(&chunk[..12], b"LIST\x1a\0\0\0INFO");
+    assert_eq!(&chunk[12..20], b"ICMT\x0e\0\0\0");
+    assert_eq!(info_chunk("ab").len(), 24);
+
+    let mut wav = encode_wav(&[0.5], 24000, 1, SampleFormat::I16);
+    append_chunk(&mut wav, &chunk);
+    assert_eq!(&wav[4..8], &(wav.len() as u32 - 8).to_le_bytes());
+    assert_eq!(decode_wav(&wav).unwrap().samples.len(), 1);
+
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), 24000, 1, SampleFormat::I16).unwrap();
+    writer.write_samples(&[0.5]).unwrap();
+    assert_eq!(
+        writer.finalize_with_chunk(&chunk).unwrap().into_inner(),
+        wav
+    );
+}
+
+#[test]
+fn writer_streams_with_unknown_length() {
+    let mut writer = WavWriter::new(Vec::new(), 24000, 1, SampleFormat::F32).unwrap();
+    writer.write_samples(&[0.25]).unwrap();
+    let wav = writer.into_inner().unwrap();
+
+    assert_eq!(
+        &wav[..44],
+        wav_header(24000, 1, SampleFormat::F32, STREAMING_DATA_SIZE)
+    );
+    assert_eq!(&wav[44..], encode_samples(&[0.25], SampleFormat::F32));
+}
+
+#[test]
+fn decodes_what_it_encodes() {
+    let samples = [0.5, -0.25, 0., 0.75];
+    for format in [SampleFormat::I16, SampleFormat::I24, SampleFormat::F32] {
+        let audio = decode_wav(&encode_wav(&samples, 48000, 2, format)).unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (48000, 2));
+        for (decoded, sample) in audio.samples.iter().zip(samples) {
+            assert!(
+                (decoded - sample).abs() < 1e-4,
+                "{format:?}: {decoded} != {sample}"
+            );
+        }
+    }
+}
+
+#[test]
+fn decode_skips_unknown_chunks() {
+    let wav = encode_wav(&[0.25], 24000, 1, SampleFormat::F32);
+    let mut with_list = wav[..36].to_vec();
+    with_list.extend_from_slice(b"LIST");
+    with_list.extend_from_slice(&3u32.to_le_bytes());
+    // 奇数長のチャンクは1バイト詰め物が入る
+    with_list.extend_from_slice(&[0; 4]);
+    with_list.extend_from_slice(&wav[36..]);
+
+    assert_eq!(decode_wav(&with_list).unwrap().samples, [0.25]);
+    assert!(decode_wav(b"not a wav").is_err());
+}